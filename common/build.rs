@@ -0,0 +1,32 @@
+use std::{path::Path, process::Command};
+
+fn main() {
+    let commit = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_owned());
+    let profile = std::env::var("PROFILE").unwrap_or_else(|_| "unknown".to_owned());
+
+    println!("cargo:rustc-env=QBD_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=QBD_BUILD_PROFILE={}", profile);
+
+    // HEAD only changes when switching branches, a commit on the current branch rewrites the
+    // branch's own ref, or `packed-refs` once refs get packed.
+    let mut watched = vec!["HEAD".to_owned(), "packed-refs".to_owned()];
+    watched.extend(git(&["symbolic-ref", "-q", "HEAD"]));
+    for file in watched {
+        if let Some(path) = git(&["rev-parse", "--git-path", &file]) {
+            // Watching a missing file would rebuild every time.
+            if Path::new(&path).exists() {
+                println!("cargo:rerun-if-changed={}", path);
+            }
+        }
+    }
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|output| output.trim().to_owned())
+}
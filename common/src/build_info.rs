@@ -0,0 +1,42 @@
+use crate::consts::APP_NAME;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("QBD_GIT_COMMIT");
+pub const BUILD_PROFILE: &str = env!("QBD_BUILD_PROFILE");
+
+/// Bumped whenever clients and servers built from different versions can no longer talk to each
+/// other.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The graphics backends compiled in, as enabled through the `game_window` features.
+pub fn graphics_backends() -> Vec<&'static str> {
+    let mut backends = Vec::new();
+    if cfg!(feature = "vulkan") {
+        backends.push("vulkan");
+    }
+    if cfg!(feature = "metal") {
+        backends.push("metal");
+    }
+    if cfg!(feature = "dx12") {
+        backends.push("dx12");
+    }
+    if cfg!(feature = "gl") {
+        backends.push("gl");
+    }
+    if backends.is_empty() {
+        backends.push("empty");
+    }
+    backends
+}
+
+/// One line describing this build, e.g. `QBD 0.1.0 (3f2a9c1, release, vulkan)`.
+pub fn summary() -> String {
+    format!(
+        "{} {} ({}, {}, {})",
+        APP_NAME,
+        VERSION,
+        GIT_COMMIT,
+        BUILD_PROFILE,
+        graphics_backends().join("+")
+    )
+}
@@ -1 +1,2 @@
 pub const APP_NAME: &str = "QBD";
+pub const TICKS_PER_SECOND: u32 = 20;
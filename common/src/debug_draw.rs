@@ -0,0 +1,234 @@
+//! Immediate mode debug shapes. Call these from anywhere during a frame and the renderer draws
+//! them on top of the scene, for a single frame or until their lifetime runs out.
+
+use crate::{
+    math::{Aabb, Vec3, Vec4},
+    sync::lock_unpoisoned,
+};
+use std::{f32::consts::TAU, sync::Mutex, time::Duration};
+
+/// Pass as a lifetime to draw a shape for the current frame only.
+pub const ONE_FRAME: Duration = Duration::ZERO;
+
+const SPHERE_SEGMENTS: usize = 24;
+
+static SHAPES: Mutex<DebugShapes> = Mutex::new(DebugShapes::new());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugLine {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub color: Vec4,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugLabel {
+    pub position: Vec3,
+    pub text: String,
+    pub color: Vec4,
+}
+
+/// Everything queued for one frame.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugFrame {
+    pub lines: Vec<DebugLine>,
+    pub labels: Vec<DebugLabel>,
+}
+
+struct DebugShapes {
+    lines: Vec<(DebugLine, Duration)>,
+    labels: Vec<(DebugLabel, Duration)>,
+}
+
+impl DebugShapes {
+    const fn new() -> DebugShapes {
+        Self {
+            lines: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+}
+
+pub fn line(start: Vec3, end: Vec3, color: Vec4, lifetime: Duration) {
+    with_shapes(|shapes| {
+        shapes
+            .lines
+            .push((DebugLine { start, end, color }, lifetime))
+    });
+}
+
+/// The twelve edges of `aabb`.
+pub fn aabb(aabb: &Aabb, color: Vec4, lifetime: Duration) {
+    let corner = |x: bool, y: bool, z: bool| {
+        Vec3::new(
+            if x { aabb.max.x } else { aabb.min.x },
+            if y { aabb.max.y } else { aabb.min.y },
+            if z { aabb.max.z } else { aabb.min.z },
+        )
+    };
+
+    with_shapes(|shapes| {
+        for &a in &[false, true] {
+            for &b in &[false, true] {
+                for (start, end) in [
+                    (corner(false, a, b), corner(true, a, b)),
+                    (corner(a, false, b), corner(a, true, b)),
+                    (corner(a, b, false), corner(a, b, true)),
+                ]
+                .iter()
+                {
+                    shapes.lines.push((
+                        DebugLine {
+                            start: *start,
+                            end: *end,
+                            color,
+                        },
+                        lifetime,
+                    ));
+                }
+            }
+        }
+    });
+}
+
+/// A line from `start` to `end` with a head at `end` a fifth of its length.
+pub fn arrow(start: Vec3, end: Vec3, color: Vec4, lifetime: Duration) {
+    let direction = end - start;
+    let length = direction.length();
+    if length == 0.0 {
+        return;
+    }
+
+    let direction = direction * (1.0 / length);
+    let (side, up) = perpendiculars(direction);
+    let head = length * 0.2;
+    let base = end - direction * head;
+
+    line(start, end, color, lifetime);
+    for offset in [side, -side, up, -up].iter() {
+        line(end, base + *offset * (head * 0.5), color, lifetime);
+    }
+}
+
+/// Three circles around `center`, one per axis.
+pub fn sphere(center: Vec3, radius: f32, color: Vec4, lifetime: Duration) {
+    let axes = [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)];
+    let point = |(a, b): (Vec3, Vec3), segment: usize| {
+        let angle = segment as f32 / SPHERE_SEGMENTS as f32 * TAU;
+        center + (a * angle.cos() + b * angle.sin()) * radius
+    };
+
+    with_shapes(|shapes| {
+        for &plane in axes.iter() {
+            for segment in 0..SPHERE_SEGMENTS {
+                shapes.lines.push((
+                    DebugLine {
+                        start: point(plane, segment),
+                        end: point(plane, segment + 1),
+                        color,
+                    },
+                    lifetime,
+                ));
+            }
+        }
+    });
+}
+
+/// Text drawn at a point in the world, facing the screen.
+pub fn label(position: Vec3, text: &str, color: Vec4, lifetime: Duration) {
+    with_shapes(|shapes| {
+        shapes.labels.push((
+            DebugLabel {
+                position,
+                text: text.to_owned(),
+                color,
+            },
+            lifetime,
+        ))
+    });
+}
+
+/// Everything to draw this frame. Shapes whose lifetime runs out after `elapsed` are forgotten,
+/// so each one is drawn at least once.
+pub fn take_frame(elapsed: Duration) -> DebugFrame {
+    with_shapes(|shapes| DebugFrame {
+        lines: advance(&mut shapes.lines, elapsed),
+        labels: advance(&mut shapes.labels, elapsed),
+    })
+}
+
+/// Forgets every queued shape, however long it had left.
+pub fn clear() {
+    with_shapes(|shapes| {
+        shapes.lines.clear();
+        shapes.labels.clear();
+    });
+}
+
+fn advance<T: Clone>(shapes: &mut Vec<(T, Duration)>, elapsed: Duration) -> Vec<T> {
+    let frame = shapes.iter().map(|(shape, _)| shape.clone()).collect();
+    shapes.retain_mut(|(_, lifetime)| match lifetime.checked_sub(elapsed) {
+        Some(left) if left > Duration::ZERO => {
+            *lifetime = left;
+            true
+        }
+        _ => false,
+    });
+
+    frame
+}
+
+fn perpendiculars(direction: Vec3) -> (Vec3, Vec3) {
+    let reference = if direction.y.abs() < 0.99 {
+        Vec3::Y
+    } else {
+        Vec3::X
+    };
+    let side = direction.cross(reference).normalize();
+
+    (side, side.cross(direction))
+}
+
+fn with_shapes<T>(f: impl FnOnce(&mut DebugShapes) -> T) -> T {
+    f(&mut lock_unpoisoned(&SHAPES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(400);
+
+    #[test]
+    fn one_frame_shapes_are_drawn_once() {
+        let mut shapes = vec![("once", ONE_FRAME)];
+
+        assert_eq!(advance(&mut shapes, FRAME), vec!["once"]);
+        assert!(advance(&mut shapes, FRAME).is_empty());
+    }
+
+    #[test]
+    fn shapes_last_until_their_lifetime_runs_out() {
+        let mut shapes = vec![("second", Duration::from_secs(1))];
+
+        assert_eq!(advance(&mut shapes, FRAME), vec!["second"]);
+        assert_eq!(advance(&mut shapes, FRAME), vec!["second"]);
+        // 0.2 s were left, so it's drawn a last time.
+        assert_eq!(advance(&mut shapes, FRAME), vec!["second"]);
+        assert!(advance(&mut shapes, FRAME).is_empty());
+    }
+    #[test]
+    fn take_frame_returns_queued_shapes() {
+        clear();
+        line(Vec3::ZERO, Vec3::X, Vec4::ONE, ONE_FRAME);
+        label(Vec3::Y, "hello", Vec4::ONE, Duration::from_secs(1));
+
+        let frame = take_frame(FRAME);
+        assert_eq!(frame.lines.len(), 1);
+        assert_eq!(frame.labels[0].text, "hello");
+
+        let frame = take_frame(FRAME);
+        assert!(frame.lines.is_empty());
+        assert_eq!(frame.labels.len(), 1);
+    }
+}
@@ -1 +1,8 @@
+pub mod build_info;
 pub mod consts;
+pub mod debug_draw;
+pub mod logging;
+pub mod math;
+pub mod metrics;
+pub mod paths;
+pub mod sync;
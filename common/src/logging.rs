@@ -0,0 +1,104 @@
+use crate::{paths::data_dir, sync::lock_unpoisoned};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, TryLockError,
+    },
+};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+/// Filter directives in the usual `module=level` form, e.g. `QBD_LOG=info,game_window::renderer=trace`.
+pub const LOG_ENV_VAR: &str = "QBD_LOG";
+
+const DEFAULT_FILTER: &str = "info";
+const MAX_LOG_FILES: usize = 7;
+const RECENT_LINES: usize = 200;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static INITIALISED: AtomicBool = AtomicBool::new(false);
+
+/// Keeps the background log writer alive. Dropping it flushes whatever is still buffered.
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+}
+
+/// Logs to stderr and to a daily rotated `<file_prefix>.<date>.log` in the data directory. Only
+/// the first call has any effect.
+pub fn init(file_prefix: &str) -> LogGuard {
+    if INITIALISED.swap(true, Ordering::SeqCst) {
+        return LogGuard { _file: None };
+    }
+
+    let filter =
+        EnvFilter::try_from_env(LOG_ENV_VAR).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    // Not being able to write the log file shouldn't keep the game from starting.
+    let file_appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(file_prefix)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(data_dir().join("logs"));
+
+    let (file_layer, file_guard) = match file_appender {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer().with_ansi(false).with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        Err(error) => {
+            eprintln!("Could not open log file: {}", error);
+            (None, None)
+        }
+    };
+
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(file_layer)
+        .with(fmt::layer().with_ansi(false).with_writer(|| RecentWriter))
+        .try_init();
+
+    LogGuard { _file: file_guard }
+}
+
+/// The last lines logged, oldest first, for crash reports.
+pub fn recent_lines() -> Vec<String> {
+    lock_unpoisoned(&RECENT).iter().cloned().collect()
+}
+
+/// Like `recent_lines`, but gives up instead of waiting when something else is logging, which
+/// might be the panicking thread itself.
+pub fn try_recent_lines() -> Option<Vec<String>> {
+    let recent = match RECENT.try_lock() {
+        Ok(recent) => recent,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => return None,
+    };
+    Some(recent.iter().cloned().collect())
+}
+
+struct RecentWriter;
+
+impl Write for RecentWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut recent = lock_unpoisoned(&RECENT);
+        for line in String::from_utf8_lossy(buf).lines() {
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line.to_owned());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use super::{BlockPos, Vec3};
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+
+/// An axis aligned bounding box.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Pod, Zeroable)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Sorts the corners, so they can be given in any order.
+    pub fn new(a: Vec3, b: Vec3) -> Aabb {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn from_center_half_extents(center: Vec3, half_extents: Vec3) -> Aabb {
+        Aabb::new(center - half_extents, center + half_extents)
+    }
+
+    /// The unit cube a block occupies.
+    pub fn block(block: BlockPos) -> Aabb {
+        let min = block.to_world();
+        Self {
+            min,
+            max: min + Vec3::ONE,
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+            && point.z >= self.min.z
+            && point.z <= self.max.z
+    }
+
+    /// Boxes that only touch don't intersect, so standing on a block isn't colliding with it.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x < other.max.x
+            && self.max.x > other.min.x
+            && self.min.y < other.max.y
+            && self.max.y > other.min.y
+            && self.min.z < other.max.z
+            && self.max.z > other.min.z
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Grows the box by `amount` on every side.
+    pub fn expand(&self, amount: f32) -> Aabb {
+        Self {
+            min: self.min - Vec3::splat(amount),
+            max: self.max + Vec3::splat(amount),
+        }
+    }
+
+    pub fn translate(&self, offset: Vec3) -> Aabb {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_can_come_in_any_order() {
+        let aabb = Aabb::new(Vec3::new(1.0, -2.0, 3.0), Vec3::new(-1.0, 2.0, 0.0));
+        assert_eq!(aabb.min, Vec3::new(-1.0, -2.0, 0.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(aabb.center(), Vec3::new(0.0, 0.0, 1.5));
+        assert_eq!(aabb.size(), Vec3::new(2.0, 4.0, 3.0));
+    }
+
+    #[test]
+    fn touching_boxes_dont_intersect() {
+        let block = Aabb::block(BlockPos::new(0, 0, 0));
+        let standing =
+            Aabb::from_center_half_extents(Vec3::new(0.5, 1.9, 0.5), Vec3::new(0.3, 0.9, 0.3));
+        assert!(!block.intersects(&standing));
+        assert!(block.intersects(&standing.translate(Vec3::new(0.0, -0.01, 0.0))));
+        assert!(block.contains_point(Vec3::new(1.0, 1.0, 1.0)));
+        assert!(!block.contains_point(Vec3::new(1.01, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn union_and_expand_grow_the_box() {
+        let a = Aabb::block(BlockPos::new(-1, 0, 0));
+        let b = Aabb::block(BlockPos::new(2, 3, -4));
+        let union = a.union(&b);
+        assert_eq!(union.min, Vec3::new(-1.0, 0.0, -4.0));
+        assert_eq!(union.max, Vec3::new(3.0, 4.0, 1.0));
+
+        let expanded = a.expand(0.5);
+        assert_eq!(expanded.min, Vec3::new(-1.5, -0.5, -0.5));
+        assert_eq!(expanded.max, Vec3::new(0.5, 1.5, 1.5));
+    }
+}
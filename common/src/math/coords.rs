@@ -0,0 +1,194 @@
+use super::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// Blocks along each side of a chunk.
+pub const CHUNK_SIZE: i32 = 16;
+pub const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// A block in the world, the block at `(x, y, z)` fills the unit cube starting at that corner.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct BlockPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// A chunk in the world, measured in chunks rather than blocks.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// A block inside a chunk, each component is in `0..CHUNK_SIZE`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct LocalBlockPos {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+}
+
+impl BlockPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> BlockPos {
+        BlockPos { x, y, z }
+    }
+
+    /// The block containing a world position, rounding towards negative infinity.
+    pub fn from_world(position: Vec3) -> BlockPos {
+        let floored = position.floor();
+        BlockPos::new(floored.x as i32, floored.y as i32, floored.z as i32)
+    }
+
+    pub fn chunk(self) -> ChunkPos {
+        ChunkPos::new(
+            self.x.div_euclid(CHUNK_SIZE),
+            self.y.div_euclid(CHUNK_SIZE),
+            self.z.div_euclid(CHUNK_SIZE),
+        )
+    }
+
+    pub fn local(self) -> LocalBlockPos {
+        LocalBlockPos::new(
+            self.x.rem_euclid(CHUNK_SIZE) as u8,
+            self.y.rem_euclid(CHUNK_SIZE) as u8,
+            self.z.rem_euclid(CHUNK_SIZE) as u8,
+        )
+    }
+
+    pub fn offset(self, x: i32, y: i32, z: i32) -> BlockPos {
+        BlockPos::new(self.x + x, self.y + y, self.z + z)
+    }
+
+    /// The world position of the block's minimum corner.
+    pub fn to_world(self) -> Vec3 {
+        Vec3::new(self.x as f32, self.y as f32, self.z as f32)
+    }
+
+    pub fn center(self) -> Vec3 {
+        self.to_world() + Vec3::splat(0.5)
+    }
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> ChunkPos {
+        ChunkPos { x, y, z }
+    }
+
+    pub fn from_world(position: Vec3) -> ChunkPos {
+        BlockPos::from_world(position).chunk()
+    }
+
+    /// The block at the chunk's minimum corner.
+    pub fn origin(self) -> BlockPos {
+        BlockPos::new(
+            self.x * CHUNK_SIZE,
+            self.y * CHUNK_SIZE,
+            self.z * CHUNK_SIZE,
+        )
+    }
+
+    pub fn block(self, local: LocalBlockPos) -> BlockPos {
+        self.origin()
+            .offset(local.x as i32, local.y as i32, local.z as i32)
+    }
+}
+
+impl LocalBlockPos {
+    pub fn new(x: u8, y: u8, z: u8) -> LocalBlockPos {
+        debug_assert!(
+            (x as i32) < CHUNK_SIZE && (y as i32) < CHUNK_SIZE && (z as i32) < CHUNK_SIZE,
+            "local block position out of range"
+        );
+        LocalBlockPos { x, y, z }
+    }
+
+    /// Index into a chunk's block array, X varies fastest, then Z, then Y.
+    pub fn index(self) -> usize {
+        let size = CHUNK_SIZE as usize;
+        (self.y as usize * size + self.z as usize) * size + self.x as usize
+    }
+
+    pub fn from_index(index: usize) -> LocalBlockPos {
+        let size = CHUNK_SIZE as usize;
+        LocalBlockPos::new(
+            (index % size) as u8,
+            (index / (size * size)) as u8,
+            (index / size % size) as u8,
+        )
+    }
+}
+
+impl From<BlockPos> for ChunkPos {
+    fn from(block: BlockPos) -> ChunkPos {
+        block.chunk()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_positions_round_down() {
+        assert_eq!(
+            BlockPos::from_world(Vec3::new(0.9, 15.99, 16.0)),
+            BlockPos::new(0, 15, 16)
+        );
+        assert_eq!(
+            BlockPos::from_world(Vec3::new(-0.1, -1.0, -16.5)),
+            BlockPos::new(-1, -1, -17)
+        );
+    }
+
+    #[test]
+    fn negative_blocks_belong_to_negative_chunks() {
+        let block = BlockPos::new(-1, -16, -17);
+        assert_eq!(block.chunk(), ChunkPos::new(-1, -1, -2));
+        assert_eq!(block.local(), LocalBlockPos::new(15, 0, 15));
+        assert_eq!(block.chunk().block(block.local()), block);
+
+        assert_eq!(
+            ChunkPos::from_world(Vec3::new(-0.001, 0.0, 15.999)),
+            ChunkPos::new(-1, 0, 0)
+        );
+        assert_eq!(
+            ChunkPos::from_world(Vec3::new(-16.0, -16.001, 16.0)),
+            ChunkPos::new(-1, -2, 1)
+        );
+        assert_eq!(ChunkPos::new(-2, 0, 3).origin(), BlockPos::new(-32, 0, 48));
+    }
+
+    #[test]
+    fn every_block_round_trips_through_its_chunk() {
+        for x in -33..33 {
+            for y in [-17, -16, -1, 0, 15, 16].iter().copied() {
+                let block = BlockPos::new(x, y, -x);
+                assert_eq!(block.chunk().block(block.local()), block);
+                assert_eq!(ChunkPos::from(block), block.chunk());
+            }
+        }
+    }
+
+    #[test]
+    fn local_indices_cover_the_chunk_once() {
+        let mut seen = std::collections::HashSet::new();
+        for index in 0..CHUNK_VOLUME {
+            let local = LocalBlockPos::from_index(index);
+            assert_eq!(local.index(), index);
+            assert!(seen.insert(local), "{:?} has two indices", local);
+        }
+        assert_eq!(LocalBlockPos::new(1, 0, 0).index(), 1);
+        assert_eq!(LocalBlockPos::new(0, 0, 1).index(), CHUNK_SIZE as usize);
+        assert_eq!(
+            LocalBlockPos::new(0, 1, 0).index(),
+            (CHUNK_SIZE * CHUNK_SIZE) as usize
+        );
+    }
+}
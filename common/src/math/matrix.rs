@@ -0,0 +1,391 @@
+use super::{Quat, Vec3, Vec4};
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+use std::ops::Mul;
+
+/// A column-major 4x4 matrix, laid out the way GLSL's `mat4` expects it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Pod, Zeroable)]
+pub struct Mat4 {
+    pub cols: [Vec4; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4::from_cols(
+        Vec4::new(1.0, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, 1.0, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, 1.0, 0.0),
+        Vec4::new(0.0, 0.0, 0.0, 1.0),
+    );
+
+    pub const fn from_cols(x: Vec4, y: Vec4, z: Vec4, w: Vec4) -> Mat4 {
+        Mat4 { cols: [x, y, z, w] }
+    }
+
+    pub fn from_cols_array(array: [f32; 16]) -> Mat4 {
+        let col = |i: usize| Vec4::new(array[i], array[i + 1], array[i + 2], array[i + 3]);
+        Mat4::from_cols(col(0), col(4), col(8), col(12))
+    }
+
+    pub fn to_cols_array(self) -> [f32; 16] {
+        let mut array = [0.0; 16];
+        for (i, col) in self.cols.iter().enumerate() {
+            array[i * 4..i * 4 + 4].copy_from_slice(&col.to_array());
+        }
+        array
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        let mut matrix = Mat4::IDENTITY;
+        matrix.cols[3] = offset.extend(1.0);
+        matrix
+    }
+
+    pub fn scale(scale: Vec3) -> Mat4 {
+        Mat4::from_cols(
+            Vec4::new(scale.x, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, scale.y, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, scale.z, 0.0),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        )
+    }
+
+    pub fn from_quat(rotation: Quat) -> Mat4 {
+        let Quat { x, y, z, w } = rotation;
+        let (x2, y2, z2) = (x + x, y + y, z + z);
+        let (xx, xy, xz) = (x * x2, x * y2, x * z2);
+        let (yy, yz, zz) = (y * y2, y * z2, z * z2);
+        let (wx, wy, wz) = (w * x2, w * y2, w * z2);
+
+        Mat4::from_cols(
+            Vec4::new(1.0 - (yy + zz), xy + wz, xz - wy, 0.0),
+            Vec4::new(xy - wz, 1.0 - (xx + zz), yz + wx, 0.0),
+            Vec4::new(xz + wy, yz - wx, 1.0 - (xx + yy), 0.0),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        )
+    }
+
+    /// Right handed perspective projection mapping depth to 0..1 like Vulkan, Metal and DX12 do.
+    /// Vulkan's clip space Y points down, flip it in the viewport or the shader.
+    pub fn perspective_rh(fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+        let f = 1.0 / (fov_y * 0.5).tan();
+        let range = far / (near - far);
+
+        Mat4::from_cols(
+            Vec4::new(f / aspect, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, f, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, range, -1.0),
+            Vec4::new(0.0, 0.0, range * near, 0.0),
+        )
+    }
+
+    /// Right handed orthographic projection mapping depth to 0..1.
+    pub fn orthographic_rh(
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    ) -> Mat4 {
+        let width = 1.0 / (right - left);
+        let height = 1.0 / (top - bottom);
+        let depth = 1.0 / (near - far);
+
+        Mat4::from_cols(
+            Vec4::new(2.0 * width, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, 2.0 * height, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, depth, 0.0),
+            Vec4::new(
+                -(left + right) * width,
+                -(top + bottom) * height,
+                depth * near,
+                1.0,
+            ),
+        )
+    }
+
+    /// A view matrix looking from `eye` towards `target`.
+    pub fn look_at_rh(eye: Vec3, target: Vec3, up: Vec3) -> Mat4 {
+        let forward = (target - eye).normalize();
+        let side = forward.cross(up).normalize();
+        let up = side.cross(forward);
+
+        Mat4::from_cols(
+            Vec4::new(side.x, up.x, -forward.x, 0.0),
+            Vec4::new(side.y, up.y, -forward.y, 0.0),
+            Vec4::new(side.z, up.z, -forward.z, 0.0),
+            Vec4::new(-side.dot(eye), -up.dot(eye), forward.dot(eye), 1.0),
+        )
+    }
+
+    pub fn row(&self, index: usize) -> Vec4 {
+        let [x, y, z, w] = self.cols;
+        Vec4::new(x[index], y[index], z[index], w[index])
+    }
+
+    pub fn transpose(self) -> Mat4 {
+        Mat4::from_cols(self.row(0), self.row(1), self.row(2), self.row(3))
+    }
+
+    /// Returns `None` for singular matrices.
+    pub fn inverse(self) -> Option<Mat4> {
+        let m = self.to_cols_array();
+        let mut inv = [0.0; 16];
+
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14]
+            + m[13] * m[6] * m[11]
+            - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14]
+            - m[12] * m[6] * m[11]
+            + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13]
+            + m[12] * m[5] * m[11]
+            - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13]
+            - m[12] * m[5] * m[10]
+            + m[12] * m[6] * m[9];
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14]
+            - m[13] * m[2] * m[11]
+            + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14]
+            + m[12] * m[2] * m[11]
+            - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13]
+            - m[12] * m[1] * m[11]
+            + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13]
+            + m[12] * m[1] * m[10]
+            - m[12] * m[2] * m[9];
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14]
+            + m[13] * m[2] * m[7]
+            - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14]
+            - m[12] * m[2] * m[7]
+            + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13]
+            + m[12] * m[1] * m[7]
+            - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13]
+            - m[12] * m[1] * m[6]
+            + m[12] * m[2] * m[5];
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10]
+            - m[9] * m[2] * m[7]
+            + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10]
+            + m[8] * m[2] * m[7]
+            - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9]
+            - m[8] * m[1] * m[7]
+            + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9]
+            + m[8] * m[1] * m[6]
+            - m[8] * m[2] * m[5];
+
+        let determinant = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+        if determinant == 0.0 {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        for value in inv.iter_mut() {
+            *value *= inverse_determinant;
+        }
+        Some(Mat4::from_cols_array(inv))
+    }
+
+    pub fn transform_point3(&self, point: Vec3) -> Vec3 {
+        let transformed = *self * point.extend(1.0);
+        transformed.truncate() / transformed.w
+    }
+
+    /// Ignores the translation, for directions and normals.
+    pub fn transform_vector3(&self, vector: Vec3) -> Vec3 {
+        (*self * vector.extend(0.0)).truncate()
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let [x, y, z, w] = other.cols;
+        Mat4::from_cols(self * x, self * y, self * z, self * w)
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, vector: Vec4) -> Vec4 {
+        let [x, y, z, w] = self.cols;
+        x * vector.x + y * vector.y + z * vector.z + w * vector.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::testing::assert_close;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_matrix_close(value: Mat4, expected: Mat4) {
+        for (value, expected) in value.cols.iter().zip(expected.cols.iter()) {
+            assert!(
+                (*value - *expected).length() < 1e-4,
+                "{:?} isn't {:?}",
+                value,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Mat4::translation(Vec3::new(3.0, -2.0, 7.5))
+            * Mat4::from_quat(Quat::from_axis_angle(Vec3::new(1.0, 2.0, -0.5), 0.8))
+            * Mat4::scale(Vec3::new(2.0, 0.5, 4.0));
+        let inverse = transform.inverse().unwrap();
+
+        assert_matrix_close(transform * inverse, Mat4::IDENTITY);
+        assert_matrix_close(inverse * transform, Mat4::IDENTITY);
+        let point = Vec3::new(-1.0, 4.0, 0.25);
+        assert_close(
+            inverse.transform_point3(transform.transform_point3(point)),
+            point,
+        );
+
+        let projection = Mat4::perspective_rh(1.2, 16.0 / 9.0, 0.1, 500.0);
+        assert_matrix_close(projection * projection.inverse().unwrap(), Mat4::IDENTITY);
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert_eq!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        let mut repeated = Mat4::IDENTITY;
+        repeated.cols[2] = repeated.cols[0];
+        assert_eq!(repeated.inverse(), None);
+    }
+
+    #[test]
+    fn transpose_swaps_rows_and_columns() {
+        let matrix = Mat4::from_cols_array([
+            1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0,
+        ]);
+        assert_eq!(matrix.row(1), Vec4::new(2.0, 6.0, 10.0, 14.0));
+        assert_eq!(matrix.transpose().cols[1], matrix.row(1));
+        assert_eq!(matrix.transpose().transpose(), matrix);
+    }
+
+    #[test]
+    fn look_at_puts_the_target_straight_ahead() {
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        assert_close(view.transform_point3(Vec3::ZERO), Vec3::new(0.0, 0.0, -5.0));
+        assert_close(
+            view.transform_point3(Vec3::new(1.0, 2.0, 0.0)),
+            Vec3::new(1.0, 2.0, -5.0),
+        );
+
+        // Looking down +X, the right is +Z.
+        let view = Mat4::look_at_rh(Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 2.0, 3.0), Vec3::Y);
+        assert_close(
+            view.transform_point3(Vec3::new(4.0, 2.0, 3.0)),
+            Vec3::new(0.0, 0.0, -3.0),
+        );
+        assert_close(
+            view.transform_point3(Vec3::new(1.0, 2.0, 4.0)),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+        assert_close(view.transform_vector3(Vec3::Y), Vec3::Y);
+    }
+
+    #[test]
+    fn perspective_maps_depth_to_zero_one() {
+        let projection = Mat4::perspective_rh(FRAC_PI_2, 2.0, 0.5, 100.0);
+
+        assert_close(
+            projection.transform_point3(Vec3::new(0.0, 0.0, -0.5)),
+            Vec3::ZERO,
+        );
+        assert_close(
+            projection.transform_point3(Vec3::new(0.0, 0.0, -100.0)),
+            Vec3::Z,
+        );
+        // A 90 degree field of view reaches the top of the screen at 45 degrees.
+        assert_close(
+            projection.transform_point3(Vec3::new(20.0, 10.0, -10.0)),
+            Vec3::new(
+                1.0,
+                1.0,
+                projection.transform_point3(Vec3::new(0.0, 0.0, -10.0)).z,
+            ),
+        );
+        assert_eq!((projection * Vec4::new(0.0, 0.0, -7.0, 1.0)).w, 7.0);
+    }
+
+    #[test]
+    fn orthographic_maps_the_box_to_clip_space() {
+        let projection = Mat4::orthographic_rh(-4.0, 4.0, -1.0, 3.0, 1.0, 11.0);
+
+        assert_close(
+            projection.transform_point3(Vec3::new(-4.0, -1.0, -1.0)),
+            Vec3::new(-1.0, -1.0, 0.0),
+        );
+        assert_close(
+            projection.transform_point3(Vec3::new(4.0, 3.0, -11.0)),
+            Vec3::new(1.0, 1.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn from_quat_rotates_like_the_quaternion() {
+        let rotation = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        let matrix = Mat4::from_quat(rotation);
+        assert_close(matrix.transform_vector3(Vec3::X), Vec3::new(0.0, 0.0, -1.0));
+
+        let rotation = Quat::from_axis_angle(Vec3::new(-2.0, 1.0, 0.5), 2.1);
+        let matrix = Mat4::from_quat(rotation);
+        for vector in [Vec3::X, Vec3::Y, Vec3::Z, Vec3::new(0.3, -4.0, 2.0)].iter() {
+            assert_close(matrix.transform_vector3(*vector), rotation * *vector);
+        }
+        // Rotations are orthonormal, the inverse is the transpose.
+        assert_matrix_close(matrix.inverse().unwrap(), matrix.transpose());
+        assert_matrix_close(Mat4::from_quat(rotation.conjugate()), matrix.transpose());
+    }
+
+    #[test]
+    fn columns_round_trip_through_arrays() {
+        let array = [
+            1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0,
+        ];
+        let matrix = Mat4::from_cols_array(array);
+        assert_eq!(matrix.cols[3], Vec4::new(13.0, 14.0, 15.0, 16.0));
+        assert_eq!(matrix.to_cols_array(), array);
+        assert_eq!(
+            Mat4::translation(Vec3::new(1.0, 2.0, 3.0)).transform_point3(Vec3::ONE),
+            Vec3::new(2.0, 3.0, 4.0)
+        );
+    }
+}
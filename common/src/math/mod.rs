@@ -0,0 +1,29 @@
+mod aabb;
+mod coords;
+mod matrix;
+mod quaternion;
+mod ray;
+mod vector;
+
+pub use self::{
+    aabb::Aabb,
+    coords::{BlockPos, ChunkPos, LocalBlockPos, CHUNK_SIZE, CHUNK_VOLUME},
+    matrix::Mat4,
+    quaternion::Quat,
+    ray::{BlockHit, BlockTraversal, Ray},
+    vector::{Vec2, Vec3, Vec4},
+};
+
+#[cfg(test)]
+mod testing {
+    use super::Vec3;
+
+    pub fn assert_close(value: Vec3, expected: Vec3) {
+        assert!(
+            (value - expected).length() < 1e-4,
+            "{:?} isn't {:?}",
+            value,
+            expected
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use super::Vec3;
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+use std::ops::Mul;
+
+/// A rotation, kept normalized by the constructors.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Pod, Zeroable)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Quat {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Quat {
+            x: axis.x * sin,
+            y: axis.y * sin,
+            z: axis.z * sin,
+            w: cos,
+        }
+    }
+
+    pub fn dot(self, other: Quat) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quat {
+        let length = self.length();
+        if length > 0.0 {
+            Quat {
+                x: self.x / length,
+                y: self.y / length,
+                z: self.z / length,
+                w: self.w / length,
+            }
+        } else {
+            Quat::IDENTITY
+        }
+    }
+
+    /// The inverse rotation of a normalized quaternion.
+    pub fn conjugate(self) -> Quat {
+        Quat {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+            w: self.w,
+        }
+    }
+
+    pub fn mul_vec3(self, vector: Vec3) -> Vec3 {
+        let axis = Vec3::new(self.x, self.y, self.z);
+        let t = 2.0 * axis.cross(vector);
+        vector + self.w * t + axis.cross(t)
+    }
+
+    /// Spherical interpolation along the shortest path.
+    pub fn slerp(self, other: Quat, t: f32) -> Quat {
+        let mut other = other;
+        let mut cos = self.dot(other);
+        if cos < 0.0 {
+            other = Quat {
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+                w: -other.w,
+            };
+            cos = -cos;
+        }
+
+        // Nearly parallel rotations divide by almost zero below, a plain lerp is accurate enough.
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+
+        Quat {
+            x: self.x * a + other.x * b,
+            y: self.y * a + other.y * b,
+            z: self.z * a + other.z * b,
+            w: self.w * a + other.w * b,
+        }
+        .normalize()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    /// Applies `other` first, then `self`.
+    fn mul(self, other: Quat) -> Quat {
+        Quat {
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+        }
+    }
+}
+
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, vector: Vec3) -> Vec3 {
+        self.mul_vec3(vector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::testing::assert_close;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    #[test]
+    fn axis_angle_rotates_counter_clockwise() {
+        let rotation = Quat::from_axis_angle(Vec3::Z, FRAC_PI_2);
+        assert_close(rotation * Vec3::X, Vec3::Y);
+        assert_close(rotation * Vec3::Z, Vec3::Z);
+        assert!((rotation.length() - 1.0).abs() < 1e-6);
+
+        // The axis doesn't have to be normalized.
+        let half_turn = Quat::from_axis_angle(Vec3::new(0.0, 3.0, 0.0), PI);
+        assert_close(
+            half_turn * Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(-1.0, 2.0, -3.0),
+        );
+    }
+
+    #[test]
+    fn products_apply_the_right_hand_side_first() {
+        let yaw = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        let pitch = Quat::from_axis_angle(Vec3::X, FRAC_PI_2);
+        let vector = Vec3::new(0.5, -1.0, 2.0);
+
+        assert_close((yaw * pitch) * vector, yaw * (pitch * vector));
+        assert_close(yaw * pitch * Vec3::Y, Vec3::X);
+        assert_close(pitch * yaw * Vec3::Y, Vec3::Z);
+    }
+
+    #[test]
+    fn conjugate_undoes_the_rotation() {
+        let rotation = Quat::from_axis_angle(Vec3::new(1.0, -1.0, 2.0), 1.3);
+        let vector = Vec3::new(3.0, 0.5, -2.0);
+        assert_close(rotation.conjugate() * (rotation * vector), vector);
+
+        let identity = rotation * rotation.conjugate();
+        assert_close(Vec3::new(identity.x, identity.y, identity.z), Vec3::ZERO);
+        assert!((identity.w - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn slerp_takes_the_short_way() {
+        let start = Quat::IDENTITY;
+        let end = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        assert_close(
+            start.slerp(end, 0.5) * Vec3::X,
+            Quat::from_axis_angle(Vec3::Y, FRAC_PI_4) * Vec3::X,
+        );
+        assert_close(start.slerp(end, 0.0) * Vec3::X, Vec3::X);
+        assert_close(start.slerp(end, 1.0) * Vec3::X, end * Vec3::X);
+
+        // The negated quaternion is the same rotation, slerp mustn't go the long way round to it.
+        let negated = Quat {
+            x: -end.x,
+            y: -end.y,
+            z: -end.z,
+            w: -end.w,
+        };
+        assert_close(
+            start.slerp(negated, 0.5) * Vec3::X,
+            Quat::from_axis_angle(Vec3::Y, FRAC_PI_4) * Vec3::X,
+        );
+
+        // Nearly equal rotations fall back to lerping.
+        let close = Quat::from_axis_angle(Vec3::Y, 0.01);
+        assert!((start.slerp(close, 0.5).length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn normalizing_zero_gives_the_identity() {
+        let zero = Quat {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 0.0,
+        };
+        assert_eq!(zero.normalize(), Quat::IDENTITY);
+    }
+}
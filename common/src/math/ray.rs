@@ -0,0 +1,256 @@
+use super::{Aabb, BlockPos, Vec3};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Kept normalized, so distances along the ray are in blocks.
+    pub direction: Vec3,
+}
+
+/// A block a ray passed through, together with the face it entered by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockHit {
+    pub block: BlockPos,
+    /// Points out of the entered face, so `block + normal` is where a placed block goes. Zero for
+    /// the block the ray starts in.
+    pub normal: [i32; 3],
+    /// How far along the ray the block was entered.
+    pub distance: f32,
+}
+
+impl BlockHit {
+    /// The block on the side of the face the ray entered through.
+    pub fn adjacent(&self) -> BlockPos {
+        self.block
+            .offset(self.normal[0], self.normal[1], self.normal[2])
+    }
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Ray {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// The distance at which the ray enters the box, zero when it starts inside it.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3 {
+            // A ray parallel to the slab is inside it everywhere or nowhere. Dividing by zero
+            // instead would give NaNs when the ray lies on one of its planes.
+            if self.direction[axis] == 0.0 {
+                if self.origin[axis] < aabb.min[axis] || self.origin[axis] > aabb.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let inverse = 1.0 / self.direction[axis];
+            let mut t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let mut t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            near = near.max(t0);
+            far = far.min(t1);
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+
+    /// Walks every block the ray passes through in order, up to `max_distance`.
+    pub fn blocks(&self, max_distance: f32) -> BlockTraversal {
+        BlockTraversal::new(*self, max_distance)
+    }
+}
+
+/// Grid traversal from "A Fast Voxel Traversal Algorithm" by Amanatides and Woo.
+pub struct BlockTraversal {
+    block: BlockPos,
+    step: [i32; 3],
+    next_boundary: Vec3,
+    boundary_delta: Vec3,
+    normal: [i32; 3],
+    distance: f32,
+    max_distance: f32,
+}
+
+impl BlockTraversal {
+    fn new(ray: Ray, max_distance: f32) -> BlockTraversal {
+        let block = BlockPos::from_world(ray.origin);
+        let corner = block.to_world();
+
+        let mut step = [0; 3];
+        let mut next_boundary = Vec3::splat(f32::INFINITY);
+        let mut boundary_delta = Vec3::splat(f32::INFINITY);
+        for axis in 0..3 {
+            let direction = ray.direction[axis];
+            if direction > 0.0 {
+                step[axis] = 1;
+                boundary_delta[axis] = 1.0 / direction;
+                next_boundary[axis] = (corner[axis] + 1.0 - ray.origin[axis]) / direction;
+            } else if direction < 0.0 {
+                step[axis] = -1;
+                boundary_delta[axis] = -1.0 / direction;
+                next_boundary[axis] = (corner[axis] - ray.origin[axis]) / direction;
+            }
+        }
+
+        Self {
+            block,
+            step,
+            next_boundary,
+            boundary_delta,
+            normal: [0; 3],
+            distance: 0.0,
+            max_distance,
+        }
+    }
+}
+
+impl Iterator for BlockTraversal {
+    type Item = BlockHit;
+
+    fn next(&mut self) -> Option<BlockHit> {
+        if self.distance > self.max_distance {
+            return None;
+        }
+
+        let hit = BlockHit {
+            block: self.block,
+            normal: self.normal,
+            distance: self.distance,
+        };
+
+        // Step across whichever boundary is closest.
+        let axis = if self.next_boundary.x < self.next_boundary.y {
+            if self.next_boundary.x < self.next_boundary.z {
+                0
+            } else {
+                2
+            }
+        } else if self.next_boundary.y < self.next_boundary.z {
+            1
+        } else {
+            2
+        };
+
+        self.distance = self.next_boundary[axis];
+        self.next_boundary[axis] += self.boundary_delta[axis];
+        match axis {
+            0 => self.block.x += self.step[0],
+            1 => self.block.y += self.step[1],
+            _ => self.block.z += self.step[2],
+        }
+        self.normal = [0; 3];
+        self.normal[axis] = -self.step[axis];
+
+        Some(hit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vec3::ZERO, Vec3::ONE)
+    }
+
+    #[test]
+    fn rays_enter_boxes_in_front_of_them() {
+        let ray = Ray::new(Vec3::new(-2.0, 0.5, 0.5), Vec3::X);
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(2.0));
+
+        let behind = Ray::new(Vec3::new(2.0, 0.5, 0.5), Vec3::X);
+        assert_eq!(behind.intersect_aabb(&unit_box()), None);
+
+        let inside = Ray::new(Vec3::splat(0.5), Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(inside.intersect_aabb(&unit_box()), Some(0.0));
+    }
+
+    #[test]
+    fn rays_parallel_to_a_face() {
+        // Lying exactly on the bottom and front planes used to be 0 * inf.
+        let on_planes = Ray::new(Vec3::new(-1.0, 0.0, 0.0), Vec3::X);
+        assert_eq!(on_planes.intersect_aabb(&unit_box()), Some(1.0));
+        let on_top = Ray::new(Vec3::new(0.5, 1.0, -3.0), Vec3::Z);
+        assert_eq!(on_top.intersect_aabb(&unit_box()), Some(3.0));
+
+        let above = Ray::new(Vec3::new(-1.0, 1.5, 0.5), Vec3::X);
+        assert_eq!(above.intersect_aabb(&unit_box()), None);
+        let beside = Ray::new(Vec3::new(-0.5, 0.5, -3.0), Vec3::Z);
+        assert_eq!(beside.intersect_aabb(&unit_box()), None);
+    }
+
+    #[test]
+    fn traversal_steps_through_faces() {
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 0.5), Vec3::X);
+        let hits: Vec<BlockHit> = ray.blocks(2.0).collect();
+
+        assert_eq!(
+            hits.iter().map(|hit| hit.block).collect::<Vec<_>>(),
+            vec![
+                BlockPos::new(0, 0, 0),
+                BlockPos::new(1, 0, 0),
+                BlockPos::new(2, 0, 0),
+            ]
+        );
+        assert_eq!(hits[0].normal, [0, 0, 0]);
+        assert_eq!(hits[1].normal, [-1, 0, 0]);
+        assert_eq!(hits[1].distance, 0.5);
+        assert_eq!(hits[2].distance, 1.5);
+        assert_eq!(hits[1].adjacent(), BlockPos::new(0, 0, 0));
+    }
+
+    #[test]
+    fn traversal_handles_negative_coordinates() {
+        let ray = Ray::new(Vec3::new(-0.5, -0.5, -15.5), Vec3::new(0.0, -1.0, -1.0));
+        let blocks: Vec<BlockPos> = ray.blocks(1.5).map(|hit| hit.block).collect();
+
+        // Exactly through an edge, Z is stepped before Y.
+        assert_eq!(
+            blocks,
+            vec![
+                BlockPos::new(-1, -1, -16),
+                BlockPos::new(-1, -1, -17),
+                BlockPos::new(-1, -2, -17),
+            ]
+        );
+    }
+
+    #[test]
+    fn traversal_visits_every_block_a_diagonal_crosses() {
+        let ray = Ray::new(Vec3::new(0.2, 0.7, 0.5), Vec3::new(1.0, 1.0, 0.0));
+        let hits: Vec<BlockHit> = ray.blocks(1.5).collect();
+
+        assert_eq!(
+            hits.iter().map(|hit| hit.block).collect::<Vec<_>>(),
+            vec![
+                BlockPos::new(0, 0, 0),
+                BlockPos::new(0, 1, 0),
+                BlockPos::new(1, 1, 0),
+            ]
+        );
+        assert_eq!(hits[1].normal, [0, -1, 0]);
+        assert_eq!(hits[2].normal, [-1, 0, 0]);
+        // Each block is entered where the ray meets its box.
+        for hit in &hits[1..] {
+            let entry = ray.intersect_aabb(&Aabb::block(hit.block)).unwrap();
+            assert!((entry - hit.distance).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
+
+// Everything the vector types have in common, component-wise.
+macro_rules! impl_vector {
+    ($name:ident, $n:expr, $($field:ident),+) => {
+        impl $name {
+            pub const ZERO: $name = $name { $($field: 0.0),+ };
+            pub const ONE: $name = $name { $($field: 1.0),+ };
+
+            pub const fn new($($field: f32),+) -> $name {
+                $name { $($field),+ }
+            }
+
+            pub const fn splat(value: f32) -> $name {
+                $name { $($field: value),+ }
+            }
+
+            pub fn dot(self, other: $name) -> f32 {
+                0.0 $(+ self.$field * other.$field)+
+            }
+
+            pub fn length_squared(self) -> f32 {
+                self.dot(self)
+            }
+
+            pub fn length(self) -> f32 {
+                self.length_squared().sqrt()
+            }
+
+            /// Returns the zero vector instead of NaNs when the length is zero.
+            pub fn normalize(self) -> $name {
+                let length = self.length();
+                if length > 0.0 {
+                    self / length
+                } else {
+                    $name::ZERO
+                }
+            }
+
+            pub fn distance(self, other: $name) -> f32 {
+                (other - self).length()
+            }
+
+            pub fn lerp(self, other: $name, t: f32) -> $name {
+                self + (other - self) * t
+            }
+
+            pub fn min(self, other: $name) -> $name {
+                $name { $($field: self.$field.min(other.$field)),+ }
+            }
+
+            pub fn max(self, other: $name) -> $name {
+                $name { $($field: self.$field.max(other.$field)),+ }
+            }
+
+            pub fn abs(self) -> $name {
+                $name { $($field: self.$field.abs()),+ }
+            }
+
+            pub fn floor(self) -> $name {
+                $name { $($field: self.$field.floor()),+ }
+            }
+
+            pub fn to_array(self) -> [f32; $n] {
+                [$(self.$field),+]
+            }
+        }
+
+        impl From<[f32; $n]> for $name {
+            fn from(array: [f32; $n]) -> $name {
+                let [$($field),+] = array;
+                $name { $($field),+ }
+            }
+        }
+
+        impl From<$name> for [f32; $n] {
+            fn from(vector: $name) -> [f32; $n] {
+                vector.to_array()
+            }
+        }
+
+        impl Index<usize> for $name {
+            type Output = f32;
+
+            fn index(&self, index: usize) -> &f32 {
+                [$(&self.$field),+][index]
+            }
+        }
+
+        impl IndexMut<usize> for $name {
+            fn index_mut(&mut self, index: usize) -> &mut f32 {
+                let components = [$(&mut self.$field),+];
+                IntoIterator::into_iter(components)
+                    .nth(index)
+                    .expect("vector index out of bounds")
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name { $($field: self.$field + other.$field),+ }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name { $($field: self.$field - other.$field),+ }
+            }
+        }
+
+        impl Mul for $name {
+            type Output = $name;
+
+            fn mul(self, other: $name) -> $name {
+                $name { $($field: self.$field * other.$field),+ }
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = $name;
+
+            fn mul(self, scalar: f32) -> $name {
+                $name { $($field: self.$field * scalar),+ }
+            }
+        }
+
+        impl Mul<$name> for f32 {
+            type Output = $name;
+
+            fn mul(self, vector: $name) -> $name {
+                vector * self
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = $name;
+
+            fn div(self, scalar: f32) -> $name {
+                $name { $($field: self.$field / scalar),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name { $($field: -self.$field),+ }
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                *self = *self - other;
+            }
+        }
+
+        impl MulAssign<f32> for $name {
+            fn mul_assign(&mut self, scalar: f32) {
+                *self = *self * scalar;
+            }
+        }
+
+        impl DivAssign<f32> for $name {
+            fn div_assign(&mut self, scalar: f32) {
+                *self = *self / scalar;
+            }
+        }
+    };
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Pod, Zeroable)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl_vector!(Vec2, 2, x, y);
+
+/// Note that GLSL aligns `vec3` to 16 bytes inside uniform and push constant blocks, while this is
+/// 12 bytes; pad it or use `Vec4` in GPU structs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Pod, Zeroable)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl_vector!(Vec3, 3, x, y, z);
+
+impl Vec3 {
+    pub const X: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+    pub const Y: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+    pub const Z: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Pod, Zeroable)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl_vector!(Vec4, 4, x, y, z, w);
+
+impl Vec4 {
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizing_zero_gives_zero() {
+        assert_eq!(Vec3::ZERO.normalize(), Vec3::ZERO);
+        assert_eq!(Vec2::new(3.0, 4.0).normalize(), Vec2::new(0.6, 0.8));
+        assert_eq!(Vec2::new(3.0, 4.0).length(), 5.0);
+    }
+
+    #[test]
+    fn cross_products_are_right_handed() {
+        assert_eq!(Vec3::X.cross(Vec3::Y), Vec3::Z);
+        assert_eq!(Vec3::Y.cross(Vec3::Z), Vec3::X);
+        assert_eq!(Vec3::Y.cross(Vec3::X), -Vec3::Z);
+    }
+
+    #[test]
+    fn components_are_indexed_in_order() {
+        let mut vector = Vec4::new(1.0, 2.0, 3.0, 4.0);
+        vector[2] = 5.0;
+        assert_eq!(vector.to_array(), [1.0, 2.0, 5.0, 4.0]);
+        assert_eq!(vector.truncate(), Vec3::new(1.0, 2.0, 5.0));
+        assert_eq!(Vec3::new(1.0, 2.0, 5.0).extend(4.0), vector);
+        assert_eq!(Vec3::new(-1.5, 2.0, 0.2).floor(), Vec3::new(-2.0, 2.0, 0.0));
+        assert_eq!(
+            Vec3::ZERO.lerp(Vec3::new(2.0, 4.0, -2.0), 0.25),
+            Vec3::new(0.5, 1.0, -0.5)
+        );
+    }
+}
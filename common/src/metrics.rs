@@ -0,0 +1,141 @@
+//! Named engine metrics. Subsystems publish their numbers here as they go and the debug overlay
+//! reads them back, so neither has to know about the other.
+
+use crate::sync::lock_unpoisoned;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+};
+
+/// Samples kept per history, about four seconds of frames at 60 FPS.
+pub const HISTORY_LEN: usize = 240;
+
+/// Milliseconds between presented frames, as a history.
+pub const FRAME_TIME: &str = "frame_time";
+/// Player position, chunk and facing, as text.
+pub const POSITION: &str = "player.position";
+pub const CHUNK: &str = "player.chunk";
+pub const FACING: &str = "player.facing";
+pub const DRAW_CALLS: &str = "renderer.draw_calls";
+/// Bytes of GPU memory allocated by the renderer.
+pub const GPU_MEMORY: &str = "renderer.gpu_memory";
+pub const ADAPTER: &str = "renderer.adapter";
+pub const BACKEND: &str = "renderer.backend";
+
+static METRICS: Mutex<Registry> = Mutex::new(Registry::new());
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricValue {
+    Number(f64),
+    Text(String),
+}
+
+struct Registry {
+    values: BTreeMap<&'static str, MetricValue>,
+    histories: BTreeMap<&'static str, VecDeque<f32>>,
+}
+
+impl Registry {
+    const fn new() -> Registry {
+        Self {
+            values: BTreeMap::new(),
+            histories: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn set_number(name: &'static str, value: f64) {
+    with_registry(|registry| registry.values.insert(name, MetricValue::Number(value)));
+}
+
+pub fn set_text(name: &'static str, text: impl Into<String>) {
+    let value = MetricValue::Text(text.into());
+    with_registry(|registry| registry.values.insert(name, value));
+}
+
+/// Forgets a value, for subsystems that shut down.
+pub fn remove(name: &'static str) {
+    with_registry(|registry| registry.values.remove(name));
+}
+
+/// Adds a sample to the history `name`, dropping the oldest once it holds `HISTORY_LEN`.
+pub fn push_sample(name: &'static str, sample: f32) {
+    with_registry(|registry| {
+        let history = registry.histories.entry(name).or_default();
+        if history.len() == HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(sample);
+    });
+}
+
+pub fn value(name: &str) -> Option<MetricValue> {
+    with_registry(|registry| registry.values.get(name).cloned())
+}
+
+pub fn number(name: &str) -> Option<f64> {
+    match value(name)? {
+        MetricValue::Number(value) => Some(value),
+        MetricValue::Text(_) => None,
+    }
+}
+
+pub fn text(name: &str) -> Option<String> {
+    match value(name)? {
+        MetricValue::Text(text) => Some(text),
+        MetricValue::Number(_) => None,
+    }
+}
+
+/// Samples pushed to `name`, oldest first.
+pub fn history(name: &str) -> Vec<f32> {
+    with_registry(|registry| {
+        registry
+            .histories
+            .get(name)
+            .map(|history| history.iter().copied().collect())
+            .unwrap_or_default()
+    })
+}
+
+fn with_registry<T>(f: impl FnOnce(&mut Registry) -> T) -> T {
+    f(&mut lock_unpoisoned(&METRICS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every test uses its own names, since they share one registry.
+
+    #[test]
+    fn histories_keep_the_newest_samples() {
+        for sample in 0..HISTORY_LEN + 10 {
+            push_sample("test.history", sample as f32);
+        }
+
+        let history = history("test.history");
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert_eq!(history[0], 10.0);
+        assert_eq!(history[HISTORY_LEN - 1], (HISTORY_LEN + 9) as f32);
+    }
+
+    #[test]
+    fn values_only_read_back_as_their_own_type() {
+        set_number("test.number", 4.5);
+        set_text("test.text", "hello");
+
+        assert_eq!(number("test.number"), Some(4.5));
+        assert_eq!(text("test.number"), None);
+        assert_eq!(text("test.text"), Some("hello".to_owned()));
+        assert_eq!(number("test.text"), None);
+        assert_eq!(value("test.missing"), None);
+    }
+
+    #[test]
+    fn removed_values_are_gone() {
+        set_number("test.removed", 1.0);
+        remove("test.removed");
+        assert_eq!(value("test.removed"), None);
+    }
+}
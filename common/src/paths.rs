@@ -0,0 +1,10 @@
+use crate::consts::APP_NAME;
+use std::path::PathBuf;
+
+/// Directory where settings, logs and saves are stored. Falls back to the working directory on
+/// platforms without a known data directory.
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_NAME)
+}
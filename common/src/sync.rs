@@ -0,0 +1,9 @@
+use std::sync::{Mutex, MutexGuard};
+
+/// Locks a global that a panic can't leave in a state that matters, like a queue of debug shapes
+/// or the last log lines, so one panicking thread doesn't take every later caller down with it.
+pub fn lock_unpoisoned<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,87 @@
+use crate::input::{action::Action, TickInput};
+use common::{
+    consts::TICKS_PER_SECOND,
+    math::{Mat4, Vec3},
+};
+use std::f32::consts::FRAC_PI_2;
+
+// Keeps the camera from flipping over when looking straight up or down.
+const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
+
+/// Vertical field of view unless the settings say otherwise.
+pub const DEFAULT_FOV_Y_DEGREES: f32 = 70.0;
+const NEAR: f32 = 0.1;
+const FAR: f32 = 1000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Camera {
+    pub fn new(position: Vec3) -> Camera {
+        Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        Vec3::new(
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            -self.yaw.cos() * self.pitch.cos(),
+        )
+    }
+
+    /// Takes world space to clip space for a viewport `aspect` times wider than it is tall.
+    pub fn view_projection(&self, aspect: f32, fov_y_degrees: f32) -> Mat4 {
+        let view = Mat4::look_at_rh(self.position, self.position + self.forward(), Vec3::Y);
+        let projection = Mat4::perspective_rh(fov_y_degrees.to_radians(), aspect, NEAR, FAR);
+
+        projection * view
+    }
+}
+
+/// First person free-fly controller driven once per fixed tick.
+pub struct CameraController {
+    camera: Camera,
+    speed: f32,
+}
+
+impl CameraController {
+    pub fn new(camera: Camera) -> CameraController {
+        Self { camera, speed: 4.3 }
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn update(&mut self, input: &TickInput) {
+        let [yaw, pitch] = input.look_delta();
+        self.camera.yaw = (self.camera.yaw + yaw) % std::f32::consts::TAU;
+        self.camera.pitch = (self.camera.pitch + pitch).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+
+        let forward = input.value(Action::MoveForward) - input.value(Action::MoveBackward);
+        let strafe = input.value(Action::MoveRight) - input.value(Action::MoveLeft);
+        let lift = input.value(Action::Jump) - input.value(Action::Crouch);
+
+        let mut speed = self.speed / TICKS_PER_SECOND as f32;
+        if input.held(Action::Sprint) {
+            speed *= 1.5;
+        }
+
+        // Horizontal movement ignores pitch so looking down doesn't slow you down.
+        let (sin, cos) = self.camera.yaw.sin_cos();
+        let movement = Vec3::new(
+            sin * forward + cos * strafe,
+            lift,
+            -cos * forward + sin * strafe,
+        );
+        self.camera.position += movement * speed;
+    }
+}
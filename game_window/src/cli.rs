@@ -0,0 +1,138 @@
+use crate::renderer::capture::CaptureOutput;
+use std::{env, path::PathBuf};
+
+const USAGE: &str = "Usage: game_window [--record <file> | --replay <file> [--headless]] \
+[--capture <directory>] [--capture-fps <fps>] [--shaders <directory>] \
+[--capture-pipe <program> [<argument>...]]";
+const DEFAULT_CAPTURE_FPS: u32 = 60;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    /// Runs a replay without rendering anything, as fast as possible.
+    pub headless: bool,
+    /// Where to write every rendered frame, advancing time by a fixed step per frame.
+    pub capture: Option<CaptureOutput>,
+    pub capture_fps: u32,
+    /// Reads shaders from this directory instead of the ones built into the game, so they can be
+    /// edited without rebuilding.
+    pub shaders: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Self {
+            record: None,
+            replay: None,
+            headless: false,
+            capture: None,
+            capture_fps: DEFAULT_CAPTURE_FPS,
+            shaders: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args() -> Result<Options, String> {
+        Options::parse(env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record" => options.record = Some(path_argument(&arg, args.next())?),
+                "--replay" => options.replay = Some(path_argument(&arg, args.next())?),
+                "--headless" => options.headless = true,
+                "--capture" => {
+                    let directory = path_argument(&arg, args.next())?;
+                    options.capture = Some(CaptureOutput::Frames(directory));
+                }
+                // Everything after it is the command, passed on as is.
+                "--capture-pipe" => {
+                    let program = args
+                        .next()
+                        .ok_or_else(|| format!("{} needs a program\n{}", arg, USAGE))?;
+                    options.capture = Some(CaptureOutput::Pipe {
+                        program,
+                        args: args.by_ref().collect(),
+                    });
+                }
+                "--capture-fps" => {
+                    options.capture_fps = args
+                        .next()
+                        .and_then(|fps| fps.parse().ok())
+                        .filter(|fps| *fps > 0)
+                        .ok_or_else(|| format!("{} needs a positive number\n{}", arg, USAGE))?;
+                }
+                "--shaders" => options.shaders = Some(path_argument(&arg, args.next())?),
+                _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+            }
+        }
+
+        if options.record.is_some() && options.replay.is_some() {
+            return Err(format!("Can't record and replay at once\n{}", USAGE));
+        }
+        if options.headless && options.replay.is_none() {
+            return Err(format!("--headless needs a replay\n{}", USAGE));
+        }
+        if options.headless && options.capture.is_some() {
+            return Err(format!("Can't capture without rendering\n{}", USAGE));
+        }
+
+        Ok(options)
+    }
+}
+
+fn path_argument(flag: &str, value: Option<String>) -> Result<PathBuf, String> {
+    value
+        .map(PathBuf::from)
+        .ok_or_else(|| format!("{} needs a file\n{}", flag, USAGE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| (*arg).to_owned()))
+    }
+
+    #[test]
+    fn capture_pipe_takes_the_rest_of_the_arguments_unsplit() {
+        let options = parse(&[
+            "--capture-fps",
+            "30",
+            "--capture-pipe",
+            "ffmpeg",
+            "-i",
+            "-",
+            "my video.mp4",
+            "--headless",
+        ])
+        .unwrap();
+
+        assert_eq!(options.capture_fps, 30);
+        assert!(!options.headless);
+        assert_eq!(
+            options.capture,
+            Some(CaptureOutput::Pipe {
+                program: "ffmpeg".to_owned(),
+                args: vec![
+                    "-i".to_owned(),
+                    "-".to_owned(),
+                    "my video.mp4".to_owned(),
+                    "--headless".to_owned(),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn capture_pipe_needs_a_program() {
+        assert!(parse(&["--capture-pipe"]).is_err());
+    }
+}
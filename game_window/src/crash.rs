@@ -0,0 +1,167 @@
+use crate::settings::Settings;
+use common::{build_info, logging, paths::data_dir, sync::lock_unpoisoned};
+use std::{
+    backtrace::Backtrace,
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{self, Write as _},
+    panic::{self, PanicHookInfo},
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static SETTINGS: Mutex<Option<String>> = Mutex::new(None);
+static ADAPTER: Mutex<Option<String>> = Mutex::new(None);
+
+/// Replaces the panic hook with one that writes a crash report to the data directory and tells
+/// the player where to find it.
+pub fn install() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let report = report(info);
+        match write_report(&report) {
+            Ok(path) => eprintln!(
+                "\nQBD crashed, sorry about that.\nA crash report was saved to {}\n\
+                 Please attach it when reporting the issue.",
+                path.display()
+            ),
+            Err(error) => eprintln!(
+                "\nQBD crashed, sorry about that.\nThe crash report could not be saved ({}), \
+                 here it is instead:\n\n{}",
+                error, report
+            ),
+        }
+    }));
+}
+
+/// Keeps the settings included in crash reports up to date.
+pub fn set_settings(settings: &Settings) {
+    let serialized = ron::ser::to_string_pretty(settings, ron::ser::PrettyConfig::default())
+        .unwrap_or_else(|error| format!("Could not serialize settings: {}", error));
+    store(&SETTINGS, serialized);
+}
+
+pub fn set_adapter_info(info: String) {
+    store(&ADAPTER, info);
+}
+
+fn store(slot: &Mutex<Option<String>>, value: String) {
+    *lock_unpoisoned(slot) = Some(value);
+}
+
+fn load(slot: &Mutex<Option<String>>) -> String {
+    // Never block inside the panic hook, the panicking thread might be the one holding the lock.
+    slot.try_lock()
+        .ok()
+        .and_then(|value| value.clone())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+fn report(info: &PanicHookInfo) -> String {
+    report_text(
+        &info.to_string(),
+        thread::current().name().unwrap_or("unnamed"),
+        &Backtrace::force_capture().to_string(),
+        logging::try_recent_lines(),
+    )
+}
+
+fn report_text(
+    panic: &str,
+    thread: &str,
+    backtrace: &str,
+    recent_lines: Option<Vec<String>>,
+) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(report, "QBD crash report");
+    let _ = writeln!(report, "Build: {}", build_info::summary());
+    let _ = writeln!(
+        report,
+        "OS: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report, "Adapter: {}", load(&ADAPTER));
+    let _ = writeln!(report, "Thread: {}", thread);
+    let _ = writeln!(report, "\n{}", panic);
+    let _ = writeln!(report, "\nBacktrace:\n{}", backtrace);
+    let _ = writeln!(report, "Settings:\n{}", load(&SETTINGS));
+
+    let _ = writeln!(report, "\nRecent log:");
+    match recent_lines {
+        Some(lines) => {
+            for line in lines {
+                let _ = writeln!(report, "{}", line);
+            }
+        }
+        None => {
+            let _ = writeln!(report, "(unavailable, the log was busy)");
+        }
+    }
+
+    report
+}
+
+fn write_report(report: &str) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or(0);
+
+    let directory = data_dir().join("crashes");
+    fs::create_dir_all(&directory)?;
+
+    // Threads or processes crashing at the same time mustn't overwrite each other's reports.
+    for attempt in 0.. {
+        let name = match attempt {
+            0 => format!("crash-{}.txt", timestamp),
+            _ => format!("crash-{}-{}.txt", timestamp, attempt),
+        };
+        let path = directory.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(report.as_bytes())?;
+                return Ok(path);
+            }
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    unreachable!("Ran out of crash report names")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_contains_build_settings_and_log() {
+        let mut settings = Settings::new();
+        settings.graphics_mut().set_field_of_view(87.5);
+        set_settings(&settings);
+
+        let report = report_text(
+            "panicked at 'oh no'",
+            "main",
+            "<backtrace>",
+            Some(vec!["first line".to_owned(), "second line".to_owned()]),
+        );
+
+        assert!(report.contains(&format!("Build: {}", build_info::summary())));
+        assert!(report.contains("panicked at 'oh no'"));
+        assert!(report.contains("87.5"));
+        assert!(report.contains("Recent log:\nfirst line\nsecond line\n"));
+    }
+
+    #[test]
+    fn report_says_when_the_log_was_busy() {
+        let report = report_text("panicked", "main", "<backtrace>", None);
+        assert!(report.contains("Recent log:\n(unavailable, the log was busy)"));
+    }
+}
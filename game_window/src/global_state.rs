@@ -1,5 +1,68 @@
-use crate::{settings::Settings, window::GameWindow};
+use crate::{
+    camera::CameraController,
+    input::InputState,
+    renderer::{debug_mode::DebugMode, font::Font},
+    settings::Settings,
+    window::GameWindow,
+};
+use std::io;
+
 pub struct GlobalState {
     pub settings: Settings,
     pub window: GameWindow,
+    pub input: InputState,
+    pub camera: CameraController,
+    pub debug_mode: DebugMode,
+    pub debug_overlay: bool,
+    /// Set when a screenshot is asked for, until the renderer takes it.
+    pub screenshot_requested: bool,
+    pub font: Font,
+    capture_cursor: bool,
+    /// What the settings file holds, as far as this run knows.
+    saved_settings: Settings,
+}
+
+impl GlobalState {
+    pub fn new(
+        settings: Settings,
+        window: GameWindow,
+        input: InputState,
+        camera: CameraController,
+        font: Font,
+    ) -> GlobalState {
+        Self {
+            saved_settings: settings.clone(),
+            settings,
+            window,
+            input,
+            camera,
+            debug_mode: DebugMode::None,
+            debug_overlay: false,
+            screenshot_requested: false,
+            font,
+            capture_cursor: false,
+        }
+    }
+
+    /// Whether the active state wants the cursor locked for mouse look. The window only actually
+    /// grabs it while focused.
+    pub fn capture_cursor(&self) -> bool {
+        self.capture_cursor
+    }
+
+    /// Writes the settings out, unless they haven't changed since they were loaded or last saved.
+    /// Settings that fell back to the defaults don't overwrite anything until they're changed.
+    pub fn save_settings(&mut self) -> io::Result<()> {
+        if self.settings == self.saved_settings {
+            return Ok(());
+        }
+        self.settings.save()?;
+        self.saved_settings = self.settings.clone();
+        Ok(())
+    }
+
+    pub fn set_capture_cursor(&mut self, capture: bool) {
+        self.capture_cursor = capture;
+        self.window.set_cursor_grabbed(capture);
+    }
 }
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Crouch,
+    Sprint,
+    PlaceBlock,
+    BreakBlock,
+    PickBlock,
+    NextItem,
+    PreviousItem,
+    Pause,
+    CycleDebugMode,
+    ToggleDebugOverlay,
+    Screenshot,
+}
+
+impl Action {
+    pub const ALL: [Action; 16] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Crouch,
+        Action::Sprint,
+        Action::PlaceBlock,
+        Action::BreakBlock,
+        Action::PickBlock,
+        Action::NextItem,
+        Action::PreviousItem,
+        Action::Pause,
+        Action::CycleDebugMode,
+        Action::ToggleDebugOverlay,
+        Action::Screenshot,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Jump => "jump",
+            Action::Crouch => "crouch",
+            Action::Sprint => "sprint",
+            Action::PlaceBlock => "place_block",
+            Action::BreakBlock => "break_block",
+            Action::PickBlock => "pick_block",
+            Action::NextItem => "next_item",
+            Action::PreviousItem => "previous_item",
+            Action::Pause => "pause",
+            Action::CycleDebugMode => "cycle_debug_mode",
+            Action::ToggleDebugOverlay => "toggle_debug_overlay",
+            Action::Screenshot => "screenshot",
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use super::{
+    action::Action,
+    gamepad::{AxisDirection, GamepadAxis, GamepadButton},
+    scancode,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+use winit::event::{ModifiersState, MouseButton};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+}
+
+/// The physical input that fires a binding. Keys are stored as scancodes so bindings stay on the
+/// same physical key regardless of the keyboard layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Trigger {
+    Key(u32),
+    Mouse(MouseButton),
+    Scroll(ScrollDirection),
+    GamepadButton(GamepadButton),
+    GamepadAxis(GamepadAxis, AxisDirection),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Binding {
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub modifiers: ModifiersState,
+}
+
+impl Binding {
+    pub fn new(trigger: Trigger) -> Binding {
+        Self {
+            trigger,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    pub fn with_modifiers(self, modifiers: ModifiersState) -> Binding {
+        Self { modifiers, ..self }
+    }
+
+    pub fn key(scancode: u32) -> Binding {
+        Binding::new(Trigger::Key(scancode))
+    }
+
+    pub fn mouse(button: MouseButton) -> Binding {
+        Binding::new(Trigger::Mouse(button))
+    }
+
+    pub fn scroll(direction: ScrollDirection) -> Binding {
+        Binding::new(Trigger::Scroll(direction))
+    }
+
+    pub fn gamepad_button(button: GamepadButton) -> Binding {
+        Binding::new(Trigger::GamepadButton(button))
+    }
+
+    pub fn gamepad_axis(axis: GamepadAxis, direction: AxisDirection) -> Binding {
+        Binding::new(Trigger::GamepadAxis(axis, direction))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = [
+            (self.modifiers.ctrl(), "Ctrl"),
+            (self.modifiers.shift(), "Shift"),
+            (self.modifiers.alt(), "Alt"),
+            (self.modifiers.logo(), "Super"),
+        ];
+        for (_, name) in modifiers.iter().filter(|(held, _)| *held) {
+            write!(f, "{}+", name)?;
+        }
+
+        match self.trigger {
+            Trigger::Key(code) => match scancode::name(code) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "Key {:#x}", code),
+            },
+            Trigger::Mouse(MouseButton::Left) => write!(f, "Left mouse"),
+            Trigger::Mouse(MouseButton::Right) => write!(f, "Right mouse"),
+            Trigger::Mouse(MouseButton::Middle) => write!(f, "Middle mouse"),
+            Trigger::Mouse(MouseButton::Other(button)) => write!(f, "Mouse {}", button),
+            Trigger::Scroll(ScrollDirection::Up) => write!(f, "Scroll up"),
+            Trigger::Scroll(ScrollDirection::Down) => write!(f, "Scroll down"),
+            Trigger::GamepadButton(button) => write!(f, "Gamepad {:?}", button),
+            Trigger::GamepadAxis(axis, AxisDirection::Positive) => {
+                write!(f, "Gamepad {:?}+", axis)
+            }
+            Trigger::GamepadAxis(axis, AxisDirection::Negative) => {
+                write!(f, "Gamepad {:?}-", axis)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub binding: Binding,
+    pub actions: [Action; 2],
+}
+
+impl fmt::Display for BindingConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is bound to both {} and {}",
+            self.binding,
+            self.actions[0].name(),
+            self.actions[1].name()
+        )
+    }
+}
+
+impl std::error::Error for BindingConflict {}
+
+/// Maps raw inputs to the action they trigger. An action may have several bindings, but a binding
+/// only ever triggers one action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputMap {
+    bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl InputMap {
+    pub fn new() -> InputMap {
+        Self {
+            bindings: BTreeMap::new(),
+        }
+    }
+
+    /// Adds `binding` to `action`, refusing if another action already uses it.
+    pub fn bind(&mut self, action: Action, binding: Binding) -> Result<(), BindingConflict> {
+        match self.action(binding) {
+            Some(bound) if bound == action => Ok(()),
+            Some(bound) => Err(BindingConflict {
+                binding,
+                actions: [bound, action],
+            }),
+            None => {
+                self.bindings.entry(action).or_default().push(binding);
+                Ok(())
+            }
+        }
+    }
+
+    /// Adds `binding` to `action`, removing it from whichever action had it before.
+    pub fn rebind(&mut self, action: Action, binding: Binding) -> Option<Action> {
+        let previous = self.action(binding);
+        if let Some(previous) = previous {
+            self.unbind(previous, binding);
+        }
+        self.bindings.entry(action).or_default().push(binding);
+
+        previous.filter(|previous| *previous != action)
+    }
+
+    pub fn unbind(&mut self, action: Action, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            bindings.retain(|bound| *bound != binding);
+        }
+    }
+
+    /// Puts back the default bindings of `action`, returning the ones that were taken from other
+    /// actions along with the action each was taken from.
+    pub fn reset(&mut self, action: Action) -> Vec<(Binding, Action)> {
+        let defaults = InputMap::default();
+        self.bindings.remove(&action);
+
+        defaults
+            .bindings_for(action)
+            .iter()
+            .filter_map(|binding| {
+                self.rebind(action, *binding)
+                    .map(|previous| (*binding, previous))
+            })
+            .collect()
+    }
+
+    pub fn reset_all(&mut self) {
+        *self = InputMap::default();
+    }
+
+    /// Gives actions the map has never heard of, such as ones added since it was saved, their
+    /// default bindings, as long as those are still free. Actions unbound on purpose keep an
+    /// empty entry and stay unbound.
+    pub fn add_missing_defaults(&mut self) {
+        let defaults = InputMap::default();
+        for action in Action::ALL.iter().copied() {
+            if self.bindings.contains_key(&action) {
+                continue;
+            }
+            for binding in defaults.bindings_for(action) {
+                let _ = self.bind(action, *binding);
+            }
+        }
+    }
+
+    pub fn action(&self, binding: Binding) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn bindings_for(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Action, &[Binding])> {
+        self.bindings
+            .iter()
+            .map(|(action, bindings)| (*action, bindings.as_slice()))
+    }
+
+    /// Bindings shared by more than one action, which can only come from a hand edited file.
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        let mut conflicts = Vec::new();
+        let mut seen: Vec<(Binding, Action)> = Vec::new();
+
+        for (action, bindings) in &self.bindings {
+            for binding in bindings {
+                if let Some((_, first)) = seen.iter().find(|(seen, _)| seen == binding) {
+                    conflicts.push(BindingConflict {
+                        binding: *binding,
+                        actions: [*first, *action],
+                    });
+                } else {
+                    seen.push((*binding, *action));
+                }
+            }
+        }
+
+        conflicts
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> InputMap {
+        let defaults = [
+            (Action::MoveForward, Binding::key(scancode::W)),
+            (Action::MoveBackward, Binding::key(scancode::S)),
+            (Action::MoveLeft, Binding::key(scancode::A)),
+            (Action::MoveRight, Binding::key(scancode::D)),
+            (Action::Jump, Binding::key(scancode::SPACE)),
+            (Action::Crouch, Binding::key(scancode::LSHIFT)),
+            (Action::Sprint, Binding::key(scancode::LCONTROL)),
+            (Action::Pause, Binding::key(scancode::ESCAPE)),
+            (Action::CycleDebugMode, Binding::key(scancode::F4)),
+            (Action::ToggleDebugOverlay, Binding::key(scancode::F3)),
+            (Action::Screenshot, Binding::key(scancode::F2)),
+            (Action::PlaceBlock, Binding::mouse(MouseButton::Right)),
+            (Action::BreakBlock, Binding::mouse(MouseButton::Left)),
+            (Action::PickBlock, Binding::mouse(MouseButton::Middle)),
+            (Action::NextItem, Binding::scroll(ScrollDirection::Down)),
+            (Action::PreviousItem, Binding::scroll(ScrollDirection::Up)),
+            (
+                Action::MoveForward,
+                Binding::gamepad_axis(GamepadAxis::LeftStickY, AxisDirection::Positive),
+            ),
+            (
+                Action::MoveBackward,
+                Binding::gamepad_axis(GamepadAxis::LeftStickY, AxisDirection::Negative),
+            ),
+            (
+                Action::MoveLeft,
+                Binding::gamepad_axis(GamepadAxis::LeftStickX, AxisDirection::Negative),
+            ),
+            (
+                Action::MoveRight,
+                Binding::gamepad_axis(GamepadAxis::LeftStickX, AxisDirection::Positive),
+            ),
+            (Action::Jump, Binding::gamepad_button(GamepadButton::South)),
+            (Action::Crouch, Binding::gamepad_button(GamepadButton::East)),
+            (
+                Action::Sprint,
+                Binding::gamepad_button(GamepadButton::LeftThumb),
+            ),
+            (Action::Pause, Binding::gamepad_button(GamepadButton::Start)),
+            (
+                Action::PlaceBlock,
+                Binding::gamepad_axis(GamepadAxis::LeftTrigger, AxisDirection::Positive),
+            ),
+            (
+                Action::BreakBlock,
+                Binding::gamepad_axis(GamepadAxis::RightTrigger, AxisDirection::Positive),
+            ),
+            (
+                Action::PickBlock,
+                Binding::gamepad_button(GamepadButton::West),
+            ),
+            (
+                Action::NextItem,
+                Binding::gamepad_button(GamepadButton::RightBumper),
+            ),
+            (
+                Action::PreviousItem,
+                Binding::gamepad_button(GamepadButton::LeftBumper),
+            ),
+        ];
+
+        let mut map = InputMap::new();
+        for (action, binding) in defaults.iter() {
+            map.bindings.entry(*action).or_default().push(*binding);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_reports_stolen_defaults() {
+        let mut map = InputMap::default();
+        let jump = Binding::key(scancode::SPACE);
+        assert_eq!(map.rebind(Action::Crouch, jump), Some(Action::Jump));
+
+        assert_eq!(map.reset(Action::Jump), vec![(jump, Action::Crouch)]);
+        assert_eq!(map.action(jump), Some(Action::Jump));
+        assert!(!map.bindings_for(Action::Crouch).contains(&jump));
+        assert!(map.reset(Action::Jump).is_empty());
+    }
+
+    #[test]
+    fn missing_actions_get_free_defaults() {
+        let mut map = InputMap::default();
+        map.bindings.remove(&Action::Jump);
+        map.bindings.remove(&Action::Pause);
+        map.rebind(Action::Crouch, Binding::key(scancode::ESCAPE));
+        map.unbind(Action::Sprint, Binding::key(scancode::LCONTROL));
+
+        map.add_missing_defaults();
+
+        assert_eq!(
+            map.action(Binding::key(scancode::SPACE)),
+            Some(Action::Jump)
+        );
+        // Taken since the map was saved, so it stays with the other action.
+        assert_eq!(
+            map.action(Binding::key(scancode::ESCAPE)),
+            Some(Action::Crouch)
+        );
+        // Unbound on purpose.
+        assert_eq!(map.action(Binding::key(scancode::LCONTROL)), None);
+        assert!(map.conflicts().is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use winit::event::ElementState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GamepadId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub fn is_trigger(self) -> bool {
+        matches!(self, GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger)
+    }
+}
+
+/// Which half of an axis a binding listens to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    Button {
+        id: GamepadId,
+        button: GamepadButton,
+        state: ElementState,
+    },
+    Axis {
+        id: GamepadId,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+/// Where gamepad events come from. The real implementation talks to the OS, tests inject a
+/// `VirtualGamepad` instead.
+pub trait GamepadSource {
+    fn poll(&mut self) -> Option<GamepadEvent>;
+}
+
+pub struct GilrsSource {
+    gilrs: gilrs::Gilrs,
+    pending: VecDeque<GamepadEvent>,
+}
+
+impl GilrsSource {
+    /// Returns `None` when the platform has no gamepad support.
+    pub fn new() -> Option<GilrsSource> {
+        let gilrs = gilrs::Gilrs::new().ok()?;
+
+        // Gamepads plugged in before startup don't get a connection event.
+        let pending = gilrs
+            .gamepads()
+            .map(|(id, _)| GamepadEvent::Connected(GamepadId(id.into())))
+            .collect();
+
+        Some(Self { gilrs, pending })
+    }
+}
+
+impl GamepadSource for GilrsSource {
+    fn poll(&mut self) -> Option<GamepadEvent> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            let id = GamepadId(id.into());
+            let event = match event {
+                gilrs::EventType::Connected => Some(GamepadEvent::Connected(id)),
+                gilrs::EventType::Disconnected => Some(GamepadEvent::Disconnected(id)),
+                gilrs::EventType::ButtonPressed(button, _) => {
+                    convert_button(button).map(|button| GamepadEvent::Button {
+                        id,
+                        button,
+                        state: ElementState::Pressed,
+                    })
+                }
+                gilrs::EventType::ButtonReleased(button, _) => {
+                    convert_button(button).map(|button| GamepadEvent::Button {
+                        id,
+                        button,
+                        state: ElementState::Released,
+                    })
+                }
+                // Analog triggers are reported as buttons with a value.
+                gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    Some(GamepadEvent::Axis {
+                        id,
+                        axis: GamepadAxis::LeftTrigger,
+                        value,
+                    })
+                }
+                gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    Some(GamepadEvent::Axis {
+                        id,
+                        axis: GamepadAxis::RightTrigger,
+                        value,
+                    })
+                }
+                gilrs::EventType::AxisChanged(axis, value, _) => {
+                    convert_axis(axis).map(|axis| GamepadEvent::Axis { id, axis, value })
+                }
+                _ => None,
+            };
+
+            if event.is_some() {
+                return event;
+            }
+        }
+
+        None
+    }
+}
+
+fn convert_button(button: gilrs::Button) -> Option<GamepadButton> {
+    Some(match button {
+        gilrs::Button::South => GamepadButton::South,
+        gilrs::Button::East => GamepadButton::East,
+        gilrs::Button::North => GamepadButton::North,
+        gilrs::Button::West => GamepadButton::West,
+        gilrs::Button::LeftTrigger => GamepadButton::LeftBumper,
+        gilrs::Button::RightTrigger => GamepadButton::RightBumper,
+        gilrs::Button::Select => GamepadButton::Select,
+        gilrs::Button::Start => GamepadButton::Start,
+        gilrs::Button::LeftThumb => GamepadButton::LeftThumb,
+        gilrs::Button::RightThumb => GamepadButton::RightThumb,
+        gilrs::Button::DPadUp => GamepadButton::DPadUp,
+        gilrs::Button::DPadDown => GamepadButton::DPadDown,
+        gilrs::Button::DPadLeft => GamepadButton::DPadLeft,
+        gilrs::Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn convert_axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+    Some(match axis {
+        gilrs::Axis::LeftStickX => GamepadAxis::LeftStickX,
+        gilrs::Axis::LeftStickY => GamepadAxis::LeftStickY,
+        gilrs::Axis::RightStickX => GamepadAxis::RightStickX,
+        gilrs::Axis::RightStickY => GamepadAxis::RightStickY,
+        _ => return None,
+    })
+}
+
+/// A scripted gamepad. Clones share the same event queue, so a test can keep one handle and give
+/// the other to `InputState`.
+#[derive(Clone)]
+pub struct VirtualGamepad {
+    id: GamepadId,
+    events: Rc<RefCell<VecDeque<GamepadEvent>>>,
+}
+
+impl VirtualGamepad {
+    pub fn new(id: GamepadId) -> VirtualGamepad {
+        Self {
+            id,
+            events: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    pub fn connect(&self) {
+        self.push(GamepadEvent::Connected(self.id));
+    }
+
+    pub fn disconnect(&self) {
+        self.push(GamepadEvent::Disconnected(self.id));
+    }
+
+    pub fn press(&self, button: GamepadButton) {
+        self.push(GamepadEvent::Button {
+            id: self.id,
+            button,
+            state: ElementState::Pressed,
+        });
+    }
+
+    pub fn release(&self, button: GamepadButton) {
+        self.push(GamepadEvent::Button {
+            id: self.id,
+            button,
+            state: ElementState::Released,
+        });
+    }
+
+    pub fn set_axis(&self, axis: GamepadAxis, value: f32) {
+        self.push(GamepadEvent::Axis {
+            id: self.id,
+            axis,
+            value,
+        });
+    }
+
+    fn push(&self, event: GamepadEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+}
+
+impl GamepadSource for VirtualGamepad {
+    fn poll(&mut self) -> Option<GamepadEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadSettings {
+    stick_dead_zone: f32,
+    trigger_dead_zone: f32,
+    sensitivity: f32,
+    response_curve: f32,
+}
+
+impl GamepadSettings {
+    pub fn new() -> GamepadSettings {
+        Self {
+            stick_dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+            sensitivity: 1.0,
+            response_curve: 2.0,
+        }
+    }
+
+    pub fn stick_dead_zone(&self) -> f32 {
+        self.stick_dead_zone
+    }
+
+    pub fn trigger_dead_zone(&self) -> f32 {
+        self.trigger_dead_zone
+    }
+
+    pub fn sensitivity(&self) -> f32 {
+        self.sensitivity
+    }
+
+    /// Exponent applied to the axis value once outside the dead zone; 1.0 is linear, higher
+    /// values give finer control near the centre.
+    pub fn response_curve(&self) -> f32 {
+        self.response_curve
+    }
+
+    pub fn set_stick_dead_zone(&mut self, dead_zone: f32) {
+        self.stick_dead_zone = dead_zone;
+    }
+
+    pub fn set_trigger_dead_zone(&mut self, dead_zone: f32) {
+        self.trigger_dead_zone = dead_zone;
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity;
+    }
+
+    pub fn set_response_curve(&mut self, response_curve: f32) {
+        self.response_curve = response_curve;
+    }
+
+    /// Maps a raw axis value to the value game logic sees: the dead zone is removed, the rest of
+    /// the range is rescaled to 0..1 and shaped by the response curve and sensitivity.
+    pub fn apply(&self, axis: GamepadAxis, raw: f32) -> f32 {
+        let dead_zone = if axis.is_trigger() {
+            self.trigger_dead_zone
+        } else {
+            self.stick_dead_zone
+        };
+
+        let magnitude = raw.abs();
+        if magnitude <= dead_zone {
+            return 0.0;
+        }
+
+        let scaled = ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0);
+        let shaped = scaled.powf(self.response_curve) * self.sensitivity;
+
+        shaped.min(1.0).copysign(raw)
+    }
+}
+
+impl Default for GamepadSettings {
+    fn default() -> GamepadSettings {
+        GamepadSettings::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::{action::Action, InputState},
+        settings::Settings,
+    };
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-5,
+            "{} isn't {}",
+            value,
+            expected
+        );
+    }
+
+    fn input_with(pads: &[&VirtualGamepad]) -> InputState {
+        let mut input = InputState::new(&Settings::new());
+        for pad in pads {
+            pad.connect();
+        }
+        // One source is enough, every clone shares the queue.
+        if let Some(pad) = pads.first() {
+            input.set_gamepad_source(Box::new((*pad).clone()));
+        }
+        input
+    }
+
+    #[test]
+    fn button_edges_become_action_events() {
+        let pad = VirtualGamepad::new(GamepadId(0));
+        let mut input = input_with(&[&pad]);
+
+        pad.press(GamepadButton::South);
+        let tick = input.tick();
+        assert!(tick.pressed(Action::Jump));
+        assert!(tick.held(Action::Jump));
+        assert_eq!(input.gamepads().collect::<Vec<_>>(), vec![GamepadId(0)]);
+
+        let tick = input.tick();
+        assert!(!tick.pressed(Action::Jump));
+        assert!(tick.held(Action::Jump));
+
+        pad.release(GamepadButton::South);
+        let tick = input.tick();
+        assert!(tick.released(Action::Jump));
+        assert!(!tick.held(Action::Jump));
+    }
+
+    #[test]
+    fn buttons_stay_held_until_every_pad_lets_go() {
+        let first = VirtualGamepad::new(GamepadId(0));
+        let second = VirtualGamepad {
+            id: GamepadId(1),
+            events: first.events.clone(),
+        };
+        let mut input = input_with(&[&first, &second]);
+
+        first.press(GamepadButton::East);
+        second.press(GamepadButton::East);
+        first.release(GamepadButton::East);
+        let tick = input.tick();
+        assert!(tick.held(Action::Crouch));
+        assert!(!tick.released(Action::Crouch));
+
+        second.disconnect();
+        let tick = input.tick();
+        assert!(tick.released(Action::Crouch));
+        assert_eq!(input.gamepads().collect::<Vec<_>>(), vec![GamepadId(0)]);
+    }
+
+    #[test]
+    fn sticks_map_to_axis_actions() {
+        let pad = VirtualGamepad::new(GamepadId(0));
+        let mut input = input_with(&[&pad]);
+        let settings = GamepadSettings::new();
+
+        pad.set_axis(GamepadAxis::LeftStickY, 1.0);
+        pad.set_axis(GamepadAxis::LeftStickX, -0.4);
+        let tick = input.tick();
+        assert_close(tick.value(Action::MoveForward), 1.0);
+        assert!(tick.pressed(Action::MoveForward));
+        assert_close(tick.value(Action::MoveBackward), 0.0);
+        assert_close(
+            tick.value(Action::MoveLeft),
+            settings.apply(GamepadAxis::LeftStickX, 0.4),
+        );
+        // Below the press threshold, so only the analog value is there.
+        assert!(!tick.held(Action::MoveLeft));
+        assert_close(tick.value(Action::MoveRight), 0.0);
+
+        pad.set_axis(GamepadAxis::LeftStickY, 0.05);
+        let tick = input.tick();
+        assert!(tick.released(Action::MoveForward));
+        assert_close(tick.value(Action::MoveForward), 0.0);
+    }
+
+    #[test]
+    fn dead_zones_depend_on_the_axis() {
+        let settings = GamepadSettings::new();
+        assert_close(settings.apply(GamepadAxis::LeftStickX, 0.15), 0.0);
+        assert_close(settings.apply(GamepadAxis::RightStickY, -0.1), 0.0);
+        assert!(settings.apply(GamepadAxis::LeftTrigger, 0.1) > 0.0);
+        assert_close(settings.apply(GamepadAxis::LeftStickX, 1.0), 1.0);
+        assert_close(settings.apply(GamepadAxis::LeftStickX, -1.0), -1.0);
+    }
+
+    #[test]
+    fn response_curve_and_sensitivity_shape_the_rest() {
+        let mut settings = GamepadSettings::new();
+        settings.set_stick_dead_zone(0.2);
+        settings.set_response_curve(1.0);
+        assert_close(settings.apply(GamepadAxis::LeftStickX, 0.6), 0.5);
+        assert_close(settings.apply(GamepadAxis::LeftStickX, -0.6), -0.5);
+
+        settings.set_response_curve(2.0);
+        assert_close(settings.apply(GamepadAxis::LeftStickX, 0.6), 0.25);
+
+        settings.set_response_curve(1.0);
+        settings.set_sensitivity(2.0);
+        assert_close(settings.apply(GamepadAxis::LeftStickX, 0.6), 1.0);
+        assert_close(settings.apply(GamepadAxis::LeftStickX, 0.4), 0.5);
+        assert_close(settings.apply(GamepadAxis::LeftStickX, -0.9), -1.0);
+    }
+}
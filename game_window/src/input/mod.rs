@@ -0,0 +1,670 @@
+pub mod action;
+pub mod binding;
+pub mod gamepad;
+pub mod mouse;
+pub mod recording;
+pub mod scancode;
+
+use self::{
+    action::Action,
+    binding::{Binding, InputMap, ScrollDirection, Trigger},
+    gamepad::{
+        AxisDirection, GamepadAxis, GamepadButton, GamepadEvent, GamepadId, GamepadSettings,
+        GamepadSource,
+    },
+    mouse::MouseSettings,
+    recording::{RecordEntry, Recorder},
+};
+use crate::{
+    settings::Settings,
+    ui::input::{UiInput, UiKey},
+};
+use common::math::Vec2;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    time::Duration,
+};
+use tracing::{info, warn};
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionEvent {
+    pub action: Action,
+    pub state: ElementState,
+}
+
+/// Raw input after it has been translated out of winit, the form in which it is recorded and
+/// replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Trigger(Trigger, ElementState),
+    Modifiers(ModifiersState),
+    FocusLost,
+    MouseMotion(f64, f64),
+    Gamepad(GamepadEvent),
+    /// Cursor position in logical pixels.
+    CursorMoved(f64, f64),
+    CursorLeft,
+    Text(char),
+    UiKey(UiKey),
+}
+
+/// Everything deciding which actions raw input turns into. Recordings carry their own, so a
+/// replay doesn't depend on the settings at the time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputConfig {
+    pub key_bindings: InputMap,
+    pub mouse: MouseSettings,
+    pub gamepad: GamepadSettings,
+}
+
+/// Everything game logic needs to know about input for a single fixed tick.
+#[derive(Debug, Clone, Default)]
+pub struct TickInput {
+    events: Vec<ActionEvent>,
+    held: HashSet<Action>,
+    values: HashMap<Action, f32>,
+    look_delta: [f32; 2],
+    triggers: Vec<Trigger>,
+    ui: UiInput,
+}
+
+impl TickInput {
+    pub fn events(&self) -> &[ActionEvent] {
+        &self.events
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.events
+            .iter()
+            .any(|event| event.action == action && event.state == ElementState::Pressed)
+    }
+
+    pub fn released(&self, action: Action) -> bool {
+        self.events
+            .iter()
+            .any(|event| event.action == action && event.state == ElementState::Released)
+    }
+
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// How strongly the action is held, from 0 to 1. Digital inputs are either 0 or 1, gamepad
+    /// axes report their analog value.
+    pub fn value(&self, action: Action) -> f32 {
+        let digital = if self.held(action) { 1.0 } else { 0.0 };
+        self.values
+            .get(&action)
+            .copied()
+            .unwrap_or(0.0)
+            .max(digital)
+    }
+
+    /// Yaw and pitch change in radians from mouse movement during this tick.
+    pub fn look_delta(&self) -> [f32; 2] {
+        self.look_delta
+    }
+
+    /// Triggers newly pressed during this tick, whether bound or not, in the order they were
+    /// pressed. Used to capture a new binding.
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    pub fn ui(&self) -> &UiInput {
+        &self.ui
+    }
+}
+
+// How far an axis has to be pushed before its binding counts as pressed.
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+/// Turns raw window events into actions, buffering them until the next fixed tick.
+pub struct InputState {
+    map: InputMap,
+    modifiers: ModifiersState,
+    held_bindings: HashSet<Binding>,
+    pending: Vec<ActionEvent>,
+    mouse_settings: MouseSettings,
+    mouse_delta: [f64; 2],
+    gamepad_settings: GamepadSettings,
+    gamepad_source: Option<Box<dyn GamepadSource>>,
+    gamepads: HashSet<GamepadId>,
+    gamepad_buttons: HashSet<(GamepadId, GamepadButton)>,
+    gamepad_axes: HashMap<(GamepadId, GamepadAxis), f32>,
+    recorder: Option<Recorder>,
+    scale_factor: f64,
+    pressed_triggers: Vec<Trigger>,
+    ui: UiInput,
+}
+
+impl InputState {
+    pub fn new(settings: &Settings) -> InputState {
+        Self {
+            map: settings.key_bindings().clone(),
+            modifiers: ModifiersState::empty(),
+            held_bindings: HashSet::new(),
+            pending: Vec::new(),
+            mouse_settings: *settings.mouse(),
+            mouse_delta: [0.0, 0.0],
+            gamepad_settings: *settings.gamepad(),
+            gamepad_source: None,
+            gamepads: HashSet::new(),
+            gamepad_buttons: HashSet::new(),
+            gamepad_axes: HashMap::new(),
+            recorder: None,
+            scale_factor: 1.0,
+            pressed_triggers: Vec::new(),
+            ui: UiInput::new(),
+        }
+    }
+
+    pub fn set_gamepad_source(&mut self, source: Box<dyn GamepadSource>) {
+        self.gamepad_source = Some(source);
+    }
+
+    pub fn config(&self) -> InputConfig {
+        InputConfig {
+            key_bindings: self.map.clone(),
+            mouse: self.mouse_settings,
+            gamepad: self.gamepad_settings,
+        }
+    }
+
+    /// Recorded along with input, so a replay switches config at the same point.
+    pub fn set_config(&mut self, config: InputConfig) {
+        self.record(RecordEntry::Config(config.clone()));
+        self.map = config.key_bindings;
+        self.mouse_settings = config.mouse;
+        self.gamepad_settings = config.gamepad;
+    }
+
+    /// Physical pixels per logical one, for converting cursor positions.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn gamepads(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.gamepads.iter().copied()
+    }
+
+    pub fn map(&self) -> &InputMap {
+        &self.map
+    }
+
+    pub fn map_mut(&mut self) -> &mut InputMap {
+        &mut self.map
+    }
+
+    /// Every event from here on, along with tick and frame markers, is written to `recorder`.
+    pub fn start_recording(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn record_frame(&mut self, elapsed: Duration) {
+        self.record(RecordEntry::Frame(elapsed));
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        scancode,
+                        state,
+                        virtual_keycode,
+                        ..
+                    },
+                ..
+            } => {
+                self.handle_event(InputEvent::Trigger(Trigger::Key(*scancode), *state));
+                if *state == ElementState::Pressed {
+                    if let Some(key) = virtual_keycode.and_then(|code| self.ui_key(code)) {
+                        self.handle_event(InputEvent::UiKey(key));
+                    }
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = position.to_logical::<f64>(self.scale_factor);
+                self.handle_event(InputEvent::CursorMoved(position.x, position.y))
+            }
+            WindowEvent::CursorLeft { .. } => self.handle_event(InputEvent::CursorLeft),
+            WindowEvent::ReceivedCharacter(character) if !character.is_control() => {
+                self.handle_event(InputEvent::Text(*character))
+            }
+            WindowEvent::MouseInput { button, state, .. } => {
+                self.handle_event(InputEvent::Trigger(Trigger::Mouse(*button), *state))
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.handle_event(InputEvent::Modifiers(*modifiers))
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                    MouseScrollDelta::PixelDelta(position) => position.y,
+                };
+                let direction = if y > 0.0 {
+                    ScrollDirection::Up
+                } else if y < 0.0 {
+                    ScrollDirection::Down
+                } else {
+                    return;
+                };
+                // Scrolling has no held state, so it is reported as a press immediately followed
+                // by a release within the same tick.
+                let trigger = Trigger::Scroll(direction);
+                self.handle_event(InputEvent::Trigger(trigger, ElementState::Pressed));
+                self.handle_event(InputEvent::Trigger(trigger, ElementState::Released));
+            }
+            WindowEvent::Focused(false) => self.handle_event(InputEvent::FocusLost),
+            _ => (),
+        }
+    }
+
+    /// Raw mouse movement, only fed in while the cursor is captured.
+    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        self.handle_event(InputEvent::MouseMotion(delta.0, delta.1));
+    }
+
+    pub fn handle_event(&mut self, event: InputEvent) {
+        self.record(RecordEntry::Input(event));
+
+        match event {
+            InputEvent::Trigger(trigger, state) => {
+                if trigger == Trigger::Mouse(MouseButton::Left) {
+                    self.ui.set_pointer(state == ElementState::Pressed);
+                }
+                self.update_trigger(trigger, state)
+            }
+            InputEvent::Modifiers(modifiers) => self.modifiers = modifiers,
+            InputEvent::FocusLost => {
+                self.ui.set_pointer(false);
+                self.release_all()
+            }
+            InputEvent::MouseMotion(x, y) => {
+                self.mouse_delta[0] += x;
+                self.mouse_delta[1] += y;
+            }
+            InputEvent::Gamepad(event) => self.handle_gamepad_event(event),
+            InputEvent::CursorMoved(x, y) => {
+                self.ui.cursor = Some(Vec2::new(x as f32, y as f32));
+            }
+            InputEvent::CursorLeft => self.ui.cursor = None,
+            InputEvent::Text(character) => self.ui.text.push(character),
+            InputEvent::UiKey(key) => self.ui.keys.push(key),
+        }
+    }
+
+    fn ui_key(&self, code: VirtualKeyCode) -> Option<UiKey> {
+        let key = match code {
+            VirtualKeyCode::Up => UiKey::Up,
+            VirtualKeyCode::Down => UiKey::Down,
+            VirtualKeyCode::Left => UiKey::Left,
+            VirtualKeyCode::Right => UiKey::Right,
+            VirtualKeyCode::Tab if self.modifiers.shift() => UiKey::BackTab,
+            VirtualKeyCode::Tab => UiKey::Tab,
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => UiKey::Enter,
+            VirtualKeyCode::Back => UiKey::Backspace,
+            VirtualKeyCode::Delete => UiKey::Delete,
+            VirtualKeyCode::Home => UiKey::Home,
+            VirtualKeyCode::End => UiKey::End,
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    fn handle_gamepad_event(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Connected(id) => {
+                info!("Gamepad {} connected", id.0);
+                self.gamepads.insert(id);
+            }
+            GamepadEvent::Disconnected(id) => {
+                info!("Gamepad {} disconnected", id.0);
+                let buttons: Vec<GamepadButton> = self
+                    .gamepad_buttons
+                    .iter()
+                    .filter(|(pad, _)| *pad == id)
+                    .map(|(_, button)| *button)
+                    .collect();
+                for button in buttons {
+                    self.update_gamepad_button(id, button, ElementState::Released);
+                }
+
+                let axes: Vec<GamepadAxis> = self
+                    .gamepad_axes
+                    .keys()
+                    .filter(|(pad, _)| *pad == id)
+                    .map(|(_, axis)| *axis)
+                    .collect();
+                for axis in axes {
+                    self.update_gamepad_axis(id, axis, 0.0);
+                }
+
+                self.gamepads.remove(&id);
+            }
+            GamepadEvent::Button { id, button, state } => {
+                self.gamepads.insert(id);
+                // Menus are navigated with the d-pad and confirmed with the bottom face button.
+                if state == ElementState::Pressed {
+                    let key = match button {
+                        GamepadButton::DPadUp => Some(UiKey::Up),
+                        GamepadButton::DPadDown => Some(UiKey::Down),
+                        GamepadButton::DPadLeft => Some(UiKey::Left),
+                        GamepadButton::DPadRight => Some(UiKey::Right),
+                        GamepadButton::South => Some(UiKey::Enter),
+                        _ => None,
+                    };
+                    self.ui.keys.extend(key);
+                }
+                self.update_gamepad_button(id, button, state);
+            }
+            GamepadEvent::Axis { id, axis, value } => {
+                self.gamepads.insert(id);
+                let value = self.gamepad_settings.apply(axis, value);
+                self.update_gamepad_axis(id, axis, value);
+            }
+        }
+    }
+
+    /// Drains the events buffered since the previous tick.
+    pub fn tick(&mut self) -> TickInput {
+        self.poll_gamepads();
+        self.record(RecordEntry::Tick);
+
+        let held = self
+            .held_bindings
+            .iter()
+            .filter_map(|binding| self.map.action(*binding))
+            .collect();
+
+        let mut values: HashMap<Action, f32> = HashMap::new();
+        for (action, bindings) in self.map.iter() {
+            for binding in bindings {
+                if let Trigger::GamepadAxis(axis, direction) = binding.trigger {
+                    let value = self.axis_value(axis, direction);
+                    let entry = values.entry(action).or_insert(0.0);
+                    *entry = entry.max(value);
+                }
+            }
+        }
+
+        let mouse_delta = std::mem::take(&mut self.mouse_delta);
+
+        TickInput {
+            events: std::mem::take(&mut self.pending),
+            held,
+            values,
+            look_delta: self.mouse_settings.look_delta(mouse_delta),
+            triggers: std::mem::take(&mut self.pressed_triggers),
+            ui: self.ui.take_frame(),
+        }
+    }
+
+    fn release_all(&mut self) {
+        let held: Vec<Binding> = self.held_bindings.iter().copied().collect();
+        for binding in held {
+            self.update_binding(binding, ElementState::Released);
+        }
+        self.modifiers = ModifiersState::empty();
+    }
+
+    fn poll_gamepads(&mut self) {
+        let mut events = Vec::new();
+        if let Some(source) = &mut self.gamepad_source {
+            while let Some(event) = source.poll() {
+                events.push(event);
+            }
+        }
+
+        for event in events {
+            self.handle_event(InputEvent::Gamepad(event));
+        }
+    }
+
+    fn record(&mut self, entry: RecordEntry) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(error) = recorder.record(entry) {
+                warn!("Input recording stopped: {}", error);
+                self.recorder = None;
+            }
+        }
+    }
+
+    fn update_gamepad_button(&mut self, id: GamepadId, button: GamepadButton, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                self.gamepad_buttons.insert((id, button));
+            }
+            ElementState::Released => {
+                self.gamepad_buttons.remove(&(id, button));
+            }
+        }
+
+        // With several gamepads the button stays held until every pad has released it.
+        let held = self.gamepad_buttons.iter().any(|(_, held)| *held == button);
+        let state = if held {
+            ElementState::Pressed
+        } else {
+            ElementState::Released
+        };
+        self.update_trigger(Trigger::GamepadButton(button), state);
+    }
+
+    fn update_gamepad_axis(&mut self, id: GamepadId, axis: GamepadAxis, value: f32) {
+        self.gamepad_axes.insert((id, axis), value);
+
+        for direction in [AxisDirection::Positive, AxisDirection::Negative].iter() {
+            let state = if self.axis_value(axis, *direction) > AXIS_PRESS_THRESHOLD {
+                ElementState::Pressed
+            } else {
+                ElementState::Released
+            };
+            self.update_trigger(Trigger::GamepadAxis(axis, *direction), state);
+        }
+    }
+
+    // The strongest push in `direction` across every connected gamepad.
+    fn axis_value(&self, axis: GamepadAxis, direction: AxisDirection) -> f32 {
+        self.gamepad_axes
+            .iter()
+            .filter(|((_, pad_axis), _)| *pad_axis == axis)
+            .map(|(_, value)| match direction {
+                AxisDirection::Positive => value.max(0.0),
+                AxisDirection::Negative => (-value).max(0.0),
+            })
+            .fold(0.0, f32::max)
+    }
+
+    fn update_trigger(&mut self, trigger: Trigger, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                let repeat = self
+                    .held_bindings
+                    .iter()
+                    .any(|binding| binding.trigger == trigger);
+                if !repeat {
+                    self.pressed_triggers.push(trigger);
+                }
+
+                // A binding with the exact modifiers held wins, otherwise the modifiers are
+                // ignored so that, for example, movement keeps working while crouching on shift.
+                let exact = Binding::new(trigger).with_modifiers(self.modifiers);
+                let binding = if self.map.action(exact).is_some() {
+                    exact
+                } else {
+                    Binding::new(trigger)
+                };
+                self.update_binding(binding, state);
+            }
+            ElementState::Released => {
+                let held: Vec<Binding> = self
+                    .held_bindings
+                    .iter()
+                    .filter(|binding| binding.trigger == trigger)
+                    .copied()
+                    .collect();
+                for binding in held {
+                    self.update_binding(binding, state);
+                }
+            }
+        }
+    }
+
+    fn update_binding(&mut self, binding: Binding, state: ElementState) {
+        let changed = match state {
+            ElementState::Pressed => self.held_bindings.insert(binding),
+            ElementState::Released => self.held_bindings.remove(&binding),
+        };
+
+        // Key repeats arrive as extra presses, those are not new edges.
+        if !changed {
+            return;
+        }
+
+        if let Some(action) = self.map.action(binding) {
+            let action_was_held = self
+                .held_bindings
+                .iter()
+                .any(|held| *held != binding && self.map.action(*held) == Some(action));
+
+            if !action_was_held {
+                self.pending.push(ActionEvent { action, state });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::scancode;
+
+    /// Input going through `map` alone, whatever the default bindings are.
+    fn input_with(bindings: &[(Action, Binding)]) -> InputState {
+        let mut input = InputState::new(&Settings::new());
+        let map = input.map_mut();
+        *map = InputMap::new();
+        for (action, binding) in bindings {
+            map.bind(*action, *binding).unwrap();
+        }
+        input
+    }
+
+    fn key(input: &mut InputState, code: u32, state: ElementState) {
+        input.handle_event(InputEvent::Trigger(Trigger::Key(code), state));
+    }
+
+    #[test]
+    fn a_press_and_release_within_one_tick_are_both_seen() {
+        let mut input = input_with(&[(Action::Jump, Binding::key(scancode::SPACE))]);
+
+        key(&mut input, scancode::SPACE, ElementState::Pressed);
+        key(&mut input, scancode::SPACE, ElementState::Released);
+        let tick = input.tick();
+
+        assert!(tick.pressed(Action::Jump));
+        assert!(tick.released(Action::Jump));
+        assert!(!tick.held(Action::Jump));
+        assert!(input.tick().events().is_empty());
+    }
+
+    #[test]
+    fn a_key_held_across_ticks_only_has_edges_on_the_first_and_last() {
+        let mut input = input_with(&[(Action::MoveForward, Binding::key(scancode::W))]);
+
+        key(&mut input, scancode::W, ElementState::Pressed);
+        let first = input.tick();
+        assert!(first.pressed(Action::MoveForward));
+        assert!(first.held(Action::MoveForward));
+        assert_eq!(first.value(Action::MoveForward), 1.0);
+
+        // Key repeats arrive as more presses.
+        key(&mut input, scancode::W, ElementState::Pressed);
+        let second = input.tick();
+        assert!(second.events().is_empty());
+        assert!(second.held(Action::MoveForward));
+
+        key(&mut input, scancode::W, ElementState::Released);
+        let last = input.tick();
+        assert!(last.released(Action::MoveForward));
+        assert!(!last.held(Action::MoveForward));
+    }
+
+    #[test]
+    fn an_action_stays_held_until_every_binding_is_released() {
+        let mut input = input_with(&[
+            (Action::Jump, Binding::key(scancode::SPACE)),
+            (Action::Jump, Binding::key(scancode::W)),
+        ]);
+
+        key(&mut input, scancode::SPACE, ElementState::Pressed);
+        key(&mut input, scancode::W, ElementState::Pressed);
+        assert_eq!(input.tick().events().len(), 1);
+
+        key(&mut input, scancode::SPACE, ElementState::Released);
+        let tick = input.tick();
+        assert!(tick.events().is_empty());
+        assert!(tick.held(Action::Jump));
+
+        key(&mut input, scancode::W, ElementState::Released);
+        assert!(input.tick().released(Action::Jump));
+    }
+
+    #[test]
+    fn bindings_with_the_held_modifiers_win_over_plain_ones() {
+        let mut input = input_with(&[
+            (Action::Jump, Binding::key(scancode::F2)),
+            (
+                Action::Screenshot,
+                Binding::key(scancode::F2).with_modifiers(ModifiersState::CTRL),
+            ),
+            (Action::MoveForward, Binding::key(scancode::W)),
+        ]);
+
+        input.handle_event(InputEvent::Modifiers(ModifiersState::CTRL));
+        key(&mut input, scancode::F2, ElementState::Pressed);
+        // Nothing is bound to ctrl+W, so it's plain W.
+        key(&mut input, scancode::W, ElementState::Pressed);
+        let tick = input.tick();
+        assert!(tick.pressed(Action::Screenshot));
+        assert!(!tick.pressed(Action::Jump));
+        assert!(tick.pressed(Action::MoveForward));
+
+        // Releasing the modifier first still releases what it pressed.
+        input.handle_event(InputEvent::Modifiers(ModifiersState::empty()));
+        key(&mut input, scancode::F2, ElementState::Released);
+        assert!(input.tick().released(Action::Screenshot));
+
+        key(&mut input, scancode::F2, ElementState::Pressed);
+        let tick = input.tick();
+        assert!(tick.pressed(Action::Jump));
+        assert!(!tick.pressed(Action::Screenshot));
+    }
+
+    #[test]
+    fn losing_focus_releases_everything_held() {
+        let mut input = input_with(&[(Action::MoveForward, Binding::key(scancode::W))]);
+
+        key(&mut input, scancode::W, ElementState::Pressed);
+        input.tick();
+        input.handle_event(InputEvent::FocusLost);
+        let tick = input.tick();
+
+        assert!(tick.released(Action::MoveForward));
+        assert!(!tick.held(Action::MoveForward));
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+// Radians turned per raw mouse count at a sensitivity of 1.
+const RADIANS_PER_COUNT: f32 = 0.0022;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseSettings {
+    sensitivity: f32,
+    invert_y: bool,
+}
+
+impl MouseSettings {
+    pub fn new() -> MouseSettings {
+        Self {
+            sensitivity: 1.0,
+            invert_y: false,
+        }
+    }
+
+    pub fn sensitivity(&self) -> f32 {
+        self.sensitivity
+    }
+
+    pub fn invert_y(&self) -> bool {
+        self.invert_y
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity;
+    }
+
+    pub fn set_invert_y(&mut self, invert_y: bool) {
+        self.invert_y = invert_y;
+    }
+
+    /// Converts raw mouse counts into a yaw and pitch delta in radians. Moving the mouse up looks
+    /// up unless the Y axis is inverted.
+    pub fn look_delta(&self, counts: [f64; 2]) -> [f32; 2] {
+        let scale = RADIANS_PER_COUNT * self.sensitivity;
+        let pitch_sign = if self.invert_y { 1.0 } else { -1.0 };
+
+        [
+            counts[0] as f32 * scale,
+            counts[1] as f32 * scale * pitch_sign,
+        ]
+    }
+}
+
+impl Default for MouseSettings {
+    fn default() -> MouseSettings {
+        MouseSettings::new()
+    }
+}
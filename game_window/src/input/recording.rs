@@ -0,0 +1,187 @@
+use super::{InputConfig, InputEvent};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Cursor, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const MAGIC: [u8; 4] = *b"QBDR";
+const FORMAT_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordEntry {
+    Input(InputEvent),
+    /// The input settings were changed to these, applying to every input recorded after it.
+    Config(InputConfig),
+    /// A fixed tick ran, consuming every input recorded before it.
+    Tick,
+    /// A frame was rendered with the given time since startup.
+    Frame(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub time: Duration,
+    pub entry: RecordEntry,
+}
+
+/// Streams input, tick and frame markers to a file as they happen, after the input config they
+/// went through at the start.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, config: &InputConfig) -> io::Result<Recorder> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, config).map_err(|error| into_io_error(*error))?;
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, entry: RecordEntry) -> io::Result<()> {
+        let record = Record {
+            time: self.start.elapsed(),
+            entry,
+        };
+        bincode::serialize_into(&mut self.writer, &record).map_err(|error| into_io_error(*error))
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// A recording loaded back into memory, handed out one record at a time.
+pub struct Replay {
+    config: InputConfig,
+    records: VecDeque<Record>,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> io::Result<Replay> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        let mut reader = Cursor::new(bytes);
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+
+        if header[..4] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an input recording",
+            ));
+        }
+        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if version != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported recording version {}", version),
+            ));
+        }
+
+        let config =
+            bincode::deserialize_from(&mut reader).map_err(|error| into_io_error(*error))?;
+        let mut records = VecDeque::new();
+        while (reader.position() as usize) < reader.get_ref().len() {
+            records.push_back(
+                bincode::deserialize_from(&mut reader).map_err(|error| into_io_error(*error))?,
+            );
+        }
+
+        Ok(Self { config, records })
+    }
+
+    /// The input config in use when recording started, which the replay has to go through
+    /// instead of the current settings until a `RecordEntry::Config` replaces it.
+    pub fn config(&self) -> &InputConfig {
+        &self.config
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn peek(&self) -> Option<&Record> {
+        self.records.front()
+    }
+}
+
+impl Iterator for Replay {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        self.records.pop_front()
+    }
+}
+
+fn into_io_error(error: bincode::ErrorKind) -> io::Error {
+    match error {
+        bincode::ErrorKind::Io(error) => error,
+        error => io::Error::new(io::ErrorKind::InvalidData, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{
+        action::Action,
+        binding::{Binding, InputMap, Trigger},
+        gamepad::GamepadSettings,
+        mouse::MouseSettings,
+        scancode,
+    };
+    use std::{env, fs, process};
+    use winit::event::ElementState;
+
+    #[test]
+    fn replays_carry_the_recorded_configs() {
+        let mut key_bindings = InputMap::default();
+        key_bindings.rebind(Action::Jump, Binding::key(scancode::F4));
+        let mut mouse = MouseSettings::new();
+        mouse.set_sensitivity(2.5);
+        let mut gamepad = GamepadSettings::new();
+        gamepad.set_stick_dead_zone(0.3);
+        let config = InputConfig {
+            key_bindings,
+            mouse,
+            gamepad,
+        };
+        let entries = [
+            RecordEntry::Input(InputEvent::Trigger(
+                Trigger::Key(scancode::F4),
+                ElementState::Pressed,
+            )),
+            RecordEntry::Tick,
+            RecordEntry::Config(InputConfig {
+                key_bindings: InputMap::default(),
+                ..config.clone()
+            }),
+            RecordEntry::Frame(Duration::from_millis(16)),
+        ];
+
+        let path = env::temp_dir().join(format!("qbd-recording-{}.bin", process::id()));
+        let mut recorder = Recorder::create(&path, &config).unwrap();
+        for entry in entries.iter() {
+            recorder.record(entry.clone()).unwrap();
+        }
+        recorder.finish().unwrap();
+        let replay = Replay::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(replay.config(), &config);
+        let replayed: Vec<RecordEntry> = replay.map(|record| record.entry).collect();
+        assert_eq!(replayed, entries);
+    }
+}
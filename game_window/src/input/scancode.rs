@@ -0,0 +1,215 @@
+// Scancodes used by the default bindings. Windows and Linux report PC set 1 codes, macOS reports
+// its own virtual key codes.
+
+#[cfg(not(target_os = "macos"))]
+mod codes {
+    pub const ESCAPE: u32 = 0x01;
+    pub const W: u32 = 0x11;
+    pub const A: u32 = 0x1e;
+    pub const S: u32 = 0x1f;
+    pub const D: u32 = 0x20;
+    pub const LCONTROL: u32 = 0x1d;
+    pub const LSHIFT: u32 = 0x2a;
+    pub const SPACE: u32 = 0x39;
+    pub const F2: u32 = 0x3c;
+    pub const F3: u32 = 0x3d;
+    pub const F4: u32 = 0x3e;
+
+    pub(super) fn name(code: u32) -> Option<&'static str> {
+        let name = match code {
+            0x01 => "Escape",
+            0x02 => "1",
+            0x03 => "2",
+            0x04 => "3",
+            0x05 => "4",
+            0x06 => "5",
+            0x07 => "6",
+            0x08 => "7",
+            0x09 => "8",
+            0x0a => "9",
+            0x0b => "0",
+            0x0c => "-",
+            0x0d => "=",
+            0x0e => "Backspace",
+            0x0f => "Tab",
+            0x10 => "Q",
+            0x11 => "W",
+            0x12 => "E",
+            0x13 => "R",
+            0x14 => "T",
+            0x15 => "Y",
+            0x16 => "U",
+            0x17 => "I",
+            0x18 => "O",
+            0x19 => "P",
+            0x1a => "[",
+            0x1b => "]",
+            0x1c => "Enter",
+            0x1d => "Left Ctrl",
+            0x1e => "A",
+            0x1f => "S",
+            0x20 => "D",
+            0x21 => "F",
+            0x22 => "G",
+            0x23 => "H",
+            0x24 => "J",
+            0x25 => "K",
+            0x26 => "L",
+            0x27 => ";",
+            0x28 => "'",
+            0x29 => "`",
+            0x2a => "Left Shift",
+            0x2b => "\\",
+            0x2c => "Z",
+            0x2d => "X",
+            0x2e => "C",
+            0x2f => "V",
+            0x30 => "B",
+            0x31 => "N",
+            0x32 => "M",
+            0x33 => ",",
+            0x34 => ".",
+            0x35 => "/",
+            0x36 => "Right Shift",
+            0x38 => "Left Alt",
+            0x39 => "Space",
+            0x3a => "Caps Lock",
+            0x3b => "F1",
+            0x3c => "F2",
+            0x3d => "F3",
+            0x3e => "F4",
+            0x3f => "F5",
+            0x40 => "F6",
+            0x41 => "F7",
+            0x42 => "F8",
+            0x43 => "F9",
+            0x44 => "F10",
+            0x57 => "F11",
+            0x58 => "F12",
+            0xe01d => "Right Ctrl",
+            0xe038 => "Right Alt",
+            0xe047 => "Home",
+            0xe048 => "Up",
+            0xe049 => "Page Up",
+            0xe04b => "Left",
+            0xe04d => "Right",
+            0xe04f => "End",
+            0xe050 => "Down",
+            0xe051 => "Page Down",
+            0xe052 => "Insert",
+            0xe053 => "Delete",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod codes {
+    pub const ESCAPE: u32 = 0x35;
+    pub const W: u32 = 0x0d;
+    pub const A: u32 = 0x00;
+    pub const S: u32 = 0x01;
+    pub const D: u32 = 0x02;
+    pub const LCONTROL: u32 = 0x3b;
+    pub const LSHIFT: u32 = 0x38;
+    pub const SPACE: u32 = 0x31;
+    pub const F2: u32 = 0x78;
+    pub const F3: u32 = 0x63;
+    pub const F4: u32 = 0x76;
+
+    pub(super) fn name(code: u32) -> Option<&'static str> {
+        let name = match code {
+            0x00 => "A",
+            0x01 => "S",
+            0x02 => "D",
+            0x03 => "F",
+            0x04 => "H",
+            0x05 => "G",
+            0x06 => "Z",
+            0x07 => "X",
+            0x08 => "C",
+            0x09 => "V",
+            0x0b => "B",
+            0x0c => "Q",
+            0x0d => "W",
+            0x0e => "E",
+            0x0f => "R",
+            0x10 => "Y",
+            0x11 => "T",
+            0x12 => "1",
+            0x13 => "2",
+            0x14 => "3",
+            0x15 => "4",
+            0x16 => "6",
+            0x17 => "5",
+            0x18 => "=",
+            0x19 => "9",
+            0x1a => "7",
+            0x1b => "-",
+            0x1c => "8",
+            0x1d => "0",
+            0x1e => "]",
+            0x1f => "O",
+            0x20 => "U",
+            0x21 => "[",
+            0x22 => "I",
+            0x23 => "P",
+            0x24 => "Return",
+            0x25 => "L",
+            0x26 => "J",
+            0x27 => "'",
+            0x28 => "K",
+            0x29 => ";",
+            0x2a => "\\",
+            0x2b => ",",
+            0x2c => "/",
+            0x2d => "N",
+            0x2e => "M",
+            0x2f => ".",
+            0x30 => "Tab",
+            0x31 => "Space",
+            0x32 => "`",
+            0x33 => "Delete",
+            0x35 => "Escape",
+            0x37 => "Command",
+            0x38 => "Left Shift",
+            0x39 => "Caps Lock",
+            0x3a => "Left Option",
+            0x3b => "Left Control",
+            0x3c => "Right Shift",
+            0x3d => "Right Option",
+            0x3e => "Right Control",
+            0x60 => "F5",
+            0x61 => "F6",
+            0x62 => "F7",
+            0x63 => "F3",
+            0x64 => "F8",
+            0x65 => "F9",
+            0x67 => "F11",
+            0x6d => "F10",
+            0x6f => "F12",
+            0x73 => "Home",
+            0x74 => "Page Up",
+            0x75 => "Forward Delete",
+            0x76 => "F4",
+            0x77 => "End",
+            0x78 => "F2",
+            0x79 => "Page Down",
+            0x7a => "F1",
+            0x7b => "Left",
+            0x7c => "Right",
+            0x7d => "Down",
+            0x7e => "Up",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+pub use codes::{A, D, ESCAPE, F2, F3, F4, LCONTROL, LSHIFT, S, SPACE, W};
+
+/// The label printed on the key at `code` on a US layout, if it's one we know.
+pub fn name(code: u32) -> Option<&'static str> {
+    codes::name(code)
+}
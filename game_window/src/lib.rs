@@ -1,6 +1,12 @@
+pub mod camera;
+pub mod cli;
+pub mod crash;
 pub mod global_state;
+pub mod input;
 pub mod renderer;
 pub mod run;
 pub mod settings;
+pub mod state;
 pub mod types;
+pub mod ui;
 pub mod window;
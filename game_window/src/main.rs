@@ -1,13 +1,133 @@
-use game_window::{global_state::GlobalState, run::run, settings::Settings, window::GameWindow};
+use common::{
+    build_info,
+    logging::{self, LogGuard},
+    math::Vec3,
+};
+use game_window::{
+    camera::{Camera, CameraController},
+    cli::Options,
+    crash,
+    global_state::GlobalState,
+    input::{
+        gamepad::GilrsSource,
+        recording::{Recorder, Replay},
+        InputState,
+    },
+    renderer::{capture::FrameCapture, font::Font, shader_library::ShaderSource},
+    run::{run, run_headless, RunOptions},
+    settings::{GraphicsBackend, Settings},
+    window::GameWindow,
+};
+use std::process;
+use tracing::{error, info, warn};
 
 fn main() {
+    let options = Options::from_args().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(2);
+    });
+
+    let log_guard = logging::init("game_window");
+    crash::install();
+    info!("Starting {}", build_info::summary());
+
+    let replay = match &options.replay {
+        Some(path) => match Replay::open(path) {
+            Ok(replay) => Some(replay),
+            Err(error) => {
+                error!("Could not open replay {}: {}", path.display(), error);
+                fail(log_guard);
+            }
+        },
+        None => None,
+    };
+
     let settings = Settings::load();
-    let (game_window, event_loop) = GameWindow::new(&settings);
+    crash::set_settings(&settings);
+    if settings.graphics().backend() != GraphicsBackend::compiled() {
+        warn!(
+            "{} was chosen in the settings, but this build renders with {}",
+            settings.graphics().backend().name(),
+            GraphicsBackend::compiled().name()
+        );
+    }
+    // A headless replay never opens a window, so it runs without a display server.
+    let (game_window, event_loop) = if options.headless {
+        (GameWindow::headless(&settings), None)
+    } else {
+        let (game_window, event_loop) = GameWindow::new(&settings);
+        (game_window, Some(event_loop))
+    };
+    let mut input = InputState::new(&settings);
+
+    // A replay brings its own input and bindings, live devices would make it diverge.
+    match &replay {
+        Some(replay) => input.set_config(replay.config().clone()),
+        None => {
+            if let Some(gamepads) = GilrsSource::new() {
+                input.set_gamepad_source(Box::new(gamepads));
+            }
+        }
+    }
 
-    let game_state: GlobalState = GlobalState {
+    if let Some(path) = &options.record {
+        match Recorder::create(path, &input.config()) {
+            Ok(recorder) => {
+                info!("Recording input to {}", path.display());
+                input.start_recording(recorder);
+            }
+            Err(error) => {
+                error!("Could not record to {}: {}", path.display(), error);
+                fail(log_guard);
+            }
+        }
+    }
+
+    let capture = match options.capture.clone() {
+        Some(output) => match FrameCapture::start(output, options.capture_fps) {
+            Ok(capture) => Some(capture),
+            Err(error) => {
+                error!("Could not start capturing: {}", error);
+                fail(log_guard);
+            }
+        },
+        None => None,
+    };
+
+    let font = Font::embedded();
+
+    let game_state: GlobalState = GlobalState::new(
         settings,
-        window: game_window,
+        game_window,
+        input,
+        CameraController::new(Camera::new(Vec3::new(0.0, 64.0, 0.0))),
+        font,
+    );
+
+    let event_loop = match event_loop {
+        Some(event_loop) => event_loop,
+        None => {
+            let replay = replay.expect("--headless is only allowed with a replay");
+            return run_headless(game_state, replay, log_guard);
+        }
     };
+    run(
+        game_state,
+        event_loop,
+        RunOptions {
+            replay,
+            capture,
+            shaders: options
+                .shaders
+                .map_or(ShaderSource::Embedded, ShaderSource::Directory),
+            log_guard,
+        },
+    );
+}
 
-    run(game_state, event_loop);
+/// Exits with a failure once everything logged so far has reached the log file, which
+/// `process::exit` alone would skip.
+fn fail(log_guard: LogGuard) -> ! {
+    drop(log_guard);
+    process::exit(1);
 }
@@ -0,0 +1,210 @@
+use super::{
+    back,
+    gpu_objects::{GpuObject, GpuObjectTracker},
+};
+use gfx_hal::{
+    adapter::MemoryType,
+    buffer::Usage,
+    device::Device,
+    memory::{Properties, Segment},
+    MemoryTypeId,
+};
+use std::ptr;
+
+/// Dynamic buffers never shrink below this many bytes, so small frames don't keep reallocating.
+const MIN_DYNAMIC_SIZE: u64 = 64 * 1024;
+
+/// The first memory type allowed by `type_mask` that has all of `properties`.
+pub fn find_memory_type(
+    memory_types: &[MemoryType],
+    type_mask: u32,
+    properties: Properties,
+) -> Option<MemoryTypeId> {
+    memory_types
+        .iter()
+        .enumerate()
+        .position(|(id, memory_type)| {
+            type_mask & (1 << id) != 0 && memory_type.properties.contains(properties)
+        })
+        .map(MemoryTypeId)
+}
+
+/// A buffer in memory the CPU can read and write directly. Coherent, so writes don't need
+/// flushing.
+pub struct HostBuffer {
+    buffer: <back::Backend as gfx_hal::Backend>::Buffer,
+    memory: <back::Backend as gfx_hal::Backend>::Memory,
+    memory_size: u64,
+    size: u64,
+}
+
+impl HostBuffer {
+    pub fn new(
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        memory_types: &[MemoryType],
+        gpu_objects: &mut GpuObjectTracker,
+        size: u64,
+        usage: Usage,
+    ) -> HostBuffer {
+        unsafe {
+            let mut buffer = device
+                .create_buffer(size, usage)
+                .expect("Failed to create buffer");
+            let requirements = device.get_buffer_requirements(&buffer);
+            let memory_type = find_memory_type(
+                memory_types,
+                requirements.type_mask,
+                Properties::CPU_VISIBLE | Properties::COHERENT,
+            )
+            .expect("No host visible memory");
+            let memory = device
+                .allocate_memory(memory_type, requirements.size)
+                .expect("Out of memory");
+            device
+                .bind_buffer_memory(&memory, 0, &mut buffer)
+                .expect("Failed to bind buffer memory");
+
+            gpu_objects.created(GpuObject::Buffer);
+            gpu_objects.allocated(requirements.size);
+
+            Self {
+                buffer,
+                memory,
+                memory_size: requirements.size,
+                size,
+            }
+        }
+    }
+
+    pub fn buffer(&self) -> &<back::Backend as gfx_hal::Backend>::Buffer {
+        &self.buffer
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Copies the whole buffer out. The GPU must be done writing it.
+    pub fn read(&self, device: &<back::Backend as gfx_hal::Backend>::Device) -> Vec<u8> {
+        let mut bytes = vec![0; self.size as usize];
+        unsafe {
+            let mapped = device
+                .map_memory(&self.memory, Segment::ALL)
+                .expect("Failed to map buffer memory");
+            ptr::copy_nonoverlapping(mapped, bytes.as_mut_ptr(), bytes.len());
+            device.unmap_memory(&self.memory);
+        }
+        bytes
+    }
+
+    /// Copies `bytes` to the start of the buffer. The GPU must not be reading it.
+    pub fn write(&self, device: &<back::Backend as gfx_hal::Backend>::Device, bytes: &[u8]) {
+        assert!(
+            bytes.len() as u64 <= self.size,
+            "{} bytes don't fit in a {} byte buffer",
+            bytes.len(),
+            self.size
+        );
+
+        unsafe {
+            let mapped = device
+                .map_memory(&self.memory, Segment::ALL)
+                .expect("Failed to map buffer memory");
+            ptr::copy_nonoverlapping(bytes.as_ptr(), mapped, bytes.len());
+            device.unmap_memory(&self.memory);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The GPU must be done with the buffer.
+    pub unsafe fn destroy(
+        self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) {
+        device.destroy_buffer(self.buffer);
+        device.free_memory(self.memory);
+        gpu_objects.destroyed(GpuObject::Buffer);
+        gpu_objects.freed(self.memory_size);
+    }
+}
+
+/// A host buffer rewritten every frame, reallocated at twice the size whenever a frame doesn't
+/// fit.
+pub struct DynamicBuffer {
+    usage: Usage,
+    allocation: Option<HostBuffer>,
+    len: u64,
+}
+
+impl DynamicBuffer {
+    pub fn new(usage: Usage) -> DynamicBuffer {
+        Self {
+            usage,
+            allocation: None,
+            len: 0,
+        }
+    }
+
+    /// The buffer, unless nothing has been written yet.
+    pub fn buffer(&self) -> Option<&<back::Backend as gfx_hal::Backend>::Buffer> {
+        self.allocation.as_ref().map(HostBuffer::buffer)
+    }
+
+    /// Bytes written by the last `write`.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Replaces the contents with `bytes`. The GPU must be done with the previous frame.
+    pub fn write(
+        &mut self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        memory_types: &[MemoryType],
+        gpu_objects: &mut GpuObjectTracker,
+        bytes: &[u8],
+    ) {
+        self.len = bytes.len() as u64;
+        if bytes.is_empty() {
+            return;
+        }
+
+        let too_small = match &self.allocation {
+            Some(allocation) => allocation.size() < self.len,
+            None => true,
+        };
+        if too_small {
+            if let Some(allocation) = self.allocation.take() {
+                unsafe { allocation.destroy(device, gpu_objects) };
+            }
+            let size = self.len.next_power_of_two().max(MIN_DYNAMIC_SIZE);
+            self.allocation = Some(HostBuffer::new(
+                device,
+                memory_types,
+                gpu_objects,
+                size,
+                self.usage,
+            ));
+        }
+
+        self.allocation.as_ref().unwrap().write(device, bytes);
+    }
+
+    /// # Safety
+    ///
+    /// The GPU must be done with the buffer.
+    pub unsafe fn destroy(
+        self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) {
+        if let Some(allocation) = self.allocation {
+            allocation.destroy(device, gpu_objects);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use super::screenshot::{self, CapturedFrame, ScreenshotError};
+use gfx_hal::window::Extent2D;
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+/// Frames read back but not written yet. Rendering waits once this many are queued, which is
+/// fine since a capture runs on simulated time.
+const QUEUED_FRAMES: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureOutput {
+    /// Numbered PNGs in a directory, `frame-000000.png` onwards.
+    Frames(PathBuf),
+    /// Raw sRGB RGBA8 frames written to the standard input of a command, like an encoder.
+    Pipe { program: String, args: Vec<String> },
+}
+
+/// Writes every rendered frame to its output on a background thread.
+pub struct FrameCapture {
+    frame_rate: u32,
+    sender: Option<SyncSender<CapturedFrame>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl FrameCapture {
+    pub fn start(output: CaptureOutput, frame_rate: u32) -> io::Result<FrameCapture> {
+        let sink = match output {
+            CaptureOutput::Frames(directory) => {
+                fs::create_dir_all(&directory)?;
+                Sink::Frames { directory, next: 0 }
+            }
+            CaptureOutput::Pipe { program, args } => {
+                let mut process = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .spawn()?;
+                let stdin = process.stdin.take().expect("Standard input is piped");
+                Sink::Pipe {
+                    process,
+                    stdin,
+                    extent: None,
+                }
+            }
+        };
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUED_FRAMES);
+        let writer = thread::Builder::new()
+            .name("capture".to_owned())
+            .spawn(move || write_frames(sink, receiver, frame_rate))?;
+
+        Ok(Self {
+            frame_rate,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn frame_rate(&self) -> u32 {
+        self.frame_rate
+    }
+
+    /// Simulated time between two captured frames.
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs(1) / self.frame_rate
+    }
+
+    pub fn push(&mut self, frame: CapturedFrame) {
+        let stopped = match &self.sender {
+            Some(sender) => sender.send(frame).is_err(),
+            None => false,
+        };
+        // The writer already logged why it stopped.
+        if stopped {
+            self.sender = None;
+        }
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        // Hanging up lets the writer drain the queue and finish.
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                error!("Capture writer panicked");
+            }
+        }
+    }
+}
+
+enum Sink {
+    Frames {
+        directory: PathBuf,
+        next: u64,
+    },
+    Pipe {
+        process: Child,
+        stdin: ChildStdin,
+        /// Size of the first frame, a raw stream can't change it.
+        extent: Option<Extent2D>,
+    },
+}
+
+impl Sink {
+    fn write(&mut self, frame: &CapturedFrame) -> Result<(), ScreenshotError> {
+        let rgba = frame.to_srgb_rgba8()?;
+        match self {
+            Sink::Frames { directory, next } => {
+                let path = directory.join(format!("frame-{:06}.png", next));
+                screenshot::write_png(&path, frame.extent, &rgba)?;
+                *next += 1;
+            }
+            Sink::Pipe { stdin, extent, .. } => {
+                let extent = *extent.get_or_insert(frame.extent);
+                if frame.extent != extent {
+                    warn!(
+                        "Skipped a {}x{} frame, the stream is {}x{}",
+                        frame.extent.width, frame.extent.height, extent.width, extent.height
+                    );
+                    return Ok(());
+                }
+                stdin.write_all(&rgba)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) {
+        if let Sink::Pipe {
+            mut process, stdin, ..
+        } = self
+        {
+            // Closing standard input tells the encoder the stream ended.
+            drop(stdin);
+            match process.wait() {
+                Ok(status) if !status.success() => error!("Capture command exited with {}", status),
+                Ok(_) => (),
+                Err(error) => error!("Could not wait for the capture command: {}", error),
+            }
+        }
+    }
+}
+
+fn write_frames(mut sink: Sink, receiver: Receiver<CapturedFrame>, frame_rate: u32) {
+    let mut frames: u64 = 0;
+    for frame in receiver {
+        if frames == 0 {
+            info!(
+                "Capturing {}x{} frames at {} FPS",
+                frame.extent.width, frame.extent.height, frame_rate
+            );
+        }
+        if let Err(error) = sink.write(&frame) {
+            error!("Capture stopped after {} frames: {}", frames, error);
+            break;
+        }
+        frames += 1;
+    }
+
+    sink.finish();
+    info!("Captured {} frames", frames);
+}
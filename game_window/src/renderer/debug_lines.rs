@@ -0,0 +1,48 @@
+use super::{gpu_data::GpuPod, pipeline::PipelineDesc};
+use bytemuck::{Pod, Zeroable};
+use common::{
+    debug_draw::DebugLine,
+    math::{Mat4, Vec3, Vec4},
+};
+use gfx_hal::{
+    format::Format,
+    pso::{BlendState, Face, Primitive, ShaderStageFlags, VertexInputRate},
+};
+use std::mem::size_of;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Pod, Zeroable)]
+pub struct DebugVertex {
+    pub position: Vec3,
+    pub color: Vec4,
+}
+
+impl GpuPod for DebugVertex {}
+
+/// Debug lines are drawn over everything else, so they don't test or write depth.
+pub fn pipeline() -> PipelineDesc {
+    PipelineDesc::new("debug-line.vert", "debug-line.frag")
+        .topology(Primitive::LineList)
+        .cull(Face::NONE)
+        .blend(BlendState::ALPHA)
+        .vertex_buffer(size_of::<DebugVertex>() as u32, VertexInputRate::Vertex)
+        .attribute(0, Format::Rgb32Sfloat, 0)
+        .attribute(1, Format::Rgba32Sfloat, size_of::<Vec3>() as u32)
+        .push_constants::<Mat4>(ShaderStageFlags::VERTEX)
+}
+
+/// Two vertices per line, for a line list.
+pub fn vertices(lines: &[DebugLine]) -> Vec<DebugVertex> {
+    let mut vertices = Vec::with_capacity(lines.len() * 2);
+    for line in lines {
+        vertices.push(DebugVertex {
+            position: line.start,
+            color: line.color,
+        });
+        vertices.push(DebugVertex {
+            position: line.end,
+            color: line.color,
+        });
+    }
+    vertices
+}
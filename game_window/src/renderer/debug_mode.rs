@@ -0,0 +1,72 @@
+use super::pipeline::{PipelineDesc, PolygonMode};
+use super::push_constants::PushConstants;
+use gfx_hal::pso::{BlendState, Face, ShaderStageFlags};
+
+/// Alternate ways of drawing the same draw list, for looking at geometry instead of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DebugMode {
+    #[default]
+    None,
+    Wireframe,
+    /// Colours every fragment by its face normal.
+    Normals,
+    /// Adds up every fragment drawn to a pixel, brighter means drawn more often.
+    Overdraw,
+    /// Draws the scene normally. The outlines of the chunks around the camera are debug shapes
+    /// queued by the in-game state, so outside of a game this looks the same as `None`.
+    ChunkBoundaries,
+}
+
+impl DebugMode {
+    pub fn next(self) -> DebugMode {
+        match self {
+            DebugMode::None => DebugMode::Wireframe,
+            DebugMode::Wireframe => DebugMode::Normals,
+            DebugMode::Normals => DebugMode::Overdraw,
+            DebugMode::Overdraw => DebugMode::ChunkBoundaries,
+            DebugMode::ChunkBoundaries => DebugMode::None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugMode::None => "none",
+            DebugMode::Wireframe => "wireframe",
+            DebugMode::Normals => "normals",
+            DebugMode::Overdraw => "overdraw",
+            DebugMode::ChunkBoundaries => "chunk boundaries",
+        }
+    }
+
+    /// The pipeline for drawing the draw list's triangles in this mode. Without line polygon
+    /// support wireframes are drawn by discarding everything away from the triangle edges.
+    pub fn triangle_pipeline(self, line_polygons: bool) -> PipelineDesc {
+        let desc = PipelineDesc::new("part-1.vert", "part-1.frag")
+            .push_constants::<PushConstants>(ShaderStageFlags::VERTEX);
+
+        match self {
+            DebugMode::None | DebugMode::ChunkBoundaries => desc.blend(BlendState::ALPHA),
+            DebugMode::Wireframe if line_polygons => desc
+                .polygon_mode(PolygonMode::Line)
+                .cull(Face::NONE)
+                .define("WIREFRAME", None),
+            DebugMode::Wireframe => desc
+                .cull(Face::NONE)
+                .define("WIREFRAME", None)
+                .define("BARYCENTRIC", None),
+            DebugMode::Normals => desc.define("NORMALS", None),
+            DebugMode::Overdraw => desc
+                .cull(Face::NONE)
+                .blend(BlendState::ADD)
+                .define("OVERDRAW", None),
+        }
+    }
+
+    /// Overdraw adds up from black, everything else keeps the scene's clear colour.
+    pub fn clear_color(self, scene: [f32; 4]) -> [f32; 4] {
+        match self {
+            DebugMode::Overdraw => [0.0, 0.0, 0.0, 1.0],
+            _ => scene,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use super::{
+    draw_list::DrawList,
+    font::Font,
+    text::{TextLayout, TextStyle},
+};
+use common::{
+    math::{Vec2, Vec4},
+    metrics::{self, MetricValue, HISTORY_LEN},
+};
+
+const MARGIN: f32 = 8.0;
+const PADDING: f32 = 6.0;
+const TEXT_SIZE: f32 = 15.0;
+const GRAPH_HEIGHT: f32 = 48.0;
+/// Frame times averaged for the FPS counter.
+const FPS_SAMPLES: usize = 30;
+const TARGET_FRAME_MS: f32 = 1000.0 / 60.0;
+
+const BACKGROUND: Vec4 = Vec4::new(0.0, 0.0, 0.0, 0.6);
+const TEXT: Vec4 = Vec4::ONE;
+const GOOD: Vec4 = Vec4::new(0.3, 0.9, 0.3, 1.0);
+const SLOW: Vec4 = Vec4::new(0.9, 0.8, 0.2, 1.0);
+const BAD: Vec4 = Vec4::new(0.9, 0.3, 0.3, 1.0);
+
+/// Draws the engine metrics in the top left corner: a frame time graph followed by whatever each
+/// subsystem last published. Metrics nobody published show as a dash.
+pub fn draw(font: &Font, draw_list: &mut DrawList) {
+    let frame_times = metrics::history(metrics::FRAME_TIME);
+    let recent = &frame_times[frame_times.len().saturating_sub(FPS_SAMPLES)..];
+    let average = if recent.is_empty() {
+        0.0
+    } else {
+        recent.iter().sum::<f32>() / recent.len() as f32
+    };
+    let worst = recent.iter().copied().fold(0.0, f32::max);
+    let fps = if average > 0.0 { 1000.0 / average } else { 0.0 };
+
+    let header = format!("{:.0} FPS ({:.1} ms, worst {:.1} ms)", fps, average, worst);
+    let lines = [
+        format!("Position {}", text(metrics::POSITION)),
+        format!("Chunk {}", text(metrics::CHUNK)),
+        format!("Facing {}", text(metrics::FACING)),
+        format!("Draw calls {}", text(metrics::DRAW_CALLS)),
+        format!(
+            "GPU memory {}",
+            metrics::number(metrics::GPU_MEMORY)
+                .map(|bytes| format!("{:.1} MiB", bytes / (1024.0 * 1024.0)))
+                .unwrap_or_else(|| "-".to_owned())
+        ),
+        format!(
+            "Adapter {} ({})",
+            text(metrics::ADAPTER),
+            text(metrics::BACKEND)
+        ),
+    ];
+
+    let style = TextStyle::new(TEXT_SIZE, TEXT);
+    let header = TextLayout::new(font, &header, &style);
+    let body = TextLayout::new(font, &lines.join("\n"), &style);
+    let graph_width = HISTORY_LEN as f32;
+    let width = header.size.x.max(body.size.x).max(graph_width);
+    let height = header.size.y + GRAPH_HEIGHT + body.size.y + PADDING * 2.0;
+
+    let origin = Vec2::splat(MARGIN);
+    draw_list.rect(
+        origin,
+        Vec2::new(width, height) + Vec2::splat(PADDING * 2.0),
+        BACKGROUND,
+    );
+
+    let mut cursor = origin + Vec2::splat(PADDING);
+    draw_list.text_layout(&header, cursor, TEXT);
+    cursor.y += header.size.y + PADDING;
+    frame_graph(&frame_times, cursor, draw_list);
+    cursor.y += GRAPH_HEIGHT + PADDING;
+    draw_list.text_layout(&body, cursor, TEXT);
+}
+
+/// One bar per frame, newest on the right. The graph always fits at least two 60 FPS frames so a
+/// steady frame rate doesn't fill it, with a line at the 60 FPS budget.
+fn frame_graph(frame_times: &[f32], position: Vec2, draw_list: &mut DrawList) {
+    let max = frame_times
+        .iter()
+        .copied()
+        .fold(TARGET_FRAME_MS * 2.0, f32::max);
+    let scale = GRAPH_HEIGHT / max;
+    let start = position.x + (HISTORY_LEN - frame_times.len()) as f32;
+
+    for (index, &frame_time) in frame_times.iter().enumerate() {
+        let height = frame_time * scale;
+        let color = if frame_time <= TARGET_FRAME_MS {
+            GOOD
+        } else if frame_time <= TARGET_FRAME_MS * 2.0 {
+            SLOW
+        } else {
+            BAD
+        };
+        draw_list.rect(
+            Vec2::new(start + index as f32, position.y + GRAPH_HEIGHT - height),
+            Vec2::new(1.0, height),
+            color,
+        );
+    }
+
+    let budget = position.y + GRAPH_HEIGHT - TARGET_FRAME_MS * scale;
+    draw_list.rect(
+        Vec2::new(position.x, budget),
+        Vec2::new(HISTORY_LEN as f32, 1.0),
+        TEXT * 0.5,
+    );
+}
+
+/// A metric as text, whether it was published as a number or not.
+fn text(name: &str) -> String {
+    match metrics::value(name) {
+        Some(MetricValue::Text(text)) => text,
+        Some(MetricValue::Number(number)) => format!("{}", number),
+        None => "-".to_owned(),
+    }
+}
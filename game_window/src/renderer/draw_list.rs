@@ -0,0 +1,156 @@
+use super::{
+    debug_mode::DebugMode,
+    font::Font,
+    push_constants::PushConstants,
+    text::{self, Align, TextLayout, TextStyle, TextVertex},
+};
+use common::{
+    debug_draw::{DebugFrame, DebugLine},
+    math::{Mat4, Vec2, Vec4},
+};
+
+/// Font size of debug labels, in logical pixels.
+const LABEL_SIZE: f32 = 14.0;
+
+/// What a frame should contain, filled in by the active states before the renderer records it.
+pub struct DrawList {
+    clear_color: [f32; 4],
+    debug_mode: DebugMode,
+    triangles: Vec<PushConstants>,
+    view_projection: Mat4,
+    debug_lines: Vec<DebugLine>,
+    screen_size: Vec2,
+    scale_factor: f32,
+    overlay: Vec<TextVertex>,
+}
+
+impl DrawList {
+    pub fn new() -> DrawList {
+        Self {
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            debug_mode: DebugMode::None,
+            triangles: Vec::new(),
+            view_projection: Mat4::IDENTITY,
+            debug_lines: Vec::new(),
+            screen_size: Vec2::ONE,
+            scale_factor: 1.0,
+            overlay: Vec::new(),
+        }
+    }
+
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.clear_color = color;
+    }
+
+    pub fn debug_mode(&self) -> DebugMode {
+        self.debug_mode
+    }
+
+    pub fn set_debug_mode(&mut self, mode: DebugMode) {
+        self.debug_mode = mode;
+    }
+
+    pub fn triangles(&self) -> &[PushConstants] {
+        &self.triangles
+    }
+
+    pub fn triangle(&mut self, color: Vec4, pos: Vec2, scale: Vec2) {
+        self.triangles.push(PushConstants { color, pos, scale });
+    }
+
+    /// Takes world space to clip space, for everything drawn in 3D.
+    pub fn view_projection(&self) -> Mat4 {
+        self.view_projection
+    }
+
+    pub fn set_view_projection(&mut self, view_projection: Mat4) {
+        self.view_projection = view_projection;
+    }
+
+    pub fn debug_lines(&self) -> &[DebugLine] {
+        &self.debug_lines
+    }
+
+    /// Debug labels become overlay text centered on where they are on screen, so the view
+    /// projection and screen have to be set first.
+    pub fn add_debug_frame(&mut self, frame: DebugFrame, font: &Font) {
+        self.debug_lines.extend(frame.lines);
+
+        for label in frame.labels {
+            let clip = self.view_projection * label.position.extend(1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+
+            let screen = self.screen_size();
+            let center = Vec2::new(
+                (clip.x / clip.w * 0.5 + 0.5) * screen.x,
+                (0.5 - clip.y / clip.w * 0.5) * screen.y,
+            );
+            let layout = TextLayout::new(
+                font,
+                &label.text,
+                &TextStyle::new(LABEL_SIZE, label.color).align(Align::Center),
+            );
+            self.text_layout(&layout, center - layout.size * 0.5, label.color);
+        }
+    }
+
+    /// Size of the screen in logical pixels.
+    pub fn screen_size(&self) -> Vec2 {
+        self.screen_size
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// `size` is in logical pixels, `scale_factor` the physical pixels per logical one.
+    pub fn set_screen(&mut self, size: Vec2, scale_factor: f32) {
+        self.screen_size = size;
+        self.scale_factor = scale_factor;
+    }
+
+    /// Overlay text with its top left corner at `position`, in logical pixels.
+    pub fn text(&mut self, font: &Font, text: &str, position: Vec2, style: &TextStyle) {
+        let layout = TextLayout::new(font, text, style);
+        self.text_layout(&layout, position, style.color);
+    }
+
+    /// Overlay text laid out beforehand, for when its size was needed to place it.
+    pub fn text_layout(&mut self, layout: &TextLayout, position: Vec2, color: Vec4) {
+        text::vertices(
+            layout,
+            position,
+            self.scale_factor,
+            color,
+            &mut self.overlay,
+        );
+    }
+
+    /// A solid overlay rectangle with its top left corner at `position`, in logical pixels.
+    /// Overlay text and rectangles are drawn in the order they were added.
+    pub fn rect(&mut self, position: Vec2, size: Vec2, color: Vec4) {
+        text::rect_vertices(
+            position,
+            position + size,
+            self.scale_factor,
+            color,
+            &mut self.overlay,
+        );
+    }
+
+    pub fn overlay_vertices(&self) -> &[TextVertex] {
+        &self.overlay
+    }
+}
+
+impl Default for DrawList {
+    fn default() -> DrawList {
+        DrawList::new()
+    }
+}
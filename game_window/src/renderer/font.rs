@@ -0,0 +1,369 @@
+use common::math::Vec2;
+use rusttype::{point, Scale};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// Glyphs are rendered into the atlas at this size in pixels, layout scales from it.
+pub const BASE_SIZE: f32 = 32.0;
+/// How far from a glyph's outline, in base size pixels, the distance field reaches. Also the
+/// padding around every glyph in the atlas.
+const SPREAD: i32 = 4;
+/// Outlines are rasterized this many times bigger than the base size before computing distances.
+const SUPERSAMPLE: i32 = 4;
+const ATLAS_WIDTH: u32 = 512;
+/// Drawn for characters the font or the atlas doesn't have.
+const REPLACEMENT: char = '?';
+/// Practically infinite squared distance for the distance transform.
+const FAR: f32 = 1e20;
+/// The font the game ships with, built in so it doesn't depend on where it's run from.
+const EMBEDDED: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+#[derive(Debug)]
+pub enum FontError {
+    Io { path: PathBuf, error: io::Error },
+    Invalid(PathBuf),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Io { path, error } => {
+                write!(f, "could not read {}: {}", path.display(), error)
+            }
+            FontError::Invalid(path) => write!(f, "{} is not a TrueType font", path.display()),
+        }
+    }
+}
+
+impl Error for FontError {}
+
+/// Where a character's distance field is in the atlas and how to place it. Sizes are in base
+/// size pixels, with Y pointing down from the baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub advance: f32,
+}
+
+/// A TrueType font with signed distance fields of Latin-1 rendered into a single channel atlas.
+/// Distance fields stay sharp at any scale, so one atlas serves every text size.
+pub struct Font {
+    font: rusttype::Font<'static>,
+    glyphs: HashMap<char, Glyph>,
+    atlas_size: (u32, u32),
+    atlas: Vec<u8>,
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+}
+
+impl Font {
+    pub fn load(path: &Path) -> Result<Font, FontError> {
+        let bytes = fs::read(path).map_err(|error| FontError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        let font = rusttype::Font::try_from_vec(bytes)
+            .ok_or_else(|| FontError::Invalid(path.to_owned()))?;
+
+        debug!("Building glyph atlas for {}", path.display());
+        Ok(Font::new(font))
+    }
+
+    /// The font built into the game.
+    pub fn embedded() -> Font {
+        let font = rusttype::Font::try_from_bytes(EMBEDDED).expect("Invalid embedded font");
+
+        debug!("Building glyph atlas for the embedded font");
+        Font::new(font)
+    }
+
+    fn new(font: rusttype::Font<'static>) -> Font {
+        let scale = Scale::uniform(BASE_SIZE);
+        let v_metrics = font.v_metrics(scale);
+
+        let characters = (' '..='~').chain('\u{a0}'..='\u{ff}');
+        let fields: Vec<(char, GlyphField)> = characters
+            .filter(|&character| character == ' ' || font.glyph(character).id().0 != 0)
+            .map(|character| (character, GlyphField::render(&font, character)))
+            .collect();
+
+        // Shelf packing, glyphs are placed left to right in rows as tall as their tallest glyph.
+        let mut positions = Vec::with_capacity(fields.len());
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+        for (_, field) in &fields {
+            if x + field.width > ATLAS_WIDTH {
+                x = 0;
+                y += row_height + 1;
+                row_height = 0;
+            }
+            positions.push((x, y));
+            x += field.width + 1;
+            row_height = row_height.max(field.height);
+        }
+        let atlas_size = (ATLAS_WIDTH, (y + row_height).next_power_of_two());
+
+        let mut atlas = vec![0; (atlas_size.0 * atlas_size.1) as usize];
+        let mut glyphs = HashMap::with_capacity(fields.len());
+        for ((character, field), (x, y)) in fields.into_iter().zip(positions) {
+            for row in 0..field.height {
+                let start = ((y + row) * atlas_size.0 + x) as usize;
+                let source = (row * field.width) as usize;
+                atlas[start..start + field.width as usize]
+                    .copy_from_slice(&field.pixels[source..source + field.width as usize]);
+            }
+
+            let uv = |x: u32, y: u32| {
+                Vec2::new(
+                    x as f32 / atlas_size.0 as f32,
+                    y as f32 / atlas_size.1 as f32,
+                )
+            };
+            glyphs.insert(
+                character,
+                Glyph {
+                    uv_min: uv(x, y),
+                    uv_max: uv(x + field.width, y + field.height),
+                    offset: field.offset,
+                    size: Vec2::new(field.width as f32, field.height as f32),
+                    advance: field.advance,
+                },
+            );
+        }
+
+        Self {
+            font,
+            glyphs,
+            atlas_size,
+            atlas,
+            ascent: v_metrics.ascent,
+            descent: v_metrics.descent,
+            line_gap: v_metrics.line_gap,
+        }
+    }
+
+    /// The glyph for `character`, or the replacement glyph when there's none.
+    pub fn glyph(&self, character: char) -> &Glyph {
+        self.glyphs
+            .get(&character)
+            .or_else(|| self.glyphs.get(&REPLACEMENT))
+            .expect("Font has no replacement glyph")
+    }
+
+    /// Extra horizontal space between two characters, in base size pixels. Usually negative.
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.font
+            .pair_kerning(Scale::uniform(BASE_SIZE), first, second)
+    }
+
+    /// Height above the baseline, in base size pixels.
+    pub fn ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    /// Depth below the baseline, in base size pixels. Negative.
+    pub fn descent(&self) -> f32 {
+        self.descent
+    }
+
+    /// Distance between consecutive baselines, in base size pixels.
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+
+    pub fn atlas_size(&self) -> (u32, u32) {
+        self.atlas_size
+    }
+
+    /// One byte per texel, 0.5 is on the outline and bigger is inside.
+    pub fn atlas(&self) -> &[u8] {
+        &self.atlas
+    }
+}
+
+/// One glyph's distance field before it is packed into the atlas.
+struct GlyphField {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    offset: Vec2,
+    advance: f32,
+}
+
+impl GlyphField {
+    fn render(font: &rusttype::Font<'static>, character: char) -> GlyphField {
+        let advance = font
+            .glyph(character)
+            .scaled(Scale::uniform(BASE_SIZE))
+            .h_metrics()
+            .advance_width;
+        let glyph = font
+            .glyph(character)
+            .scaled(Scale::uniform(BASE_SIZE * SUPERSAMPLE as f32))
+            .positioned(point(0.0, 0.0));
+
+        let bounds = match glyph.pixel_bounding_box() {
+            Some(bounds) => bounds,
+            None => {
+                return GlyphField {
+                    width: 0,
+                    height: 0,
+                    pixels: Vec::new(),
+                    offset: Vec2::ZERO,
+                    advance,
+                }
+            }
+        };
+
+        // The field covers the outline plus the spread on every side, in whole base pixels.
+        let min_x = bounds.min.x.div_euclid(SUPERSAMPLE) - SPREAD;
+        let min_y = bounds.min.y.div_euclid(SUPERSAMPLE) - SPREAD;
+        let max_x = (bounds.max.x + SUPERSAMPLE - 1).div_euclid(SUPERSAMPLE) + SPREAD;
+        let max_y = (bounds.max.y + SUPERSAMPLE - 1).div_euclid(SUPERSAMPLE) + SPREAD;
+        let (width, height) = ((max_x - min_x) as usize, (max_y - min_y) as usize);
+
+        let (fine_width, fine_height) =
+            (width * SUPERSAMPLE as usize, height * SUPERSAMPLE as usize);
+        let mut inside = vec![false; fine_width * fine_height];
+        glyph.draw(|x, y, coverage| {
+            let x = (bounds.min.x + x as i32 - min_x * SUPERSAMPLE) as usize;
+            let y = (bounds.min.y + y as i32 - min_y * SUPERSAMPLE) as usize;
+            inside[y * fine_width + x] = coverage > 0.5;
+        });
+
+        let to_inside = distance_transform(&inside, fine_width, fine_height, true);
+        let to_outside = distance_transform(&inside, fine_width, fine_height, false);
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                // Average the signed distance over the fine pixels this texel covers.
+                let mut sum = 0.0;
+                for fine_y in y * SUPERSAMPLE as usize..(y + 1) * SUPERSAMPLE as usize {
+                    for fine_x in x * SUPERSAMPLE as usize..(x + 1) * SUPERSAMPLE as usize {
+                        let index = fine_y * fine_width + fine_x;
+                        sum += to_inside[index].sqrt() - to_outside[index].sqrt();
+                    }
+                }
+                let distance = sum / (SUPERSAMPLE * SUPERSAMPLE * SUPERSAMPLE) as f32;
+
+                let value = 0.5 - distance / (2.0 * SPREAD as f32);
+                pixels.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+
+        GlyphField {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+            offset: Vec2::new(min_x as f32, min_y as f32),
+            advance,
+        }
+    }
+}
+
+/// Squared distance from every pixel to the nearest pixel whose `inside` is `target`, using
+/// Felzenszwalb and Huttenlocher's separable transform.
+fn distance_transform(inside: &[bool], width: usize, height: usize, target: bool) -> Vec<f32> {
+    let mut distances: Vec<f32> = inside
+        .iter()
+        .map(|&pixel| if pixel == target { 0.0 } else { FAR })
+        .collect();
+
+    let longest = width.max(height);
+    let mut line = vec![0.0; longest];
+    let mut output = vec![0.0; longest];
+    let mut parabolas = vec![0; longest];
+    let mut boundaries = vec![0.0; longest + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            line[y] = distances[y * width + x];
+        }
+        transform_line(
+            &line[..height],
+            &mut output,
+            &mut parabolas,
+            &mut boundaries,
+        );
+        for y in 0..height {
+            distances[y * width + x] = output[y];
+        }
+    }
+    for y in 0..height {
+        line[..width].copy_from_slice(&distances[y * width..(y + 1) * width]);
+        transform_line(&line[..width], &mut output, &mut parabolas, &mut boundaries);
+        distances[y * width..(y + 1) * width].copy_from_slice(&output[..width]);
+    }
+
+    distances
+}
+
+/// The 1D squared distance transform of `line`: the lower envelope of parabolas rooted at every
+/// sample.
+fn transform_line(
+    line: &[f32],
+    output: &mut [f32],
+    parabolas: &mut [usize],
+    boundaries: &mut [f32],
+) {
+    let intersection = |q: usize, p: usize| {
+        let (q_f, p_f) = (q as f32, p as f32);
+        ((line[q] + q_f * q_f) - (line[p] + p_f * p_f)) / (2.0 * q_f - 2.0 * p_f)
+    };
+
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = f32::NEG_INFINITY;
+    boundaries[1] = f32::INFINITY;
+    for q in 1..line.len() {
+        let mut s = intersection(q, parabolas[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(q, parabolas[k]);
+        }
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+    for (q, distance) in output.iter_mut().enumerate().take(line.len()) {
+        while boundaries[k + 1] < q as f32 {
+            k += 1;
+        }
+        let offset = q as f32 - parabolas[k] as f32;
+        *distance = offset * offset + line[parabolas[k]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_font_covers_latin_1() {
+        let font = Font::embedded();
+
+        for character in ('!'..='~').chain('\u{c0}'..='\u{ff}') {
+            let glyph = font.glyph(character);
+            assert!(glyph.advance > 0.0, "{:?} has no advance", character);
+            assert!(glyph.uv_max.x > glyph.uv_min.x && glyph.uv_max.y > glyph.uv_min.y);
+        }
+        // Characters outside the atlas fall back to the replacement glyph.
+        assert_eq!(font.glyph('\u{4e2d}'), font.glyph(REPLACEMENT));
+
+        let (width, height) = font.atlas_size();
+        assert_eq!(font.atlas().len(), (width * height) as usize);
+        assert!(font.ascent() > 0.0 && font.descent() < 0.0);
+    }
+}
@@ -0,0 +1,42 @@
+use bytemuck::Pod;
+use common::math::{Mat4, Vec2, Vec3, Vec4};
+use std::mem::{align_of, size_of};
+
+/// Plain data that can be copied to the GPU byte for byte, like push constants, uniforms and
+/// vertices.
+///
+/// Opted into with an empty impl next to the type. bytemuck's `Pod` derive already refuses
+/// padding, pointers and invalid bit patterns at compile time, and `#[repr(C)]` is still needed
+/// for the field order to match the shader. There's deliberately no blanket impl for every `Pod`
+/// type: the impl is where a type states that a shader declares the same layout, which `Pod`
+/// alone doesn't say, so block or chunk data can't end up in a push constant by accident.
+pub trait GpuPod: Pod {
+    /// Shaders read their inputs as 32-bit words, evaluated at compile time wherever the type is
+    /// uploaded.
+    const WORD_LAYOUT: () = assert!(
+        size_of::<Self>().is_multiple_of(size_of::<u32>())
+            && align_of::<Self>() >= align_of::<u32>(),
+        "GPU data must be a multiple of 4 bytes in size and 4 byte aligned"
+    );
+}
+
+impl GpuPod for Vec2 {}
+impl GpuPod for Vec3 {}
+impl GpuPod for Vec4 {}
+impl GpuPod for Mat4 {}
+
+pub fn bytes_of<T: GpuPod>(value: &T) -> &[u8] {
+    let () = T::WORD_LAYOUT;
+    bytemuck::bytes_of(value)
+}
+
+pub fn slice_bytes<T: GpuPod>(values: &[T]) -> &[u8] {
+    let () = T::WORD_LAYOUT;
+    bytemuck::cast_slice(values)
+}
+
+/// Views a value as the 32-bit words push constants are made of.
+pub fn words_of<T: GpuPod>(value: &T) -> &[u32] {
+    let () = T::WORD_LAYOUT;
+    bytemuck::cast_slice(bytemuck::bytes_of(value))
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GpuObject {
+    CommandPool,
+    CommandBuffer,
+    RenderPass,
+    PipelineCache,
+    DescriptorSetLayout,
+    PipelineLayout,
+    GraphicsPipeline,
+    Framebuffer,
+    Buffer,
+    Memory,
+    Image,
+    ImageView,
+    Sampler,
+    DescriptorPool,
+    Fence,
+    Semaphore,
+}
+
+/// Counts live GPU objects so anything still alive once the renderer is torn down can be
+/// reported, along with how much memory they hold.
+#[derive(Debug, Default)]
+pub struct GpuObjectTracker {
+    live: BTreeMap<GpuObject, isize>,
+    memory_bytes: u64,
+}
+
+impl GpuObjectTracker {
+    pub fn new() -> GpuObjectTracker {
+        Self {
+            live: BTreeMap::new(),
+            memory_bytes: 0,
+        }
+    }
+
+    pub fn created(&mut self, object: GpuObject) {
+        *self.live.entry(object).or_insert(0) += 1;
+    }
+
+    pub fn destroyed(&mut self, object: GpuObject) {
+        *self.live.entry(object).or_insert(0) -= 1;
+    }
+
+    pub fn allocated(&mut self, bytes: u64) {
+        self.created(GpuObject::Memory);
+        self.memory_bytes += bytes;
+    }
+
+    pub fn freed(&mut self, bytes: u64) {
+        self.destroyed(GpuObject::Memory);
+        self.memory_bytes = self.memory_bytes.saturating_sub(bytes);
+    }
+
+    /// Bytes of device memory currently allocated.
+    pub fn memory_bytes(&self) -> u64 {
+        self.memory_bytes
+    }
+
+    pub fn leaked(&self) -> Vec<(GpuObject, isize)> {
+        self.live
+            .iter()
+            .filter(|(_, count)| **count != 0)
+            .map(|(object, count)| (*object, *count))
+            .collect()
+    }
+}
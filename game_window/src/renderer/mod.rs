@@ -1,8 +1,8 @@
 use crate::{
-    settings::Settings,
+    settings::{self, Settings},
     types::{EventLoop, LogicalSize, PhysicalSize},
 };
-use common::consts::APP_NAME;
+use common::{consts::APP_NAME, math::Vec2, metrics};
 
 #[cfg(feature = "dx12")]
 use gfx_backend_dx12 as back;
@@ -22,82 +22,278 @@ use gfx_backend_vulkan as back;
 
 use gfx_hal::{
     adapter::Adapter,
-    command::{ClearColor, ClearValue, CommandBuffer, CommandBufferFlags, Level, SubpassContents},
+    buffer::SubRange,
+    command::{CommandBuffer, CommandBufferFlags, Level},
     device::Device,
     format::{ChannelType, Format},
-    image::Layout,
-    pass::{Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, SubpassDesc},
+    image::{Layout, NumSamples},
     pool::{CommandPool, CommandPoolCreateFlags},
     prelude::{CommandQueue, QueueFamily},
-    pso::ShaderStageFlags,
+    pso::{ShaderStageFlags, Viewport},
     queue::{QueueGroup, Submission},
-    window::{Extent2D, PresentationSurface, Surface, SwapchainConfig},
+    window::{Extent2D, PresentMode, PresentationSurface, Surface, SwapchainConfig},
     Instance,
 };
-use shaderc::ShaderKind;
-use std::{mem::ManuallyDrop, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    mem::ManuallyDrop,
+};
+use tracing::{debug_span, error, info, warn};
 use winit::window::Window;
 
-use self::push_constants::PushConstants;
-mod push_constants;
+use self::{
+    buffer::{DynamicBuffer, HostBuffer},
+    capture::FrameCapture,
+    debug_mode::DebugMode,
+    draw_list::DrawList,
+    font::Font,
+    gpu_objects::{GpuObject, GpuObjectTracker},
+    pipeline::{PipelineId, Pipelines},
+    render_graph::{
+        ImageDesc, ImageId, ImageSize, ImportedImage, LoadOp, PassDesc, PassId, RenderGraph,
+        RenderGraphBuilder,
+    },
+    render_targets::DEPTH_FORMAT,
+    screenshot::Screenshot,
+    shader_library::{ShaderLibrary, ShaderSource},
+    texture::Texture,
+};
+pub mod buffer;
+pub mod capture;
+pub mod debug_lines;
+pub mod debug_mode;
+pub mod debug_overlay;
+pub mod draw_list;
+pub mod font;
+pub mod gpu_data;
+pub mod gpu_objects;
+pub mod pipeline;
+pub mod push_constants;
+pub mod reflection;
+pub mod render_graph;
+pub mod render_targets;
+pub mod screenshot;
+pub mod shader_library;
+pub mod text;
+pub mod texture;
+
 pub struct Resources {
     pub instance: <back::Backend as gfx_hal::Backend>::Instance,
     pub adapter: Adapter<back::Backend>,
     pub surface: <back::Backend as gfx_hal::Backend>::Surface,
     pub device: <back::Backend as gfx_hal::Backend>::Device,
-    pub render_passes: Vec<<back::Backend as gfx_hal::Backend>::RenderPass>,
-    pub pipeline_layouts: Vec<<back::Backend as gfx_hal::Backend>::PipelineLayout>,
-    pub pipelines: Vec<<back::Backend as gfx_hal::Backend>::GraphicsPipeline>,
+    pub render_graph: RenderGraph,
+    pub frame_graph: FrameGraph,
+    pub pipelines: Pipelines,
+    pub triangle_pipeline: PipelineId,
+    /// Whether the device can rasterize polygons as lines, for the wireframe debug mode.
+    pub line_polygons: bool,
+    pub unavailable_debug_modes: HashSet<DebugMode>,
+    pub debug_line_pipeline: PipelineId,
+    pub debug_line_buffer: DynamicBuffer,
+    pub text_pipeline: PipelineId,
+    pub text_buffer: DynamicBuffer,
+    pub font_atlas: Texture,
+    pub descriptor_pool: <back::Backend as gfx_hal::Backend>::DescriptorPool,
+    pub font_descriptor_set: <back::Backend as gfx_hal::Backend>::DescriptorSet,
+    pub present_screenshot_pipeline: PipelineId,
+    /// Points at the screenshot being drawn, rewritten for each new one.
+    pub screenshot_descriptor_set: <back::Backend as gfx_hal::Backend>::DescriptorSet,
+    /// Buffers the GPU may still be reading, destroyed once the frame's fence is signalled.
+    pub retired_buffers: Vec<HostBuffer>,
     pub command_pool: <back::Backend as gfx_hal::Backend>::CommandPool,
     pub submission_complete_fence: <back::Backend as gfx_hal::Backend>::Fence,
     pub rendering_complete_semaphore: <back::Backend as gfx_hal::Backend>::Semaphore,
     pub surface_color_format: Format,
     pub command_buffer: <back::Backend as gfx_hal::Backend>::CommandBuffer,
     pub queue_group: QueueGroup<back::Backend>,
+    pub gpu_objects: GpuObjectTracker,
+    pub shader_library: ShaderLibrary,
+    pub screenshot_requested: bool,
+    /// Drawn into on frames with a screenshot, kept while capturing.
+    pub screenshot: Option<Screenshot>,
+    /// Whether the last submitted frame copied into `screenshot`, saved once that frame is done.
+    pub screenshot_pending: bool,
+    /// Receives every frame instead of the screenshots directory while set.
+    pub capture: Option<FrameCapture>,
 }
 
 impl Resources {
-    pub fn new(event_loop: &EventLoop, settings: &Settings, window: &Window) -> Resources {
+    pub fn new(
+        event_loop: &EventLoop,
+        settings: &Settings,
+        window: &Window,
+        font: &Font,
+        shaders: ShaderSource,
+    ) -> Resources {
         let (instance, adapter, surface) = generate_backend_instance(window);
-        let (device, mut queue_group) = get_logical_device(&adapter, &surface);
+        let line_polygons = {
+            use gfx_hal::adapter::PhysicalDevice;
+
+            adapter
+                .physical_device
+                .features()
+                .contains(gfx_hal::Features::NON_FILL_POLYGON_MODE)
+        };
+        let features = if line_polygons {
+            gfx_hal::Features::NON_FILL_POLYGON_MODE
+        } else {
+            gfx_hal::Features::empty()
+        };
+        let (device, mut queue_group) = get_logical_device(&adapter, &surface, features);
 
-        let (command_pool, mut command_buffer) = generate_command_buffer(&device, &queue_group);
+        let mut gpu_objects = GpuObjectTracker::new();
+        let (command_pool, mut command_buffer) =
+            generate_command_buffer(&device, &queue_group, &mut gpu_objects);
 
         let surface_color_format = get_surface_color_format(&surface, &adapter);
 
-        let render_pass = render_pass(surface_color_format, &device);
+        let samples = {
+            use gfx_hal::adapter::PhysicalDevice;
 
-        let pipeline_layout = generate_pipeline(&device);
+            render_targets::supported_samples(
+                &adapter.physical_device.limits(),
+                settings.graphics().msaa_samples(),
+            )
+        };
+        log_samples(samples, settings.graphics().msaa_samples());
+
+        let (submission_complete_fence, rendering_complete_semaphore) =
+            generate_fence_and_semaphore(&device, &mut gpu_objects);
 
-        let vertex_shader = include_str!("shaders/part-1.vert");
-        let fragment_shader = include_str!("shaders/part-1.frag");
+        let (render_graph, frame_graph) =
+            frame_graph(surface_color_format, samples, &device, &mut gpu_objects);
 
-        let pipeline = unsafe {
-            make_pipeline(
+        let mut shader_library = ShaderLibrary::new(shaders);
+        let mut pipelines = Pipelines::new(&device, &mut gpu_objects);
+        let triangle_pipeline = pipelines
+            .get_or_create(
                 &device,
-                &render_pass,
-                &pipeline_layout,
-                vertex_shader,
-                fragment_shader,
+                &render_graph,
+                frame_graph.scene,
+                &mut shader_library,
+                &mut gpu_objects,
+                &DebugMode::None.triangle_pipeline(line_polygons),
             )
+            .unwrap_or_else(|error| panic!("{}", error));
+        let debug_line_pipeline = pipelines
+            .get_or_create(
+                &device,
+                &render_graph,
+                frame_graph.scene,
+                &mut shader_library,
+                &mut gpu_objects,
+                &debug_lines::pipeline(),
+            )
+            .unwrap_or_else(|error| panic!("{}", error));
+        let text_pipeline = pipelines
+            .get_or_create(
+                &device,
+                &render_graph,
+                frame_graph.scene,
+                &mut shader_library,
+                &mut gpu_objects,
+                &text::pipeline(),
+            )
+            .unwrap_or_else(|error| panic!("{}", error));
+        let present_screenshot_pipeline = pipelines
+            .get_or_create(
+                &device,
+                &render_graph,
+                frame_graph.present_screenshot,
+                &mut shader_library,
+                &mut gpu_objects,
+                &screenshot::present_pipeline(),
+            )
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        let memory_types = {
+            use gfx_hal::adapter::PhysicalDevice;
+
+            adapter.physical_device.memory_properties().memory_types
+        };
+        let font_atlas = Texture::new(
+            &device,
+            &memory_types,
+            &mut gpu_objects,
+            font.atlas_size(),
+            Format::R8Unorm,
+            font.atlas(),
+        );
+        let (descriptor_pool, font_descriptor_set, screenshot_descriptor_set) = unsafe {
+            use gfx_hal::pso::{
+                Descriptor, DescriptorPool, DescriptorPoolCreateFlags, DescriptorRangeDesc,
+                DescriptorSetWrite, DescriptorType, ImageDescriptorType,
+            };
+
+            let mut descriptor_pool = device
+                .create_descriptor_pool(
+                    2,
+                    [DescriptorRangeDesc {
+                        ty: DescriptorType::Image {
+                            ty: ImageDescriptorType::Sampled { with_sampler: true },
+                        },
+                        count: 2,
+                    }],
+                    DescriptorPoolCreateFlags::empty(),
+                )
+                .expect("Out of memory");
+            gpu_objects.created(GpuObject::DescriptorPool);
+
+            let set = descriptor_pool
+                .allocate_set(&pipelines.get(text_pipeline).set_layouts[0])
+                .expect("Failed to allocate font descriptor set");
+            device.write_descriptor_sets(vec![DescriptorSetWrite {
+                set: &set,
+                binding: 0,
+                array_offset: 0,
+                descriptors: Some(Descriptor::CombinedImageSampler(
+                    font_atlas.view(),
+                    Layout::ShaderReadOnlyOptimal,
+                    font_atlas.sampler(),
+                )),
+            }]);
+
+            let screenshot_set = descriptor_pool
+                .allocate_set(&pipelines.get(present_screenshot_pipeline).set_layouts[0])
+                .expect("Failed to allocate screenshot descriptor set");
+
+            (descriptor_pool, set, screenshot_set)
         };
 
-        let (submission_complete_fence, rendering_complete_semaphore) =
-            generate_fence_and_semaphore(&device);
         Self {
             instance,
             adapter,
             surface,
             device,
             command_pool,
-            render_passes: vec![render_pass],
-            pipeline_layouts: vec![pipeline_layout],
-            pipelines: vec![pipeline],
+            render_graph,
+            frame_graph,
+            pipelines,
+            triangle_pipeline,
+            line_polygons,
+            unavailable_debug_modes: HashSet::new(),
+            debug_line_pipeline,
+            debug_line_buffer: DynamicBuffer::new(gfx_hal::buffer::Usage::VERTEX),
+            text_pipeline,
+            text_buffer: DynamicBuffer::new(gfx_hal::buffer::Usage::VERTEX),
+            font_atlas,
+            descriptor_pool,
+            font_descriptor_set,
+            present_screenshot_pipeline,
+            screenshot_descriptor_set,
+            retired_buffers: Vec::new(),
             submission_complete_fence,
             rendering_complete_semaphore,
             surface_color_format,
             command_buffer,
             queue_group,
+            gpu_objects,
+            shader_library,
+            screenshot_requested: false,
+            screenshot: None,
+            screenshot_pending: false,
+            capture: None,
         }
     }
     pub fn reset_fence_and_command_pool(&mut self) {
@@ -112,15 +308,72 @@ impl Resources {
                 .expect("Out of memory");
 
             self.command_pool.reset(false);
+
+            for buffer in self.retired_buffers.drain(..) {
+                buffer.destroy(&self.device, &mut self.gpu_objects);
+            }
+            self.render_graph
+                .retire_framebuffers(&self.device, &mut self.gpu_objects);
+            if std::mem::take(&mut self.screenshot_pending) {
+                let frame = self
+                    .screenshot
+                    .as_ref()
+                    .expect("Pending screenshots aren't destroyed")
+                    .read(&self.device);
+                match &mut self.capture {
+                    Some(capture) => capture.push(frame),
+                    None => screenshot::save_async(frame),
+                }
+            }
+            // Only a capture keeps drawing into the same screenshot.
+            if self.capture.is_none() && !self.screenshot_requested {
+                if let Some(screenshot) = self.screenshot.take() {
+                    screenshot.destroy(&self.device, &mut self.gpu_objects);
+                }
+            }
         }
     }
 
-    pub fn reconfigure_swap(&mut self, surface_extent: &mut Extent2D) {
+    /// Saves the next rendered frame to a PNG in the screenshots directory.
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Sends every frame from now on to `capture`, which already has them while capturing.
+    pub fn start_capture(&mut self, capture: FrameCapture) {
+        self.capture = Some(capture);
+    }
+
+    /// Configures the swapchain and recreates everything sized to it or depending on the number
+    /// of samples. Has to come after `reset_fence_and_command_pool`, since the last frame may
+    /// still be drawing into the old render targets until then.
+    pub fn reconfigure_swap(
+        &mut self,
+        surface_extent: &mut Extent2D,
+        present_mode: settings::PresentMode,
+        msaa_samples: NumSamples,
+    ) {
         let caps = self.surface.capabilities(&self.adapter.physical_device);
 
         let mut swapchain_config =
             SwapchainConfig::from_caps(&caps, self.surface_color_format, *surface_extent);
 
+        let present_mode = match present_mode {
+            settings::PresentMode::Fifo => PresentMode::FIFO,
+            settings::PresentMode::Mailbox => PresentMode::MAILBOX,
+            settings::PresentMode::Immediate => PresentMode::IMMEDIATE,
+        };
+        // Every surface supports FIFO.
+        swapchain_config.present_mode = if caps.present_modes.contains(present_mode) {
+            present_mode
+        } else {
+            warn!(
+                "{:?} presentation is not supported, using FIFO",
+                present_mode
+            );
+            PresentMode::FIFO
+        };
+
         // This seems to fix some fullscreen slowdown on macOS.
         if caps.image_count.contains(&3) {
             swapchain_config.image_count = 3;
@@ -133,14 +386,75 @@ impl Resources {
                 .configure_swapchain(&self.device, swapchain_config)
                 .expect("Failed to configure swapchain");
         };
+
+        let samples = {
+            use gfx_hal::adapter::PhysicalDevice;
+
+            render_targets::supported_samples(&self.adapter.physical_device.limits(), msaa_samples)
+        };
+        if samples != self.render_graph.samples(self.frame_graph.scene) {
+            log_samples(samples, msaa_samples);
+            self.set_samples(samples);
+        }
+
+        let memory_types = {
+            use gfx_hal::adapter::PhysicalDevice;
+
+            self.adapter
+                .physical_device
+                .memory_properties()
+                .memory_types
+        };
+        unsafe {
+            self.render_graph.resize(
+                &self.device,
+                &memory_types,
+                &mut self.gpu_objects,
+                *surface_extent,
+            );
+        }
+    }
+
+    /// Replaces the render graph and every pipeline with ones drawing `samples` per pixel. Keeps
+    /// drawing with the old ones if any pipeline can't be created.
+    fn set_samples(&mut self, samples: NumSamples) {
+        let (render_graph, frame_graph) = frame_graph(
+            self.surface_color_format,
+            samples,
+            &self.device,
+            &mut self.gpu_objects,
+        );
+        let recreated = unsafe {
+            self.pipelines
+                .recreate(&self.device, &render_graph, &mut self.shader_library)
+        };
+        match recreated {
+            Ok(()) => unsafe {
+                std::mem::replace(&mut self.render_graph, render_graph)
+                    .destroy(&self.device, &mut self.gpu_objects);
+                self.frame_graph = frame_graph;
+            },
+            Err(error) => {
+                error!(
+                    "Could not switch to {}x multisampling, keeping {}x: {}",
+                    samples,
+                    self.render_graph.samples(self.frame_graph.scene),
+                    error
+                );
+                unsafe { render_graph.destroy(&self.device, &mut self.gpu_objects) };
+            }
+        }
     }
 
+    /// Draws and presents a frame. Returns whether one was submitted, which doesn't happen when
+    /// no swapchain image could be acquired.
     pub fn render(
         &mut self,
         should_configure_swapchain: &mut bool,
         surface_extent: &mut Extent2D,
-        start_time: Instant,
-    ) {
+        draw_list: &DrawList,
+    ) -> bool {
+        let acquire_span = debug_span!("acquire").entered();
         let surface_image = unsafe {
             // We refuse to wait more than a second, to avoid hanging.
             let acquire_timeout_ns = 1_000_000_000;
@@ -149,118 +463,142 @@ impl Resources {
                 Ok((image, _)) => image,
                 Err(_) => {
                     *should_configure_swapchain = true;
-                    return;
+                    return false;
                 }
             }
         };
 
-        let framebuffer = unsafe {
+        acquire_span.exit();
+
+        let record_span = debug_span!("record").entered();
+        let mut draw_calls = 0;
+        let triangle_pipeline = self.debug_mode_pipeline(draw_list.debug_mode());
+        {
+            use gfx_hal::adapter::PhysicalDevice;
+
+            let memory_types = self
+                .adapter
+                .physical_device
+                .memory_properties()
+                .memory_types;
+            self.debug_line_buffer.write(
+                &self.device,
+                &memory_types,
+                &mut self.gpu_objects,
+                gpu_data::slice_bytes(&debug_lines::vertices(draw_list.debug_lines())),
+            );
+            self.text_buffer.write(
+                &self.device,
+                &memory_types,
+                &mut self.gpu_objects,
+                gpu_data::slice_bytes(draw_list.overlay_vertices()),
+            );
+        }
+        // Taken out while the frame borrows it, put back once it's recorded.
+        let screenshot = if self.screenshot_requested || self.capture.is_some() {
+            match self.screenshot.take() {
+                Some(screenshot) if screenshot.extent() == *surface_extent => Some(screenshot),
+                old => {
+                    use gfx_hal::adapter::PhysicalDevice;
+
+                    // The last frame using it is done, `reset_fence_and_command_pool` read it.
+                    if let Some(old) = old {
+                        unsafe {
+                            old.destroy(&self.device, &mut self.gpu_objects);
+                        }
+                    }
+
+                    let memory_types = self
+                        .adapter
+                        .physical_device
+                        .memory_properties()
+                        .memory_types;
+                    let screenshot = Screenshot::new(
+                        &self.device,
+                        &memory_types,
+                        &mut self.gpu_objects,
+                        *surface_extent,
+                        self.surface_color_format,
+                    );
+                    unsafe {
+                        use gfx_hal::pso::{Descriptor, DescriptorSetWrite};
+
+                        self.device.write_descriptor_sets(vec![DescriptorSetWrite {
+                            set: &self.screenshot_descriptor_set,
+                            binding: 0,
+                            array_offset: 0,
+                            descriptors: Some(Descriptor::CombinedImageSampler(
+                                screenshot.view(),
+                                Layout::ShaderReadOnlyOptimal,
+                                screenshot.sampler(),
+                            )),
+                        }]);
+                    }
+                    Some(screenshot)
+                }
+            }
+        } else {
+            None
+        };
+        let swapchain_view = {
             use std::borrow::Borrow;
 
-            use gfx_hal::image::Extent;
-
-            self.device
-                .create_framebuffer(
-                    &self.render_passes[0],
-                    vec![surface_image.borrow()],
-                    Extent {
-                        width: surface_extent.width,
-                        height: surface_extent.height,
-                        depth: 1,
-                    },
-                )
-                .unwrap()
+            surface_image.borrow()
         };
+        // Binding a screenshot leaves out the scene pass drawing into the swapchain image directly.
+        let mut imported = vec![ImportedImage {
+            image: self.frame_graph.swapchain,
+            view: swapchain_view,
+        }];
+        if let Some(screenshot) = &screenshot {
+            imported.push(ImportedImage {
+                image: self.frame_graph.screenshot_target,
+                view: screenshot.view(),
+            });
+        }
 
-        let viewport = {
-            use gfx_hal::pso::{Rect, Viewport};
-
-            Viewport {
-                rect: Rect {
-                    x: 0,
-                    y: 0,
-                    w: surface_extent.width as i16,
-                    h: surface_extent.height as i16,
-                },
-                depth: 0.0..1.0,
-            }
-        };
         unsafe {
             self.command_buffer
                 .begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
+            if let Some(staging) = self.font_atlas.record_upload(&mut self.command_buffer) {
+                self.retired_buffers.push(staging);
+            }
 
-            self.command_buffer.set_viewports(0, &[viewport.clone()]);
-            self.command_buffer.set_scissors(0, &[viewport.rect]);
-            self.command_buffer.begin_render_pass(
-                &self.render_passes[0],
-                &framebuffer,
-                viewport.rect,
-                &[ClearValue {
-                    color: ClearColor {
-                        float32: [0.0, 0.0, 0.0, 1.0],
-                    },
-                }],
-                SubpassContents::Inline,
-            );
-            self.command_buffer
-                .bind_graphics_pipeline(&self.pipelines[0]);
-
-            let anim = start_time.elapsed().as_secs_f32().sin() * 0.5 + 0.5;
-
-            let small = [0.33, 0.33];
-
-            let triangles = &[
-                // Red triangle
-                PushConstants {
-                    color: [1.0, 0.0, 0.0, 1.0],
-                    pos: [-0.5, -0.5],
-                    scale: small,
-                },
-                // Green triangle
-                PushConstants {
-                    color: [0.0, 1.0, 0.0, 1.0],
-                    pos: [0.0, -0.5],
-                    scale: small,
-                },
-                // Blue triangle
-                PushConstants {
-                    color: [0.0, 0.0, 1.0, 1.0],
-                    pos: [0.5, -0.5],
-                    scale: small,
-                },
-                // Blue <-> cyan animated triangle
-                PushConstants {
-                    color: [0.0, anim, 1.0, 1.0],
-                    pos: [-0.5, 0.5],
-                    scale: small,
-                },
-                // Down <-> up animated triangle
-                PushConstants {
-                    color: [1.0, 1.0, 1.0, 1.0],
-                    pos: [0.0, 0.5 - anim * 0.5],
-                    scale: small,
-                },
-                // Small <-> big animated triangle
-                PushConstants {
-                    color: [1.0, 1.0, 1.0, 1.0],
-                    pos: [0.5, 0.5],
-                    scale: [0.33 + anim * 0.33, 0.33 + anim * 0.33],
-                },
-            ];
-            for triangle in triangles {
-                self.command_buffer.push_graphics_constants(
-                    &self.pipeline_layouts[0],
-                    ShaderStageFlags::VERTEX,
-                    0,
-                    push_constant_bytes(triangle),
+            let clear_color = draw_list.debug_mode().clear_color(draw_list.clear_color());
+            for pass in self.render_graph.schedule(&imported) {
+                let _pass_span =
+                    debug_span!("pass", name = self.render_graph.pass_name(pass)).entered();
+                let viewport = self.render_graph.begin_pass(
+                    &self.device,
+                    &mut self.gpu_objects,
+                    &mut self.command_buffer,
+                    pass,
+                    &imported,
+                    clear_color,
                 );
+                let context = PassContext {
+                    viewport: &viewport,
+                    draw_list,
+                    triangle_pipeline,
+                };
+                draw_calls += (self.frame_graph.records[&pass])(self, &context);
+                self.render_graph.end_pass(&mut self.command_buffer);
+            }
+            drop(imported);
 
-                self.command_buffer.draw(0..3, 0..1);
+            if let Some(screenshot) = screenshot {
+                screenshot.record_copy(&mut self.command_buffer);
+                self.screenshot = Some(screenshot);
+                self.screenshot_pending = true;
+                self.screenshot_requested = false;
             }
-            self.command_buffer.end_render_pass();
             self.command_buffer.finish();
         }
+        record_span.exit();
+        metrics::set_number(metrics::DRAW_CALLS, f64::from(draw_calls));
+        metrics::set_number(metrics::GPU_MEMORY, self.gpu_objects.memory_bytes() as f64);
 
+        let _submit_span = debug_span!("submit").entered();
         unsafe {
             let submission = Submission {
                 command_buffers: vec![&self.command_buffer],
@@ -276,17 +614,129 @@ impl Resources {
             );
 
             *should_configure_swapchain |= result.is_err();
+        }
+        true
+    }
+
+    /// Records drawing the whole draw list, returning the number of draw calls.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer must be recording, inside the render graph's scene or screenshot pass.
+    unsafe fn record_scene(&mut self, context: &PassContext) -> u32 {
+        let PassContext {
+            viewport,
+            draw_list,
+            triangle_pipeline,
+        } = *context;
+        let mut draw_calls = 0;
+        let pipeline = self.pipelines.get(triangle_pipeline);
+        self.command_buffer
+            .bind_graphics_pipeline(&pipeline.pipeline);
+
+        for triangle in draw_list.triangles() {
+            self.command_buffer.push_graphics_constants(
+                &pipeline.layout,
+                ShaderStageFlags::VERTEX,
+                0,
+                gpu_data::words_of(triangle),
+            );
 
-            self.device.destroy_framebuffer(framebuffer);
+            self.command_buffer.draw(0..3, 0..1);
+            draw_calls += 1;
+        }
+
+        if let Some(buffer) = self.debug_line_buffer.buffer() {
+            let vertex_count = draw_list.debug_lines().len() as u32 * 2;
+            if vertex_count > 0 {
+                let pipeline = self.pipelines.get(self.debug_line_pipeline);
+                self.command_buffer
+                    .bind_graphics_pipeline(&pipeline.pipeline);
+                self.command_buffer
+                    .bind_vertex_buffers(0, std::iter::once((buffer, SubRange::WHOLE)));
+                self.command_buffer.push_graphics_constants(
+                    &pipeline.layout,
+                    ShaderStageFlags::VERTEX,
+                    0,
+                    gpu_data::words_of(&draw_list.view_projection()),
+                );
+                self.command_buffer.draw(0..vertex_count, 0..1);
+                draw_calls += 1;
+            }
+        }
+
+        // The 2D overlay goes on top of everything else.
+        if let Some(buffer) = self.text_buffer.buffer() {
+            let vertex_count = draw_list.overlay_vertices().len() as u32;
+            if vertex_count > 0 {
+                let pipeline = self.pipelines.get(self.text_pipeline);
+                self.command_buffer
+                    .bind_graphics_pipeline(&pipeline.pipeline);
+                self.command_buffer.bind_graphics_descriptor_sets(
+                    &pipeline.layout,
+                    0,
+                    std::iter::once(&self.font_descriptor_set),
+                    &[],
+                );
+                self.command_buffer
+                    .bind_vertex_buffers(0, std::iter::once((buffer, SubRange::WHOLE)));
+                self.command_buffer.push_graphics_constants(
+                    &pipeline.layout,
+                    ShaderStageFlags::VERTEX,
+                    0,
+                    gpu_data::words_of(&Vec2::new(viewport.rect.w as f32, viewport.rect.h as f32)),
+                );
+                self.command_buffer.draw(0..vertex_count, 0..1);
+                draw_calls += 1;
+            }
         }
+        draw_calls
+    }
+
+    /// Records drawing the screenshot drawn this frame onto the swapchain image, returning the
+    /// number of draw calls.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer must be recording, inside the render graph's present screenshot pass.
+    unsafe fn record_present_screenshot(&mut self, _context: &PassContext) -> u32 {
+        let pipeline = self.pipelines.get(self.present_screenshot_pipeline);
+        self.command_buffer
+            .bind_graphics_pipeline(&pipeline.pipeline);
+        self.command_buffer.bind_graphics_descriptor_sets(
+            &pipeline.layout,
+            0,
+            std::iter::once(&self.screenshot_descriptor_set),
+            &[],
+        );
+        self.command_buffer.draw(0..3, 0..1);
+        1
     }
-}
 
-pub unsafe fn push_constant_bytes<T>(push_constants: &T) -> &[u32] {
-    let size_in_bytes = std::mem::size_of::<T>();
-    let size_in_u32s = size_in_bytes / std::mem::size_of::<u32>();
-    let start_ptr = push_constants as *const T as *const u32;
-    std::slice::from_raw_parts(start_ptr, size_in_u32s)
+    /// The pipeline to draw the draw list's triangles with in `mode`. Modes the device can't draw
+    /// are logged once and fall back to drawing normally.
+    fn debug_mode_pipeline(&mut self, mode: DebugMode) -> PipelineId {
+        if mode == DebugMode::None || self.unavailable_debug_modes.contains(&mode) {
+            return self.triangle_pipeline;
+        }
+
+        let result = self.pipelines.get_or_create(
+            &self.device,
+            &self.render_graph,
+            self.frame_graph.scene,
+            &mut self.shader_library,
+            &mut self.gpu_objects,
+            &mode.triangle_pipeline(self.line_polygons),
+        );
+        match result {
+            Ok(id) => id,
+            Err(error) => {
+                error!("Could not draw {} debug mode: {}", mode.name(), error);
+                self.unavailable_debug_modes.insert(mode);
+                self.triangle_pipeline
+            }
+        }
+    }
 }
 
 pub fn calc_logical_and_physical_window_size(
@@ -316,6 +766,19 @@ fn generate_backend_instance(
     };
 
     let adapter = instance.enumerate_adapters().remove(0);
+    info!(
+        "Using adapter {} ({:?})",
+        adapter.info.name, adapter.info.device_type
+    );
+    metrics::set_text(metrics::ADAPTER, adapter.info.name.clone());
+    metrics::set_text(
+        metrics::BACKEND,
+        settings::GraphicsBackend::compiled().name(),
+    );
+    crate::crash::set_adapter_info(format!(
+        "{} ({:?}, vendor {:#x}, device {:#x})",
+        adapter.info.name, adapter.info.device_type, adapter.info.vendor, adapter.info.device
+    ));
 
     (instance, adapter, surface)
 }
@@ -323,6 +786,7 @@ fn generate_backend_instance(
 fn get_logical_device(
     adapter: &Adapter<back::Backend>,
     surface: &<back::Backend as gfx_hal::Backend>::Surface,
+    features: gfx_hal::Features,
 ) -> (
     <back::Backend as gfx_hal::Backend>::Device,
     QueueGroup<back::Backend>,
@@ -340,7 +804,7 @@ fn get_logical_device(
 
         adapter
             .physical_device
-            .open(&[(queue_family, &[1.0])], gfx_hal::Features::empty())
+            .open(&[(queue_family, &[1.0])], features)
             .expect("Failed to open device")
     };
 
@@ -350,6 +814,7 @@ fn get_logical_device(
 fn generate_command_buffer(
     device: &<back::Backend as gfx_hal::Backend>::Device,
     queue_group: &QueueGroup<back::Backend>,
+    gpu_objects: &mut GpuObjectTracker,
 ) -> (
     <back::Backend as gfx_hal::Backend>::CommandPool,
     <back::Backend as gfx_hal::Backend>::CommandBuffer,
@@ -358,8 +823,10 @@ fn generate_command_buffer(
         let mut command_pool = device
             .create_command_pool(queue_group.family, CommandPoolCreateFlags::empty())
             .expect("Out of memory");
+        gpu_objects.created(GpuObject::CommandPool);
 
         let command_buffer = command_pool.allocate_one(Level::Primary);
+        gpu_objects.created(GpuObject::CommandBuffer);
 
         (command_pool, command_buffer)
     }
@@ -381,144 +848,128 @@ fn get_surface_color_format(
         .unwrap_or(default_format)
 }
 
-fn render_pass(
-    surface_color_format: Format,
-    device: &<back::Backend as gfx_hal::Backend>::Device,
-) -> <back::Backend as gfx_hal::Backend>::RenderPass {
-    let color_attachment = Attachment {
-        format: Some(surface_color_format),
-        samples: 1,
-        ops: AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::Store),
-        stencil_ops: AttachmentOps::DONT_CARE,
-        layouts: Layout::Undefined..Layout::Present,
-    };
-
-    let subpass = SubpassDesc {
-        colors: &[(0, Layout::ColorAttachmentOptimal)],
-        depth_stencil: None,
-        inputs: &[],
-        resolves: &[],
-        preserves: &[],
-    };
-
-    unsafe {
-        device
-            .create_render_pass(&[color_attachment], &[subpass], &[])
-            .expect("Out of memory")
-    }
-}
-
-fn generate_pipeline(
-    device: &<back::Backend as gfx_hal::Backend>::Device,
-) -> <back::Backend as gfx_hal::Backend>::PipelineLayout {
-    unsafe {
-        let push_constant_bytes = std::mem::size_of::<push_constants::PushConstants>() as u32;
-        device
-            .create_pipeline_layout(&[], &[(ShaderStageFlags::VERTEX, 0..push_constant_bytes)])
-            .expect("Out of memory")
-    }
+/// Passes and images of the render graph every frame is drawn with.
+pub struct FrameGraph {
+    pub scene: PassId,
+    /// Draws the scene into a screenshot instead, on frames with one bound. Has the same
+    /// attachments as `scene`, so it draws with the pipelines made for that.
+    ///
+    /// gfx-hal only hands out a view of swapchain images, so they can't be copied from. The
+    /// screenshot is drawn onto the swapchain image by `present_screenshot` instead.
+    pub screenshot: PassId,
+    pub present_screenshot: PassId,
+    pub swapchain: ImageId,
+    /// Bound on frames with a screenshot, which leaves out `scene`.
+    pub screenshot_target: ImageId,
+    records: HashMap<PassId, RecordPass>,
 }
 
-fn compile_shader(glsl: &str, shader_kind: ShaderKind) -> Vec<u32> {
-    let mut compiler = shaderc::Compiler::new().unwrap();
-
-    let compiled_shader = compiler
-        .compile_into_spirv(glsl, shader_kind, "unnamed", "main", None)
-        .expect("Failed to compile shader");
+/// Records the draws of a pass, returning the number of draw calls.
+type RecordPass = unsafe fn(&mut Resources, &PassContext) -> u32;
 
-    compiled_shader.as_binary().to_vec()
+/// What a pass is recorded with this frame.
+#[derive(Clone, Copy)]
+struct PassContext<'a> {
+    viewport: &'a Viewport,
+    draw_list: &'a DrawList,
+    triangle_pipeline: PipelineId,
 }
 
-/// # Safety
-///
-/// This shit se puede despichar, no se que hace but ok.
-unsafe fn make_pipeline(
+fn frame_graph(
+    surface_color_format: Format,
+    samples: NumSamples,
     device: &<back::Backend as gfx_hal::Backend>::Device,
-    render_pass: &<back::Backend as gfx_hal::Backend>::RenderPass,
-    pipeline_layout: &<back::Backend as gfx_hal::Backend>::PipelineLayout,
-    vertex_shader: &str,
-    fragment_shader: &str,
-) -> <back::Backend as gfx_hal::Backend>::GraphicsPipeline {
-    use gfx_hal::pass::Subpass;
-    use gfx_hal::pso::{
-        BlendState, ColorBlendDesc, ColorMask, EntryPoint, Face, GraphicsPipelineDesc,
-        InputAssemblerDesc, Primitive, PrimitiveAssemblerDesc, Rasterizer, Specialization,
+    gpu_objects: &mut GpuObjectTracker,
+) -> (RenderGraph, FrameGraph) {
+    let mut graph = RenderGraphBuilder::new();
+    let target = ImageDesc::new(surface_color_format, 1, ImageSize::Swapchain);
+    let swapchain = graph.import("swapchain", target, Layout::Present);
+    let screenshot_target = graph.import("screenshot", target, Layout::ShaderReadOnlyOptimal);
+    let depth = graph.transient(
+        "depth",
+        ImageDesc::new(DEPTH_FORMAT, samples, ImageSize::Swapchain),
+    );
+    let multisampled_color = if samples > 1 {
+        Some(graph.transient(
+            "multisampled colour",
+            ImageDesc::new(surface_color_format, samples, ImageSize::Swapchain),
+        ))
+    } else {
+        None
     };
 
-    let vertex_shader_module = device
-        .create_shader_module(&compile_shader(vertex_shader, ShaderKind::Vertex))
-        .expect("Failed to create vertex shader module");
-
-    let fragment_shader_module = device
-        .create_shader_module(&compile_shader(fragment_shader, ShaderKind::Fragment))
-        .expect("Failed to create fragment shader module");
-
-    let (vs_entry, fs_entry) = (
-        EntryPoint {
-            entry: "main",
-            module: &vertex_shader_module,
-            specialization: Specialization::default(),
-        },
-        EntryPoint {
-            entry: "main",
-            module: &fragment_shader_module,
-            specialization: Specialization::default(),
-        },
-    );
-    let primitive_assembler = PrimitiveAssemblerDesc::Vertex {
-        buffers: &[],
-        attributes: &[],
-        input_assembler: InputAssemblerDesc::new(Primitive::TriangleList),
-        vertex: vs_entry,
-        tessellation: None,
-        geometry: None,
+    let scene_pass = |name, target| {
+        let pass = PassDesc::new(name).depth(depth, LoadOp::Clear);
+        match multisampled_color {
+            Some(color) => pass.color(color, LoadOp::Clear).resolve(target),
+            None => pass.color(target, LoadOp::Clear),
+        }
     };
-    let mut pipeline_desc = GraphicsPipelineDesc::new(
-        primitive_assembler,
-        Rasterizer {
-            cull_face: Face::BACK,
-            ..Rasterizer::FILL
-        },
-        Some(fs_entry),
-        pipeline_layout,
-        Subpass {
-            index: 0,
-            main_pass: render_pass,
-        },
+    let scene = graph.add_pass(scene_pass("scene", swapchain).unless(screenshot_target));
+    let screenshot = graph.add_pass(scene_pass("screenshot", screenshot_target));
+    // Draws over every pixel, and with a single sample since there's nothing to smooth.
+    let present_screenshot = graph.add_pass(
+        PassDesc::new("present screenshot")
+            .color(swapchain, LoadOp::DontCare)
+            .sample(screenshot_target),
     );
 
-    pipeline_desc.blender.targets.push(ColorBlendDesc {
-        mask: ColorMask::ALL,
-        blend: Some(BlendState::ALPHA),
-    });
-
-    let pipeline = device
-        .create_graphics_pipeline(&pipeline_desc, None)
-        .expect("Failed to create graphics pipeline");
-
-    device.destroy_shader_module(vertex_shader_module);
-    device.destroy_shader_module(fragment_shader_module);
+    (
+        graph.build(device, gpu_objects),
+        FrameGraph {
+            scene,
+            screenshot,
+            present_screenshot,
+            swapchain,
+            screenshot_target,
+            records: vec![
+                (scene, Resources::record_scene as RecordPass),
+                (screenshot, Resources::record_scene),
+                (present_screenshot, Resources::record_present_screenshot),
+            ]
+            .into_iter()
+            .collect(),
+        },
+    )
+}
 
-    pipeline
+fn log_samples(samples: NumSamples, requested: NumSamples) {
+    if samples < requested {
+        warn!(
+            "{}x multisampling is not supported, using {}x",
+            requested, samples
+        );
+    } else {
+        info!("Multisampling with {}x", samples);
+    }
 }
 
 fn generate_fence_and_semaphore(
     device: &<back::Backend as gfx_hal::Backend>::Device,
+    gpu_objects: &mut GpuObjectTracker,
 ) -> (
     <back::Backend as gfx_hal::Backend>::Fence,
     <back::Backend as gfx_hal::Backend>::Semaphore,
 ) {
-    (
-        device.create_fence(true).expect("Out of memory"),
-        device.create_semaphore().expect("Out of memory"),
-    )
+    let fence = device.create_fence(true).expect("Out of memory");
+    gpu_objects.created(GpuObject::Fence);
+    let semaphore = device.create_semaphore().expect("Out of memory");
+    gpu_objects.created(GpuObject::Semaphore);
+
+    (fence, semaphore)
 }
 
 pub struct ResourceHolder(pub ManuallyDrop<Resources>);
 impl ResourceHolder {
-    pub fn new(event_loop: &EventLoop, settings: &Settings, window: &Window) -> ResourceHolder {
+    pub fn new(
+        event_loop: &EventLoop,
+        settings: &Settings,
+        window: &Window,
+        font: &Font,
+        shaders: ShaderSource,
+    ) -> ResourceHolder {
         ResourceHolder(ManuallyDrop::new(Resources::new(
-            event_loop, settings, window,
+            event_loop, settings, window, font, shaders,
         )))
     }
 }
@@ -530,32 +981,85 @@ impl Drop for ResourceHolder {
                 instance,
                 mut surface,
                 device,
-                command_pool,
-                render_passes,
-                pipeline_layouts,
+                mut command_pool,
+                render_graph,
+                frame_graph: _,
                 pipelines,
+                triangle_pipeline: _,
+                line_polygons: _,
+                unavailable_debug_modes: _,
+                debug_line_pipeline: _,
+                debug_line_buffer,
+                text_pipeline: _,
+                text_buffer,
+                font_atlas,
+                descriptor_pool,
+                font_descriptor_set: _,
+                present_screenshot_pipeline: _,
+                screenshot_descriptor_set: _,
+                retired_buffers,
                 submission_complete_fence,
                 rendering_complete_semaphore,
                 adapter,
-                surface_color_format,
+                surface_color_format: _,
                 command_buffer,
                 queue_group,
+                mut gpu_objects,
+                shader_library: _,
+                screenshot_requested: _,
+                screenshot,
+                screenshot_pending,
+                mut capture,
             } = ManuallyDrop::take(&mut self.0);
 
-            device.destroy_semaphore(rendering_complete_semaphore);
-            device.destroy_fence(submission_complete_fence);
-            for pipeline in pipelines {
-                device.destroy_graphics_pipeline(pipeline);
+            // Nothing can be destroyed while the GPU may still be using it.
+            if device.wait_idle().is_err() {
+                error!("Device ran out of memory while waiting for it to go idle");
             }
-            for pipeline_layout in pipeline_layouts {
-                device.destroy_pipeline_layout(pipeline_layout);
+
+            if let Some(screenshot) = screenshot {
+                if screenshot_pending {
+                    let frame = screenshot.read(&device);
+                    match &mut capture {
+                        Some(capture) => capture.push(frame),
+                        None => screenshot::save_async(frame),
+                    }
+                }
+                screenshot.destroy(&device, &mut gpu_objects);
             }
-            for render_pass in render_passes {
-                device.destroy_render_pass(render_pass);
+            // Waits for the last frames to be written.
+            drop(capture);
+            device.destroy_semaphore(rendering_complete_semaphore);
+            gpu_objects.destroyed(GpuObject::Semaphore);
+            device.destroy_fence(submission_complete_fence);
+            gpu_objects.destroyed(GpuObject::Fence);
+            debug_line_buffer.destroy(&device, &mut gpu_objects);
+            text_buffer.destroy(&device, &mut gpu_objects);
+            for buffer in retired_buffers {
+                buffer.destroy(&device, &mut gpu_objects);
             }
+            font_atlas.destroy(&device, &mut gpu_objects);
+            device.destroy_descriptor_pool(descriptor_pool);
+            gpu_objects.destroyed(GpuObject::DescriptorPool);
+            render_graph.destroy(&device, &mut gpu_objects);
+            pipelines.destroy(&device, &mut gpu_objects);
+            command_pool.free(vec![command_buffer]);
+            gpu_objects.destroyed(GpuObject::CommandBuffer);
             device.destroy_command_pool(command_pool);
+            gpu_objects.destroyed(GpuObject::CommandPool);
             surface.unconfigure_swapchain(&device);
+
+            // The queues and device go before the surface and instance they were created from.
+            drop(queue_group);
+            drop(device);
             instance.destroy_surface(surface);
+            drop(adapter);
+
+            if cfg!(debug_assertions) {
+                for (object, count) in gpu_objects.leaked() {
+                    warn!("Leaked {} {:?}", count, object);
+                }
+            }
         }
     }
 }
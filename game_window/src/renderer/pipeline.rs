@@ -0,0 +1,622 @@
+use super::{
+    back,
+    gpu_data::GpuPod,
+    gpu_objects::{GpuObject, GpuObjectTracker},
+    reflection::{PipelineInterface, ReflectionError, ShaderReflection},
+    render_graph::{PassId, RenderGraph},
+    shader_library::{Defines, ShaderError, ShaderLibrary},
+};
+use common::paths::data_dir;
+use gfx_hal::{
+    device::Device,
+    format::Format,
+    pass::Subpass,
+    pso::{
+        self, AttributeDesc, BlendState, ColorBlendDesc, ColorMask, CreationError, DepthTest,
+        DescriptorSetLayoutBinding, DescriptorType, Element, EntryPoint, Face,
+        GraphicsPipelineDesc, InputAssemblerDesc, Multisampling, Primitive, PrimitiveAssemblerDesc,
+        Rasterizer, ShaderStageFlags, Specialization, VertexBufferDesc, VertexInputRate,
+    },
+};
+use std::{collections::HashMap, error::Error, fmt, fs, mem::size_of, ops::Range, path::PathBuf};
+use tracing::{debug, info, warn};
+
+/// `gfx_hal::pso::PolygonMode` can't be hashed, so descriptions use this instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+impl From<PolygonMode> for pso::PolygonMode {
+    fn from(mode: PolygonMode) -> pso::PolygonMode {
+        match mode {
+            PolygonMode::Fill => pso::PolygonMode::Fill,
+            PolygonMode::Line => pso::PolygonMode::Line,
+            PolygonMode::Point => pso::PolygonMode::Point,
+        }
+    }
+}
+
+/// Everything that makes one graphics pipeline different from another. Identical descriptions
+/// share a single pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PipelineDesc {
+    vertex_shader: String,
+    fragment_shader: String,
+    defines: Defines,
+    topology: Primitive,
+    cull: Face,
+    polygon_mode: PolygonMode,
+    blend: Option<BlendState>,
+    depth: Option<DepthTest>,
+    vertex_buffers: Vec<VertexBufferDesc>,
+    attributes: Vec<AttributeDesc>,
+    descriptor_sets: Vec<Vec<(u32, DescriptorType, ShaderStageFlags)>>,
+    push_constants: Vec<(ShaderStageFlags, Range<u32>)>,
+}
+
+impl PipelineDesc {
+    /// An opaque, back face culled triangle list pipeline drawing with the given shaders from
+    /// the shader library.
+    pub fn new(vertex_shader: &str, fragment_shader: &str) -> PipelineDesc {
+        Self {
+            vertex_shader: vertex_shader.to_owned(),
+            fragment_shader: fragment_shader.to_owned(),
+            defines: Defines::new(),
+            topology: Primitive::TriangleList,
+            cull: Face::BACK,
+            polygon_mode: PolygonMode::Fill,
+            blend: None,
+            depth: None,
+            vertex_buffers: Vec::new(),
+            attributes: Vec::new(),
+            descriptor_sets: Vec::new(),
+            push_constants: Vec::new(),
+        }
+    }
+
+    pub fn define(mut self, name: &str, value: Option<&str>) -> PipelineDesc {
+        self.defines
+            .insert(name.to_owned(), value.map(str::to_owned));
+        self
+    }
+
+    pub fn topology(mut self, topology: Primitive) -> PipelineDesc {
+        self.topology = topology;
+        self
+    }
+
+    pub fn cull(mut self, cull: Face) -> PipelineDesc {
+        self.cull = cull;
+        self
+    }
+
+    pub fn polygon_mode(mut self, polygon_mode: PolygonMode) -> PipelineDesc {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn blend(mut self, blend: BlendState) -> PipelineDesc {
+        self.blend = Some(blend);
+        self
+    }
+
+    /// Tests against the render pass's depth attachment, which is cleared to 1.0 every frame.
+    pub fn depth(mut self, depth: DepthTest) -> PipelineDesc {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Adds a vertex buffer binding, following `attribute` calls describe its contents.
+    pub fn vertex_buffer(mut self, stride: u32, rate: VertexInputRate) -> PipelineDesc {
+        self.vertex_buffers.push(VertexBufferDesc {
+            binding: self.vertex_buffers.len() as u32,
+            stride,
+            rate,
+        });
+        self
+    }
+
+    pub fn attribute(mut self, location: u32, format: Format, offset: u32) -> PipelineDesc {
+        let binding = self
+            .vertex_buffers
+            .len()
+            .checked_sub(1)
+            .expect("Vertex attributes need a vertex buffer first") as u32;
+        self.attributes.push(AttributeDesc {
+            location,
+            binding,
+            element: Element { format, offset },
+        });
+        self
+    }
+
+    /// Adds the next descriptor set, as `(binding, type, stages)` for each of its descriptors.
+    pub fn descriptor_set(
+        mut self,
+        bindings: &[(u32, DescriptorType, ShaderStageFlags)],
+    ) -> PipelineDesc {
+        self.descriptor_sets.push(bindings.to_vec());
+        self
+    }
+
+    /// Declares push constants of type `T` starting at byte 0 for the given stages.
+    pub fn push_constants<T: GpuPod>(mut self, stages: ShaderStageFlags) -> PipelineDesc {
+        let () = T::WORD_LAYOUT;
+        self.push_constants.push((stages, 0..size_of::<T>() as u32));
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Shader(ShaderError),
+    Interface(ReflectionError),
+    Creation(CreationError),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::Shader(error) => write!(f, "{}", error),
+            PipelineError::Interface(error) => {
+                write!(f, "pipeline doesn't match its shaders: {}", error)
+            }
+            PipelineError::Creation(error) => write!(f, "could not create pipeline: {}", error),
+        }
+    }
+}
+
+impl Error for PipelineError {}
+
+impl From<ShaderError> for PipelineError {
+    fn from(error: ShaderError) -> Self {
+        PipelineError::Shader(error)
+    }
+}
+
+impl From<ReflectionError> for PipelineError {
+    fn from(error: ReflectionError) -> Self {
+        PipelineError::Interface(error)
+    }
+}
+
+impl From<CreationError> for PipelineError {
+    fn from(error: CreationError) -> Self {
+        PipelineError::Creation(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineId(usize);
+
+pub struct Pipeline {
+    /// One per descriptor set of the description, for allocating sets to bind.
+    pub set_layouts: Vec<<back::Backend as gfx_hal::Backend>::DescriptorSetLayout>,
+    pub layout: <back::Backend as gfx_hal::Backend>::PipelineLayout,
+    pub pipeline: <back::Backend as gfx_hal::Backend>::GraphicsPipeline,
+}
+
+/// Owns every graphics pipeline, creating each distinct description once per render graph pass
+/// it's used in. The driver's pipeline cache is saved to disk on shutdown so the next start
+/// doesn't have to compile them again.
+pub struct Pipelines {
+    cache: <back::Backend as gfx_hal::Backend>::PipelineCache,
+    ids: PipelineIds,
+    pipelines: Vec<Pipeline>,
+}
+
+impl Pipelines {
+    pub fn new(
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) -> Pipelines {
+        // Drivers check the header of the data themselves and ignore it when it came from
+        // another GPU or driver version.
+        let data = fs::read(cache_path()).ok();
+        let cache = unsafe {
+            device
+                .create_pipeline_cache(data.as_deref())
+                .or_else(|_| device.create_pipeline_cache(None))
+                .expect("Out of memory")
+        };
+        gpu_objects.created(GpuObject::PipelineCache);
+
+        Self {
+            cache,
+            ids: PipelineIds::default(),
+            pipelines: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, id: PipelineId) -> &Pipeline {
+        &self.pipelines[id.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// Returns the pipeline drawing `desc` in `pass` of `render_graph`, creating it the first
+    /// time it's asked for. It also works in passes with the same attachments as `pass`.
+    pub fn get_or_create(
+        &mut self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        render_graph: &RenderGraph,
+        pass: PassId,
+        shader_library: &mut ShaderLibrary,
+        gpu_objects: &mut GpuObjectTracker,
+        desc: &PipelineDesc,
+    ) -> Result<PipelineId, PipelineError> {
+        if let Some(id) = self.ids.get(desc, pass) {
+            return Ok(id);
+        }
+
+        let spirv = compile(shader_library, desc)?;
+        let [vertex_spirv, fragment_spirv] = &spirv;
+
+        let descriptor_sets: Vec<Vec<DescriptorSetLayoutBinding>> = desc
+            .descriptor_sets
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .map(|&(binding, ty, stage_flags)| DescriptorSetLayoutBinding {
+                        binding,
+                        ty,
+                        count: 1,
+                        stage_flags,
+                        immutable_samplers: false,
+                    })
+                    .collect()
+            })
+            .collect();
+        let descriptor_set_refs: Vec<&[DescriptorSetLayoutBinding]> =
+            descriptor_sets.iter().map(Vec::as_slice).collect();
+
+        PipelineInterface {
+            push_constants: &desc.push_constants,
+            descriptor_sets: &descriptor_set_refs,
+            attributes: &desc.attributes,
+        }
+        .validate(&[
+            &ShaderReflection::reflect(vertex_spirv, ShaderStageFlags::VERTEX)?,
+            &ShaderReflection::reflect(fragment_spirv, ShaderStageFlags::FRAGMENT)?,
+        ])?;
+
+        let set_layouts: Vec<_> = descriptor_sets
+            .iter()
+            .map(|bindings| unsafe {
+                gpu_objects.created(GpuObject::DescriptorSetLayout);
+                device
+                    .create_descriptor_set_layout(bindings, &[])
+                    .expect("Out of memory")
+            })
+            .collect();
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(&set_layouts, &desc.push_constants)
+                .expect("Out of memory")
+        };
+        gpu_objects.created(GpuObject::PipelineLayout);
+
+        let pipeline =
+            unsafe { self.create_pipeline(device, render_graph, pass, &layout, desc, &spirv) };
+        let pipeline = match pipeline {
+            Ok(pipeline) => pipeline,
+            Err(error) => {
+                unsafe {
+                    device.destroy_pipeline_layout(layout);
+                    for set_layout in set_layouts {
+                        device.destroy_descriptor_set_layout(set_layout);
+                        gpu_objects.destroyed(GpuObject::DescriptorSetLayout);
+                    }
+                }
+                gpu_objects.destroyed(GpuObject::PipelineLayout);
+                return Err(error.into());
+            }
+        };
+        gpu_objects.created(GpuObject::GraphicsPipeline);
+
+        debug!(
+            "Created pipeline {} with {}",
+            desc.vertex_shader, desc.fragment_shader
+        );
+        let id = self.ids.insert(desc, pass);
+        debug_assert_eq!(id.0, self.pipelines.len());
+        self.pipelines.push(Pipeline {
+            set_layouts,
+            layout,
+            pipeline,
+        });
+        Ok(id)
+    }
+
+    unsafe fn create_pipeline(
+        &self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        render_graph: &RenderGraph,
+        pass: PassId,
+        layout: &<back::Backend as gfx_hal::Backend>::PipelineLayout,
+        desc: &PipelineDesc,
+        [vertex_spirv, fragment_spirv]: &[Vec<u32>; 2],
+    ) -> Result<<back::Backend as gfx_hal::Backend>::GraphicsPipeline, CreationError> {
+        let vertex_shader_module = device
+            .create_shader_module(vertex_spirv)
+            .map_err(CreationError::Shader)?;
+        let fragment_shader_module = match device.create_shader_module(fragment_spirv) {
+            Ok(module) => module,
+            Err(error) => {
+                device.destroy_shader_module(vertex_shader_module);
+                return Err(CreationError::Shader(error));
+            }
+        };
+
+        let primitive_assembler = PrimitiveAssemblerDesc::Vertex {
+            buffers: &desc.vertex_buffers,
+            attributes: &desc.attributes,
+            input_assembler: InputAssemblerDesc::new(desc.topology),
+            vertex: EntryPoint {
+                entry: "main",
+                module: &vertex_shader_module,
+                specialization: Specialization::default(),
+            },
+            tessellation: None,
+            geometry: None,
+        };
+        let mut pipeline_desc = GraphicsPipelineDesc::new(
+            primitive_assembler,
+            Rasterizer {
+                polygon_mode: desc.polygon_mode.into(),
+                cull_face: desc.cull,
+                ..Rasterizer::FILL
+            },
+            Some(EntryPoint {
+                entry: "main",
+                module: &fragment_shader_module,
+                specialization: Specialization::default(),
+            }),
+            layout,
+            Subpass {
+                index: 0,
+                main_pass: render_graph.render_pass(pass),
+            },
+        );
+        pipeline_desc.blender.targets.push(ColorBlendDesc {
+            mask: ColorMask::ALL,
+            blend: desc.blend,
+        });
+        pipeline_desc.depth_stencil.depth = desc.depth;
+        let samples = render_graph.samples(pass);
+        if samples > 1 {
+            pipeline_desc.multisampling = Some(Multisampling {
+                rasterization_samples: samples,
+                sample_shading: None,
+                sample_mask: !0,
+                alpha_coverage: false,
+                alpha_to_one: false,
+            });
+        }
+
+        let pipeline = device.create_graphics_pipeline(&pipeline_desc, Some(&self.cache));
+
+        device.destroy_shader_module(vertex_shader_module);
+        device.destroy_shader_module(fragment_shader_module);
+
+        pipeline
+    }
+
+    /// Creates every pipeline again for the same pass of a rebuilt render graph, whose passes
+    /// can have a different number of samples. Their IDs stay the same. Nothing changes unless
+    /// every pipeline could be created.
+    ///
+    /// # Safety
+    ///
+    /// None of the pipelines may still be in use by the GPU.
+    pub unsafe fn recreate(
+        &mut self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        render_graph: &RenderGraph,
+        shader_library: &mut ShaderLibrary,
+    ) -> Result<(), PipelineError> {
+        let mut created = Vec::with_capacity(self.pipelines.len());
+        for (desc, pass, id) in self.ids.iter() {
+            let pipeline = compile(shader_library, desc)
+                .map_err(PipelineError::from)
+                .and_then(|spirv| {
+                    let layout = &self.pipelines[id.0].layout;
+                    self.create_pipeline(device, render_graph, pass, layout, desc, &spirv)
+                        .map_err(PipelineError::from)
+                });
+            match pipeline {
+                Ok(pipeline) => created.push((id, pipeline)),
+                Err(error) => {
+                    for (_, pipeline) in created {
+                        device.destroy_graphics_pipeline(pipeline);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        for (id, pipeline) in created {
+            let old = std::mem::replace(&mut self.pipelines[id.0].pipeline, pipeline);
+            device.destroy_graphics_pipeline(old);
+        }
+        Ok(())
+    }
+
+    /// Saves the pipeline cache and destroys every pipeline.
+    ///
+    /// # Safety
+    ///
+    /// None of the pipelines may still be in use by the GPU.
+    pub unsafe fn destroy(
+        self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) {
+        match device.get_pipeline_cache_data(&self.cache) {
+            Ok(data) if !data.is_empty() => {
+                let path = cache_path();
+                let saved = path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&path, &data));
+                match saved {
+                    Ok(()) => info!("Saved {} bytes of pipeline cache", data.len()),
+                    Err(error) => warn!("Could not save pipeline cache: {}", error),
+                }
+            }
+            Ok(_) => (),
+            Err(error) => warn!("Could not read pipeline cache: {}", error),
+        }
+
+        for pipeline in self.pipelines {
+            device.destroy_graphics_pipeline(pipeline.pipeline);
+            gpu_objects.destroyed(GpuObject::GraphicsPipeline);
+            device.destroy_pipeline_layout(pipeline.layout);
+            gpu_objects.destroyed(GpuObject::PipelineLayout);
+            for set_layout in pipeline.set_layouts {
+                device.destroy_descriptor_set_layout(set_layout);
+                gpu_objects.destroyed(GpuObject::DescriptorSetLayout);
+            }
+        }
+        device.destroy_pipeline_cache(self.cache);
+        gpu_objects.destroyed(GpuObject::PipelineCache);
+    }
+}
+
+/// Gives every distinct description its own ID for each pass it's drawn in, numbered in the
+/// order they were first created.
+#[derive(Default)]
+struct PipelineIds {
+    ids: HashMap<PipelineDesc, Vec<(PassId, PipelineId)>>,
+    len: usize,
+}
+
+impl PipelineIds {
+    fn get(&self, desc: &PipelineDesc, pass: PassId) -> Option<PipelineId> {
+        self.ids
+            .get(desc)?
+            .iter()
+            .find(|(id_pass, _)| *id_pass == pass)
+            .map(|(_, id)| *id)
+    }
+
+    fn insert(&mut self, desc: &PipelineDesc, pass: PassId) -> PipelineId {
+        if let Some(id) = self.get(desc, pass) {
+            return id;
+        }
+        let id = PipelineId(self.len);
+        self.len += 1;
+        self.ids.entry(desc.clone()).or_default().push((pass, id));
+        id
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&PipelineDesc, PassId, PipelineId)> {
+        self.ids
+            .iter()
+            .flat_map(|(desc, ids)| ids.iter().map(move |&(pass, id)| (desc, pass, id)))
+    }
+}
+
+fn compile(
+    shader_library: &mut ShaderLibrary,
+    desc: &PipelineDesc,
+) -> Result<[Vec<u32>; 2], ShaderError> {
+    Ok([
+        shader_library
+            .compile(&desc.vertex_shader, &desc.defines)?
+            .to_vec(),
+        shader_library
+            .compile(&desc.fragment_shader, &desc.defines)?
+            .to_vec(),
+    ])
+}
+
+fn cache_path() -> PathBuf {
+    data_dir().join("pipeline_cache.bin")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::render_graph::{PassDesc, RenderGraphBuilder};
+    use gfx_hal::pso::Comparison;
+
+    fn text_desc() -> PipelineDesc {
+        PipelineDesc::new("text.vert", "text.frag")
+            .define("SDF", None)
+            .define("SAMPLES", Some("4"))
+            .blend(BlendState::ALPHA)
+            .depth(DepthTest {
+                fun: Comparison::LessEqual,
+                write: false,
+            })
+            .vertex_buffer(16, VertexInputRate::Vertex)
+            .attribute(0, Format::Rg32Sfloat, 0)
+            .attribute(1, Format::Rg32Sfloat, 8)
+    }
+
+    fn passes() -> (PassId, PassId) {
+        let mut graph = RenderGraphBuilder::new();
+        (
+            graph.add_pass(PassDesc::new("scene")),
+            graph.add_pass(PassDesc::new("present")),
+        )
+    }
+
+    #[test]
+    fn the_same_description_twice_gets_the_same_id() {
+        let (scene, _) = passes();
+        let mut ids = PipelineIds::default();
+        let first = ids.insert(&text_desc(), scene);
+
+        // Defines given in another order still describe the same pipeline.
+        let reordered = PipelineDesc::new("text.vert", "text.frag")
+            .define("SAMPLES", Some("4"))
+            .define("SDF", None)
+            .blend(BlendState::ALPHA)
+            .depth(DepthTest {
+                fun: Comparison::LessEqual,
+                write: false,
+            })
+            .vertex_buffer(16, VertexInputRate::Vertex)
+            .attribute(0, Format::Rg32Sfloat, 0)
+            .attribute(1, Format::Rg32Sfloat, 8);
+        assert_eq!(ids.get(&reordered, scene), Some(first));
+        assert_eq!(ids.insert(&reordered, scene), first);
+    }
+
+    #[test]
+    fn different_descriptions_get_new_ids() {
+        let (scene, _) = passes();
+        let mut ids = PipelineIds::default();
+        let text = ids.insert(&text_desc(), scene);
+        let lines = text_desc().polygon_mode(PolygonMode::Line);
+
+        assert_eq!(ids.get(&lines, scene), None);
+        let lines = ids.insert(&lines, scene);
+        assert_ne!(lines, text);
+        assert_eq!(ids.iter().count(), 2);
+    }
+
+    #[test]
+    fn each_pass_gets_its_own_pipeline() {
+        let (scene, present) = passes();
+        let mut ids = PipelineIds::default();
+        let in_scene = ids.insert(&text_desc(), scene);
+
+        assert_eq!(ids.get(&text_desc(), present), None);
+        let in_present = ids.insert(&text_desc(), present);
+        assert_ne!(in_present, in_scene);
+        assert_eq!(ids.get(&text_desc(), scene), Some(in_scene));
+        assert_eq!(ids.iter().count(), 2);
+    }
+}
@@ -1,7 +1,13 @@
+use super::gpu_data::GpuPod;
+use bytemuck::{Pod, Zeroable};
+use common::math::{Vec2, Vec4};
+
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
 pub struct PushConstants {
-    pub color: [f32; 4],
-    pub pos: [f32; 2],
-    pub scale: [f32; 2],
+    pub color: Vec4,
+    pub pos: Vec2,
+    pub scale: Vec2,
 }
+
+impl GpuPod for PushConstants {}
@@ -0,0 +1,1040 @@
+//! Just enough SPIR-V reflection to check the Rust side of a pipeline against its shaders, so a
+//! stale push constant struct or a missing vertex attribute fails loudly at pipeline creation
+//! instead of rendering garbage.
+
+use gfx_hal::{
+    format::{ChannelType, Format},
+    pso::{
+        AttributeDesc, BufferDescriptorFormat, BufferDescriptorType, DescriptorSetLayoutBinding,
+        DescriptorType, ImageDescriptorType, ShaderStageFlags,
+    },
+};
+use std::{collections::HashMap, error::Error, fmt, ops::Range};
+
+const MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+// Opcodes.
+const OP_NAME: u32 = 5;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// Decorations.
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// Storage classes.
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+// Image dimensions.
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericType {
+    Float,
+    Sint,
+    Uint,
+}
+
+impl NumericType {
+    /// What a shader reads when fetching an attribute of the given format.
+    fn of_format(format: Format) -> NumericType {
+        match format.base_format().1 {
+            ChannelType::Uint => NumericType::Uint,
+            ChannelType::Sint => NumericType::Sint,
+            _ => NumericType::Float,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorKind {
+    Sampler,
+    SampledImage,
+    CombinedImageSampler,
+    StorageImage,
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    UniformBuffer,
+    StorageBuffer,
+    InputAttachment,
+}
+
+impl DescriptorKind {
+    fn matches(self, ty: DescriptorType) -> bool {
+        use BufferDescriptorFormat::{Structured, Texel};
+        use BufferDescriptorType::{Storage, Uniform};
+
+        match (self, ty) {
+            (DescriptorKind::Sampler, DescriptorType::Sampler) => true,
+            (DescriptorKind::InputAttachment, DescriptorType::InputAttachment) => true,
+            (DescriptorKind::SampledImage, DescriptorType::Image { ty }) => {
+                ty == ImageDescriptorType::Sampled {
+                    with_sampler: false,
+                }
+            }
+            (DescriptorKind::CombinedImageSampler, DescriptorType::Image { ty }) => {
+                ty == ImageDescriptorType::Sampled { with_sampler: true }
+            }
+            (DescriptorKind::StorageImage, DescriptorType::Image { ty }) => {
+                matches!(ty, ImageDescriptorType::Storage { .. })
+            }
+            (DescriptorKind::UniformTexelBuffer, DescriptorType::Buffer { ty, format }) => {
+                ty == Uniform && format == Texel
+            }
+            (DescriptorKind::StorageTexelBuffer, DescriptorType::Buffer { ty, format }) => {
+                matches!(ty, Storage { .. }) && format == Texel
+            }
+            (DescriptorKind::UniformBuffer, DescriptorType::Buffer { ty, format }) => {
+                ty == Uniform && matches!(format, Structured { .. })
+            }
+            (DescriptorKind::StorageBuffer, DescriptorType::Buffer { ty, format }) => {
+                matches!(ty, Storage { .. }) && matches!(format, Structured { .. })
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectedDescriptor {
+    pub name: Option<String>,
+    pub set: u32,
+    pub binding: u32,
+    pub kind: DescriptorKind,
+    /// Array length, 1 for plain descriptors and 0 for unsized arrays.
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexInput {
+    pub name: Option<String>,
+    pub location: u32,
+    pub numeric_type: NumericType,
+}
+
+/// The interface one shader stage expects from the pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderReflection {
+    pub stage: ShaderStageFlags,
+    /// The bytes of the push constant block the shader declares.
+    pub push_constants: Option<Range<u32>>,
+    pub descriptors: Vec<ReflectedDescriptor>,
+    /// Only filled in for vertex shaders, other stages read from the previous stage instead.
+    pub vertex_inputs: Vec<VertexInput>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ReflectionError {
+    InvalidSpirv(&'static str),
+    PushConstantsNotDeclared {
+        stage: ShaderStageFlags,
+        shader: Range<u32>,
+    },
+    PushConstantsMismatch {
+        stages: ShaderStageFlags,
+        shader: Range<u32>,
+        declared: Range<u32>,
+    },
+    DescriptorNotDeclared {
+        stage: ShaderStageFlags,
+        descriptor: ReflectedDescriptor,
+    },
+    DescriptorMismatch {
+        stage: ShaderStageFlags,
+        descriptor: ReflectedDescriptor,
+        declared: DescriptorSetLayoutBinding,
+    },
+    VertexInputNotDeclared(VertexInput),
+    VertexInputMismatch {
+        input: VertexInput,
+        declared: Format,
+    },
+}
+
+impl fmt::Display for ReflectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |name: &Option<String>| name.clone().unwrap_or_else(|| "unnamed".to_owned());
+
+        match self {
+            ReflectionError::InvalidSpirv(reason) => write!(f, "invalid SPIR-V: {}", reason),
+            ReflectionError::PushConstantsNotDeclared { stage, shader } => write!(
+                f,
+                "the {:?} shader uses push constant bytes {:?} but the pipeline layout declares \
+                 no push constants for it",
+                stage, shader
+            ),
+            ReflectionError::PushConstantsMismatch {
+                stages,
+                shader,
+                declared,
+            } => write!(
+                f,
+                "the {:?} push constant range is declared as bytes {:?} but the shaders use bytes \
+                 {:?}, does the Rust struct still match the push_constant block?",
+                stages, declared, shader
+            ),
+            ReflectionError::DescriptorNotDeclared { stage, descriptor } => write!(
+                f,
+                "the {:?} shader uses {:?} `{}` at set {} binding {} but the pipeline layout \
+                 doesn't declare it for that stage",
+                stage,
+                descriptor.kind,
+                name(&descriptor.name),
+                descriptor.set,
+                descriptor.binding
+            ),
+            ReflectionError::DescriptorMismatch {
+                stage,
+                descriptor,
+                declared,
+            } => write!(
+                f,
+                "the {:?} shader uses {} {:?} `{}` at set {} binding {} but the pipeline layout \
+                 declares {} {:?}",
+                stage,
+                descriptor.count,
+                descriptor.kind,
+                name(&descriptor.name),
+                descriptor.set,
+                descriptor.binding,
+                declared.count,
+                declared.ty
+            ),
+            ReflectionError::VertexInputNotDeclared(input) => write!(
+                f,
+                "the vertex shader reads `{}` from location {} but no vertex attribute is bound \
+                 there",
+                name(&input.name),
+                input.location
+            ),
+            ReflectionError::VertexInputMismatch { input, declared } => write!(
+                f,
+                "the vertex shader reads `{}` at location {} as {:?} but the attribute format is \
+                 {:?}",
+                name(&input.name),
+                input.location,
+                input.numeric_type,
+                declared
+            ),
+        }
+    }
+}
+
+impl Error for ReflectionError {}
+
+/// Everything the Rust side declares about a pipeline's interface.
+pub struct PipelineInterface<'a> {
+    pub push_constants: &'a [(ShaderStageFlags, Range<u32>)],
+    /// Indexed by descriptor set.
+    pub descriptor_sets: &'a [&'a [DescriptorSetLayoutBinding]],
+    pub attributes: &'a [AttributeDesc],
+}
+
+impl PipelineInterface<'_> {
+    /// Checks every stage of a pipeline against the declarations.
+    pub fn validate(&self, shaders: &[&ShaderReflection]) -> Result<(), ReflectionError> {
+        for shader in shaders {
+            self.validate_push_constants(shader)?;
+            self.validate_descriptors(shader)?;
+            self.validate_vertex_inputs(shader)?;
+        }
+
+        // A declared range should be exactly what its stages use together, anything else means
+        // the Rust struct and the shader block went out of sync.
+        for (stages, declared) in self.push_constants {
+            let used = shaders
+                .iter()
+                .filter(|shader| stages.contains(shader.stage))
+                .filter_map(|shader| shader.push_constants.clone())
+                .fold(None, |used: Option<Range<u32>>, range| match used {
+                    Some(used) => Some(used.start.min(range.start)..used.end.max(range.end)),
+                    None => Some(range),
+                });
+
+            if let Some(used) = used {
+                if used.end != declared.end {
+                    return Err(ReflectionError::PushConstantsMismatch {
+                        stages: *stages,
+                        shader: used,
+                        declared: declared.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_push_constants(&self, shader: &ShaderReflection) -> Result<(), ReflectionError> {
+        let used = match &shader.push_constants {
+            Some(used) => used,
+            None => return Ok(()),
+        };
+
+        let declared = self
+            .push_constants
+            .iter()
+            .find(|(stages, _)| stages.contains(shader.stage))
+            .map(|(_, range)| range)
+            .ok_or_else(|| ReflectionError::PushConstantsNotDeclared {
+                stage: shader.stage,
+                shader: used.clone(),
+            })?;
+
+        if used.start < declared.start || used.end > declared.end {
+            return Err(ReflectionError::PushConstantsMismatch {
+                stages: shader.stage,
+                shader: used.clone(),
+                declared: declared.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_descriptors(&self, shader: &ShaderReflection) -> Result<(), ReflectionError> {
+        for descriptor in &shader.descriptors {
+            let declared = self
+                .descriptor_sets
+                .get(descriptor.set as usize)
+                .and_then(|set| {
+                    set.iter()
+                        .find(|declared| declared.binding == descriptor.binding)
+                })
+                .filter(|declared| declared.stage_flags.contains(shader.stage))
+                .ok_or_else(|| ReflectionError::DescriptorNotDeclared {
+                    stage: shader.stage,
+                    descriptor: descriptor.clone(),
+                })?;
+
+            let count_matches =
+                descriptor.count == 0 || declared.count >= descriptor.count as usize;
+            if !descriptor.kind.matches(declared.ty) || !count_matches {
+                return Err(ReflectionError::DescriptorMismatch {
+                    stage: shader.stage,
+                    descriptor: descriptor.clone(),
+                    declared: declared.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_vertex_inputs(&self, shader: &ShaderReflection) -> Result<(), ReflectionError> {
+        for input in &shader.vertex_inputs {
+            let declared = self
+                .attributes
+                .iter()
+                .find(|attribute| attribute.location == input.location)
+                .ok_or_else(|| ReflectionError::VertexInputNotDeclared(input.clone()))?
+                .element
+                .format;
+
+            if NumericType::of_format(declared) != input.numeric_type {
+                return Err(ReflectionError::VertexInputMismatch {
+                    input: input.clone(),
+                    declared,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Scalar(NumericType, u32),
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array(u32, Option<u32>),
+    Struct(Vec<u32>),
+    Pointer(u32),
+}
+
+#[derive(Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<(u32, u32), u32>,
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+    /// Result id, pointer type and storage class.
+    variables: Vec<(u32, u32, u32)>,
+}
+
+impl ShaderReflection {
+    pub fn reflect(spirv: &[u32], stage: ShaderStageFlags) -> Result<Self, ReflectionError> {
+        let module = Module::parse(spirv)?;
+
+        let mut reflection = Self {
+            stage,
+            push_constants: None,
+            descriptors: Vec::new(),
+            vertex_inputs: Vec::new(),
+        };
+
+        for &(id, pointer, storage_class) in &module.variables {
+            let ty = match module.types.get(&pointer) {
+                Some(Type::Pointer(ty)) => *ty,
+                _ => return Err(ReflectionError::InvalidSpirv("variable is not a pointer")),
+            };
+
+            match storage_class {
+                STORAGE_PUSH_CONSTANT => {
+                    reflection.push_constants = Some(module.push_constant_range(ty)?);
+                }
+                STORAGE_INPUT if stage == ShaderStageFlags::VERTEX => {
+                    if module.decorations.contains_key(&(id, DECORATION_BUILT_IN)) {
+                        continue;
+                    }
+                    let location = module
+                        .decorations
+                        .get(&(id, DECORATION_LOCATION))
+                        .copied()
+                        .ok_or(ReflectionError::InvalidSpirv(
+                            "vertex input without location",
+                        ))?;
+
+                    reflection.vertex_inputs.push(VertexInput {
+                        name: module.names.get(&id).cloned(),
+                        location,
+                        numeric_type: module.numeric_type(ty)?,
+                    });
+                }
+                STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                    let (kind, count) = module.descriptor_kind(ty, storage_class)?;
+                    let decoration = |decoration| {
+                        module
+                            .decorations
+                            .get(&(id, decoration))
+                            .copied()
+                            .ok_or(ReflectionError::InvalidSpirv("descriptor without binding"))
+                    };
+
+                    reflection.descriptors.push(ReflectedDescriptor {
+                        name: module.names.get(&id).cloned(),
+                        set: decoration(DECORATION_DESCRIPTOR_SET)?,
+                        binding: decoration(DECORATION_BINDING)?,
+                        kind,
+                        count,
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        reflection
+            .descriptors
+            .sort_by_key(|descriptor| (descriptor.set, descriptor.binding));
+        reflection.vertex_inputs.sort_by_key(|input| input.location);
+        Ok(reflection)
+    }
+}
+
+impl Module {
+    fn parse(spirv: &[u32]) -> Result<Module, ReflectionError> {
+        if spirv.len() < HEADER_WORDS || spirv[0] != MAGIC {
+            return Err(ReflectionError::InvalidSpirv("missing SPIR-V header"));
+        }
+
+        let mut module = Module::default();
+        let mut words = &spirv[HEADER_WORDS..];
+        while let Some(&first) = words.first() {
+            let (count, opcode) = ((first >> 16) as usize, first & 0xffff);
+            if count == 0 || count > words.len() {
+                return Err(ReflectionError::InvalidSpirv("truncated instruction"));
+            }
+            module.instruction(opcode, &words[1..count])?;
+            words = &words[count..];
+        }
+
+        Ok(module)
+    }
+
+    fn instruction(&mut self, opcode: u32, operands: &[u32]) -> Result<(), ReflectionError> {
+        let operand = |index: usize| {
+            operands
+                .get(index)
+                .copied()
+                .ok_or(ReflectionError::InvalidSpirv("missing operand"))
+        };
+
+        match opcode {
+            OP_NAME => {
+                let name = literal_string(operands.get(1..).unwrap_or(&[]));
+                if !name.is_empty() {
+                    self.names.insert(operand(0)?, name);
+                }
+            }
+            OP_TYPE_INT => {
+                let numeric_type = if operand(2)? == 0 {
+                    NumericType::Uint
+                } else {
+                    NumericType::Sint
+                };
+                self.types
+                    .insert(operand(0)?, Type::Scalar(numeric_type, operand(1)?));
+            }
+            OP_TYPE_FLOAT => {
+                self.types
+                    .insert(operand(0)?, Type::Scalar(NumericType::Float, operand(1)?));
+            }
+            OP_TYPE_VECTOR => {
+                self.types
+                    .insert(operand(0)?, Type::Vector(operand(1)?, operand(2)?));
+            }
+            OP_TYPE_MATRIX => {
+                self.types
+                    .insert(operand(0)?, Type::Matrix(operand(1)?, operand(2)?));
+            }
+            OP_TYPE_IMAGE => {
+                let image = Type::Image {
+                    dim: operand(2)?,
+                    sampled: operand(6)?,
+                };
+                self.types.insert(operand(0)?, image);
+            }
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operand(0)?, Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0)?, Type::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                let length = self.constants.get(&operand(2)?).copied().ok_or(
+                    ReflectionError::InvalidSpirv("array length is not a constant"),
+                )?;
+                self.types
+                    .insert(operand(0)?, Type::Array(operand(1)?, Some(length)));
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                self.types
+                    .insert(operand(0)?, Type::Array(operand(1)?, None));
+            }
+            OP_TYPE_STRUCT => {
+                self.types
+                    .insert(operand(0)?, Type::Struct(operands[1..].to_vec()));
+            }
+            OP_TYPE_POINTER => {
+                self.types.insert(operand(0)?, Type::Pointer(operand(2)?));
+            }
+            OP_CONSTANT => {
+                // Only 32-bit integer constants matter here, as array lengths.
+                self.constants.insert(operand(1)?, operand(2)?);
+            }
+            OP_VARIABLE => {
+                self.variables.push((operand(1)?, operand(0)?, operand(2)?));
+            }
+            OP_DECORATE => {
+                let value = operands.get(2).copied().unwrap_or(0);
+                self.decorations.insert((operand(0)?, operand(1)?), value);
+            }
+            OP_MEMBER_DECORATE => {
+                let value = operands.get(3).copied().unwrap_or(0);
+                self.member_decorations
+                    .insert((operand(0)?, operand(1)?, operand(2)?), value);
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, ty: u32) -> Result<&Type, ReflectionError> {
+        self.types
+            .get(&ty)
+            .ok_or(ReflectionError::InvalidSpirv("unknown type"))
+    }
+
+    fn push_constant_range(&self, ty: u32) -> Result<Range<u32>, ReflectionError> {
+        let members = match self.get(ty)? {
+            Type::Struct(members) => members,
+            _ => {
+                return Err(ReflectionError::InvalidSpirv(
+                    "push constants are not a block",
+                ))
+            }
+        };
+
+        let mut range: Option<Range<u32>> = None;
+        for (index, &member) in members.iter().enumerate() {
+            let offset = self.member_offset(ty, index as u32)?;
+            let end = offset + self.member_size(ty, index as u32, member)?;
+            range = Some(match range {
+                Some(range) => range.start.min(offset)..range.end.max(end),
+                None => offset..end,
+            });
+        }
+
+        Ok(range.unwrap_or(0..0))
+    }
+
+    fn member_offset(&self, ty: u32, member: u32) -> Result<u32, ReflectionError> {
+        self.member_decorations
+            .get(&(ty, member, DECORATION_OFFSET))
+            .copied()
+            .ok_or(ReflectionError::InvalidSpirv("block member without offset"))
+    }
+
+    fn member_size(&self, parent: u32, index: u32, ty: u32) -> Result<u32, ReflectionError> {
+        match self.get(ty)? {
+            // Matrices inside blocks are laid out with an explicit stride per column.
+            Type::Matrix(column, count) => {
+                match self
+                    .member_decorations
+                    .get(&(parent, index, DECORATION_MATRIX_STRIDE))
+                {
+                    Some(stride) => Ok(stride * count),
+                    None => Ok(self.size(*column)? * count),
+                }
+            }
+            _ => self.size(ty),
+        }
+    }
+
+    fn size(&self, ty: u32) -> Result<u32, ReflectionError> {
+        match self.get(ty)? {
+            Type::Scalar(_, width) => Ok(width / 8),
+            Type::Vector(component, count) => Ok(self.size(*component)? * count),
+            Type::Matrix(column, count) => Ok(self.size(*column)? * count),
+            Type::Array(element, Some(length)) => {
+                match self.decorations.get(&(ty, DECORATION_ARRAY_STRIDE)) {
+                    Some(stride) => Ok(stride * length),
+                    None => Ok(self.size(*element)? * length),
+                }
+            }
+            Type::Struct(members) => {
+                let mut size = 0;
+                for (index, &member) in members.iter().enumerate() {
+                    let end = self.member_offset(ty, index as u32)?
+                        + self.member_size(ty, index as u32, member)?;
+                    size = size.max(end);
+                }
+                Ok(size)
+            }
+            _ => Err(ReflectionError::InvalidSpirv("type has no size")),
+        }
+    }
+
+    fn numeric_type(&self, ty: u32) -> Result<NumericType, ReflectionError> {
+        match self.get(ty)? {
+            Type::Scalar(numeric_type, _) => Ok(*numeric_type),
+            Type::Vector(component, _) | Type::Matrix(component, _) => {
+                self.numeric_type(*component)
+            }
+            Type::Array(element, _) => self.numeric_type(*element),
+            _ => Err(ReflectionError::InvalidSpirv("input is not numeric")),
+        }
+    }
+
+    fn descriptor_kind(
+        &self,
+        ty: u32,
+        storage_class: u32,
+    ) -> Result<(DescriptorKind, u32), ReflectionError> {
+        let (ty, count) = match self.get(ty)? {
+            Type::Array(element, length) => (*element, length.unwrap_or(0)),
+            _ => (ty, 1),
+        };
+
+        let kind = match (self.get(ty)?, storage_class) {
+            (Type::Struct(_), STORAGE_STORAGE_BUFFER) => DescriptorKind::StorageBuffer,
+            (Type::Struct(_), _)
+                if self
+                    .decorations
+                    .contains_key(&(ty, DECORATION_BUFFER_BLOCK)) =>
+            {
+                DescriptorKind::StorageBuffer
+            }
+            (Type::Struct(_), _) => DescriptorKind::UniformBuffer,
+            (Type::Sampler, _) => DescriptorKind::Sampler,
+            (Type::SampledImage, _) => DescriptorKind::CombinedImageSampler,
+            (Type::Image { dim, .. }, _) if *dim == DIM_SUBPASS_DATA => {
+                DescriptorKind::InputAttachment
+            }
+            (Type::Image { dim, sampled }, _) if *dim == DIM_BUFFER => {
+                if *sampled == 2 {
+                    DescriptorKind::StorageTexelBuffer
+                } else {
+                    DescriptorKind::UniformTexelBuffer
+                }
+            }
+            (Type::Image { sampled, .. }, _) => {
+                if *sampled == 2 {
+                    DescriptorKind::StorageImage
+                } else {
+                    DescriptorKind::SampledImage
+                }
+            }
+            _ => return Err(ReflectionError::InvalidSpirv("unsupported descriptor type")),
+        };
+
+        Ok((kind, count))
+    }
+}
+
+/// Decodes a nul terminated UTF-8 string packed little endian into words.
+fn literal_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfx_hal::pso::Element;
+    use shaderc::{Compiler, ShaderKind};
+
+    const VERTEX: &str = r#"
+        #version 450
+
+        layout(push_constant) uniform Constants {
+            mat4 view_projection;
+            vec4 tint;
+            vec2 offset;
+        } constants;
+
+        layout(location = 0) in vec3 position;
+        layout(location = 2) in uvec2 id;
+        layout(location = 1) in ivec4 bones;
+
+        layout(location = 0) out vec4 color;
+
+        void main() {
+            gl_Position = constants.view_projection * vec4(position, 1.0);
+            color = constants.tint + vec4(vec2(id), vec2(bones.xy));
+        }
+    "#;
+
+    const FRAGMENT: &str = r#"
+        #version 450
+
+        layout(set = 0, binding = 0) uniform Globals {
+            mat4 view;
+            float time;
+        } globals;
+        layout(set = 0, binding = 1) uniform texture2D atlas;
+        layout(set = 1, binding = 0) uniform sampler atlas_sampler;
+        layout(set = 1, binding = 2) readonly buffer Lights {
+            vec4 lights[];
+        };
+
+        layout(push_constant) uniform Constants {
+            mat4 view_projection;
+            vec4 tint;
+        } constants;
+
+        layout(location = 0) in vec4 color;
+
+        layout(location = 0) out vec4 target;
+
+        void main() {
+            vec4 texel = texture(sampler2D(atlas, atlas_sampler), color.xy);
+            target = texel * constants.tint * globals.time + lights[0];
+        }
+    "#;
+
+    fn reflect(source: &str, stage: ShaderStageFlags) -> ShaderReflection {
+        let kind = match stage {
+            ShaderStageFlags::VERTEX => ShaderKind::Vertex,
+            _ => ShaderKind::Fragment,
+        };
+        let spirv = Compiler::new()
+            .expect("Failed to create shader compiler")
+            .compile_into_spirv(source, kind, "test.glsl", "main", None)
+            .expect("Test shader doesn't compile");
+
+        ShaderReflection::reflect(spirv.as_binary(), stage).expect("Failed to reflect test shader")
+    }
+
+    fn binding(
+        binding: u32,
+        ty: DescriptorType,
+        stage_flags: ShaderStageFlags,
+    ) -> DescriptorSetLayoutBinding {
+        DescriptorSetLayoutBinding {
+            binding,
+            ty,
+            count: 1,
+            stage_flags,
+            immutable_samplers: false,
+        }
+    }
+
+    fn attribute(location: u32, format: Format) -> AttributeDesc {
+        AttributeDesc {
+            location,
+            binding: 0,
+            element: Element { format, offset: 0 },
+        }
+    }
+
+    fn uniform_buffer() -> DescriptorType {
+        DescriptorType::Buffer {
+            ty: BufferDescriptorType::Uniform,
+            format: BufferDescriptorFormat::Structured {
+                dynamic_offset: false,
+            },
+        }
+    }
+
+    fn storage_buffer() -> DescriptorType {
+        DescriptorType::Buffer {
+            ty: BufferDescriptorType::Storage { read_only: true },
+            format: BufferDescriptorFormat::Structured {
+                dynamic_offset: false,
+            },
+        }
+    }
+
+    fn sampled_image() -> DescriptorType {
+        DescriptorType::Image {
+            ty: ImageDescriptorType::Sampled {
+                with_sampler: false,
+            },
+        }
+    }
+
+    #[test]
+    fn vertex_inputs_skip_builtins_and_outputs() {
+        let vertex = reflect(VERTEX, ShaderStageFlags::VERTEX);
+
+        let inputs: Vec<_> = vertex
+            .vertex_inputs
+            .iter()
+            .map(|input| (input.name.as_deref(), input.location, input.numeric_type))
+            .collect();
+        assert_eq!(
+            inputs,
+            vec![
+                (Some("position"), 0, NumericType::Float),
+                (Some("bones"), 1, NumericType::Sint),
+                (Some("id"), 2, NumericType::Uint),
+            ]
+        );
+        assert!(vertex.descriptors.is_empty());
+
+        // Fragment inputs come from the vertex shader, not from vertex buffers.
+        assert!(reflect(FRAGMENT, ShaderStageFlags::FRAGMENT)
+            .vertex_inputs
+            .is_empty());
+    }
+
+    #[test]
+    fn push_constants_cover_the_whole_block() {
+        let vertex = reflect(VERTEX, ShaderStageFlags::VERTEX);
+        assert_eq!(vertex.push_constants, Some(0..88));
+
+        let fragment = reflect(FRAGMENT, ShaderStageFlags::FRAGMENT);
+        assert_eq!(fragment.push_constants, Some(0..80));
+    }
+
+    #[test]
+    fn descriptors_are_sorted_by_set_and_binding() {
+        let fragment = reflect(FRAGMENT, ShaderStageFlags::FRAGMENT);
+
+        let descriptors: Vec<_> = fragment
+            .descriptors
+            .iter()
+            .map(|descriptor| {
+                (
+                    descriptor.set,
+                    descriptor.binding,
+                    descriptor.kind,
+                    descriptor.count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            descriptors,
+            vec![
+                (0, 0, DescriptorKind::UniformBuffer, 1),
+                (0, 1, DescriptorKind::SampledImage, 1),
+                (1, 0, DescriptorKind::Sampler, 1),
+                (1, 2, DescriptorKind::StorageBuffer, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn garbage_is_not_spirv() {
+        assert!(matches!(
+            ShaderReflection::reflect(&[1, 2, 3, 4, 5, 6], ShaderStageFlags::VERTEX),
+            Err(ReflectionError::InvalidSpirv(_))
+        ));
+
+        // An instruction claiming more words than are left.
+        let mut truncated = Compiler::new()
+            .unwrap()
+            .compile_into_spirv(VERTEX, ShaderKind::Vertex, "test.glsl", "main", None)
+            .unwrap()
+            .as_binary()
+            .to_vec();
+        truncated.truncate(HEADER_WORDS);
+        truncated.push(0x0010_0000);
+        assert!(matches!(
+            ShaderReflection::reflect(&truncated, ShaderStageFlags::VERTEX),
+            Err(ReflectionError::InvalidSpirv("truncated instruction"))
+        ));
+    }
+
+    struct Declarations {
+        push_constants: Vec<(ShaderStageFlags, Range<u32>)>,
+        descriptor_sets: Vec<Vec<DescriptorSetLayoutBinding>>,
+        attributes: Vec<AttributeDesc>,
+    }
+
+    impl Declarations {
+        /// What the Rust side of a pipeline using the test shaders would declare.
+        fn matching() -> Declarations {
+            Declarations {
+                push_constants: vec![(
+                    ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                    0..88,
+                )],
+                descriptor_sets: vec![
+                    vec![
+                        binding(0, uniform_buffer(), ShaderStageFlags::FRAGMENT),
+                        binding(1, sampled_image(), ShaderStageFlags::FRAGMENT),
+                    ],
+                    vec![
+                        binding(0, DescriptorType::Sampler, ShaderStageFlags::FRAGMENT),
+                        binding(2, storage_buffer(), ShaderStageFlags::FRAGMENT),
+                    ],
+                ],
+                attributes: vec![
+                    attribute(0, Format::Rgb32Sfloat),
+                    attribute(1, Format::Rgba8Sint),
+                    attribute(2, Format::Rg32Uint),
+                ],
+            }
+        }
+
+        fn validate(&self) -> Result<(), ReflectionError> {
+            let descriptor_sets: Vec<_> = self.descriptor_sets.iter().map(Vec::as_slice).collect();
+            PipelineInterface {
+                push_constants: &self.push_constants,
+                descriptor_sets: &descriptor_sets,
+                attributes: &self.attributes,
+            }
+            .validate(&[
+                &reflect(VERTEX, ShaderStageFlags::VERTEX),
+                &reflect(FRAGMENT, ShaderStageFlags::FRAGMENT),
+            ])
+        }
+    }
+
+    #[test]
+    fn matching_declarations_validate() {
+        Declarations::matching().validate().unwrap();
+
+        // Normalized integer attributes are read as floats.
+        let mut declarations = Declarations::matching();
+        declarations.attributes[0] = attribute(0, Format::Rgba8Unorm);
+        declarations.validate().unwrap();
+    }
+
+    #[test]
+    fn push_constant_mismatches_are_rejected() {
+        let mut declarations = Declarations::matching();
+        declarations.push_constants[0].1 = 0..80;
+        assert!(matches!(
+            declarations.validate(),
+            Err(ReflectionError::PushConstantsMismatch { shader, .. }) if shader == (0..88)
+        ));
+
+        // A range longer than anything the shaders use means the Rust struct grew.
+        declarations.push_constants[0].1 = 0..96;
+        assert!(matches!(
+            declarations.validate(),
+            Err(ReflectionError::PushConstantsMismatch { shader, declared, .. })
+                if shader == (0..88) && declared == (0..96)
+        ));
+
+        declarations.push_constants[0].0 = ShaderStageFlags::VERTEX;
+        declarations.push_constants[0].1 = 0..88;
+        assert!(matches!(
+            declarations.validate(),
+            Err(ReflectionError::PushConstantsNotDeclared {
+                stage: ShaderStageFlags::FRAGMENT,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn descriptor_mismatches_are_rejected() {
+        let mut declarations = Declarations::matching();
+        declarations.descriptor_sets[1].remove(1);
+        assert!(matches!(
+            declarations.validate(),
+            Err(ReflectionError::DescriptorNotDeclared { descriptor, .. })
+                if descriptor.set == 1 && descriptor.binding == 2
+        ));
+
+        let mut declarations = Declarations::matching();
+        declarations.descriptor_sets[0][0].ty = storage_buffer();
+        assert!(matches!(
+            declarations.validate(),
+            Err(ReflectionError::DescriptorMismatch { descriptor, .. })
+                if descriptor.kind == DescriptorKind::UniformBuffer
+        ));
+
+        let mut declarations = Declarations::matching();
+        declarations.descriptor_sets[0][1].stage_flags = ShaderStageFlags::VERTEX;
+        assert!(matches!(
+            declarations.validate(),
+            Err(ReflectionError::DescriptorNotDeclared { descriptor, .. })
+                if descriptor.kind == DescriptorKind::SampledImage
+        ));
+    }
+
+    #[test]
+    fn vertex_input_mismatches_are_rejected() {
+        let mut declarations = Declarations::matching();
+        declarations.attributes.remove(1);
+        assert!(matches!(
+            declarations.validate(),
+            Err(ReflectionError::VertexInputNotDeclared(input)) if input.location == 1
+        ));
+
+        let mut declarations = Declarations::matching();
+        declarations.attributes[2] = attribute(2, Format::Rg32Sfloat);
+        assert!(matches!(
+            declarations.validate(),
+            Err(ReflectionError::VertexInputMismatch { input, declared: Format::Rg32Sfloat })
+                if input.numeric_type == NumericType::Uint
+        ));
+    }
+}
@@ -0,0 +1,1395 @@
+//! Describes a frame as passes drawing into images, instead of render passes and framebuffers
+//! wired up by hand. Passes name the images they draw into and read from, the graph orders them,
+//! creates a render pass for each, allocates the images only used within the frame, letting ones
+//! alike that are never needed at the same time share an image, and puts barriers between passes
+//! that touch the same image.
+
+use super::{
+    back,
+    gpu_objects::{GpuObject, GpuObjectTracker},
+    render_targets::RenderTarget,
+};
+use gfx_hal::{
+    adapter::MemoryType,
+    command::{ClearColor, ClearDepthStencil, ClearValue, CommandBuffer, SubpassContents},
+    device::Device,
+    format::Format,
+    image::{Access, Extent, Layout, NumSamples, SubresourceRange, Usage},
+    memory::{Barrier, Dependencies},
+    pass::{Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, SubpassDesc},
+    pso::{PipelineStage, Rect, Viewport},
+    window::Extent2D,
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageSize {
+    /// Follows the swapchain through every resize.
+    Swapchain,
+    Fixed(Extent2D),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDesc {
+    pub format: Format,
+    pub samples: NumSamples,
+    pub size: ImageSize,
+}
+
+impl ImageDesc {
+    pub fn new(format: Format, samples: NumSamples, size: ImageSize) -> ImageDesc {
+        Self {
+            format,
+            samples,
+            size,
+        }
+    }
+}
+
+/// What a pass does with an attachment's contents before drawing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadOp {
+    Clear,
+    /// Keeps what an earlier pass drew.
+    Load,
+    /// Every pixel gets drawn over anyway.
+    DontCare,
+}
+
+impl From<LoadOp> for AttachmentLoadOp {
+    fn from(load: LoadOp) -> AttachmentLoadOp {
+        match load {
+            LoadOp::Clear => AttachmentLoadOp::Clear,
+            LoadOp::Load => AttachmentLoadOp::Load,
+            LoadOp::DontCare => AttachmentLoadOp::DontCare,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageUse {
+    Color(LoadOp),
+    Depth(LoadOp),
+    /// Receives the resolved samples of the colour attachment at the same index.
+    Resolve,
+    /// Read by the pass's shaders.
+    Sampled,
+}
+
+impl ImageUse {
+    fn reads(self) -> bool {
+        matches!(
+            self,
+            ImageUse::Color(LoadOp::Load) | ImageUse::Depth(LoadOp::Load) | ImageUse::Sampled
+        )
+    }
+
+    fn layout(self) -> Layout {
+        match self {
+            ImageUse::Color(_) | ImageUse::Resolve => Layout::ColorAttachmentOptimal,
+            ImageUse::Depth(_) => Layout::DepthStencilAttachmentOptimal,
+            ImageUse::Sampled => Layout::ShaderReadOnlyOptimal,
+        }
+    }
+
+    fn usage(self) -> Usage {
+        match self {
+            ImageUse::Color(_) | ImageUse::Resolve => Usage::COLOR_ATTACHMENT,
+            ImageUse::Depth(_) => Usage::DEPTH_STENCIL_ATTACHMENT,
+            ImageUse::Sampled => Usage::SAMPLED,
+        }
+    }
+
+    fn stages(self) -> PipelineStage {
+        match self {
+            ImageUse::Color(_) | ImageUse::Resolve => PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            ImageUse::Depth(_) => {
+                PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS
+            }
+            ImageUse::Sampled => PipelineStage::FRAGMENT_SHADER,
+        }
+    }
+
+    fn access(self) -> Access {
+        match self {
+            ImageUse::Color(_) | ImageUse::Resolve => {
+                Access::COLOR_ATTACHMENT_READ | Access::COLOR_ATTACHMENT_WRITE
+            }
+            ImageUse::Depth(_) => {
+                Access::DEPTH_STENCIL_ATTACHMENT_READ | Access::DEPTH_STENCIL_ATTACHMENT_WRITE
+            }
+            ImageUse::Sampled => Access::SHADER_READ,
+        }
+    }
+}
+
+/// The images a pass draws into and reads from. Attachments are bound in the order colours,
+/// depth, resolves.
+#[derive(Debug, Clone)]
+pub struct PassDesc {
+    name: &'static str,
+    colors: Vec<(ImageId, LoadOp)>,
+    depth: Option<(ImageId, LoadOp)>,
+    resolves: Vec<ImageId>,
+    sampled: Vec<ImageId>,
+    after: Vec<PassId>,
+    unless: Vec<ImageId>,
+}
+
+impl PassDesc {
+    pub fn new(name: &'static str) -> PassDesc {
+        Self {
+            name,
+            colors: Vec::new(),
+            depth: None,
+            resolves: Vec::new(),
+            sampled: Vec::new(),
+            after: Vec::new(),
+            unless: Vec::new(),
+        }
+    }
+
+    pub fn color(mut self, image: ImageId, load: LoadOp) -> PassDesc {
+        self.colors.push((image, load));
+        self
+    }
+
+    pub fn depth(mut self, image: ImageId, load: LoadOp) -> PassDesc {
+        self.depth = Some((image, load));
+        self
+    }
+
+    /// Resolves the next colour attachment without a resolve yet into `image`.
+    pub fn resolve(mut self, image: ImageId) -> PassDesc {
+        self.resolves.push(image);
+        self
+    }
+
+    pub fn sample(mut self, image: ImageId) -> PassDesc {
+        self.sampled.push(image);
+        self
+    }
+
+    /// Runs after `pass` even when they share no images.
+    pub fn after(mut self, pass: PassId) -> PassDesc {
+        self.after.push(pass);
+        self
+    }
+
+    /// Leaves the pass out of frames that bind the imported `image`, for when passes using it
+    /// do this one's work differently. They never run in the same frame.
+    pub fn unless(mut self, image: ImageId) -> PassDesc {
+        self.unless.push(image);
+        self
+    }
+
+    /// Attachments in binding order, then the sampled images.
+    fn uses(&self) -> Vec<(ImageId, ImageUse)> {
+        self.colors
+            .iter()
+            .map(|&(image, load)| (image, ImageUse::Color(load)))
+            .chain(
+                self.depth
+                    .map(|(image, load)| (image, ImageUse::Depth(load))),
+            )
+            .chain(
+                self.resolves
+                    .iter()
+                    .map(|&image| (image, ImageUse::Resolve)),
+            )
+            .chain(self.sampled.iter().map(|&image| (image, ImageUse::Sampled)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ImageKind {
+    /// Created by the graph and only valid within a frame.
+    Transient,
+    /// Provided for each frame, ending up in `final_layout`.
+    Imported { final_layout: Layout },
+}
+
+#[derive(Debug, Clone)]
+struct ImageDecl {
+    name: &'static str,
+    desc: ImageDesc,
+    kind: ImageKind,
+}
+
+/// Collects images and passes, then creates everything they need at once.
+#[derive(Debug, Clone)]
+pub struct RenderGraphBuilder {
+    images: Vec<ImageDecl>,
+    passes: Vec<PassDesc>,
+}
+
+impl RenderGraphBuilder {
+    pub fn new() -> RenderGraphBuilder {
+        Self {
+            images: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    /// An image the graph allocates itself. Its contents don't outlive the frame, so the first
+    /// pass using it can't read it.
+    pub fn transient(&mut self, name: &'static str, desc: ImageDesc) -> ImageId {
+        self.images.push(ImageDecl {
+            name,
+            desc,
+            kind: ImageKind::Transient,
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    /// An image owned by someone else, like the swapchain, bound anew every frame. The last pass
+    /// using it leaves it in `final_layout`. Once sampled it can only be sampled again, ending
+    /// up in `ShaderReadOnlyOptimal`.
+    pub fn import(&mut self, name: &'static str, desc: ImageDesc, final_layout: Layout) -> ImageId {
+        self.images.push(ImageDecl {
+            name,
+            desc,
+            kind: ImageKind::Imported { final_layout },
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, pass: PassDesc) -> PassId {
+        self.passes.push(pass);
+        PassId(self.passes.len() - 1)
+    }
+
+    /// Orders the passes and creates their render passes. Images are allocated by `resize`.
+    ///
+    /// Panics like `compile`.
+    pub fn build(
+        self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) -> RenderGraph {
+        let plan = self.compile();
+        let render_passes = plan
+            .passes
+            .iter()
+            .map(|pass| {
+                let render_pass = unsafe { pass.create_render_pass(device) };
+                gpu_objects.created(GpuObject::RenderPass);
+                render_pass
+            })
+            .collect();
+
+        RenderGraph {
+            images: self.images,
+            plan,
+            render_passes,
+            targets: Vec::new(),
+            extent: Extent2D {
+                width: 0,
+                height: 0,
+            },
+            framebuffers: Vec::new(),
+            states: HashMap::new(),
+        }
+    }
+
+    /// Works out everything about the graph that doesn't need the GPU: the order passes run in,
+    /// which transient images share one and the layouts each pass takes its attachments through.
+    ///
+    /// Panics if the passes depend on each other in a cycle, use an image twice in one pass, read
+    /// an image before anything drew into it, draw into an imported image after sampling it or
+    /// need an imported image in another layout than the pass before left it in.
+    fn compile(&self) -> GraphPlan {
+        let order = self.order();
+        let position: HashMap<PassId, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(position, pass)| (*pass, position))
+            .collect();
+
+        // Every use of each image, in execution order.
+        let mut uses: Vec<Vec<(usize, ImageUse)>> = vec![Vec::new(); self.images.len()];
+        for (index, pass) in self.passes.iter().enumerate() {
+            assert!(
+                pass.resolves.is_empty() || pass.resolves.len() == pass.colors.len(),
+                "Pass {} has to resolve every colour attachment or none",
+                pass.name
+            );
+            for image in &pass.unless {
+                assert!(
+                    self.images[image.0].kind != ImageKind::Transient,
+                    "Pass {} can only be left out for imported images, {} isn't",
+                    pass.name,
+                    self.images[image.0].name
+                );
+            }
+            let pass_uses = pass.uses();
+            for (used, &(image, image_use)) in pass_uses.iter().enumerate() {
+                assert!(
+                    pass_uses[..used]
+                        .iter()
+                        .all(|(earlier, _)| *earlier != image),
+                    "Pass {} uses {} more than once",
+                    pass.name,
+                    self.images[image.0].name
+                );
+                uses[image.0].push((position[&PassId(index)], image_use));
+            }
+        }
+        for (image, image_uses) in uses.iter_mut().enumerate() {
+            image_uses.sort_by_key(|(position, _)| *position);
+            if let Some((_, first)) = image_uses.first() {
+                assert!(
+                    !first.reads(),
+                    "{} is read before any pass draws into it",
+                    self.images[image].name
+                );
+            }
+            if let ImageKind::Imported { final_layout } = self.images[image].kind {
+                // The graph only transitions its own images between passes, render passes take
+                // imported ones into the layout their next use needs.
+                if let Some(sampled) = image_uses
+                    .iter()
+                    .position(|(_, image_use)| *image_use == ImageUse::Sampled)
+                {
+                    assert!(
+                        image_uses[sampled..]
+                            .iter()
+                            .all(|(_, image_use)| *image_use == ImageUse::Sampled),
+                        "{} is imported, it can't be drawn into after being sampled",
+                        self.images[image].name
+                    );
+                    assert!(
+                        final_layout == Layout::ShaderReadOnlyOptimal,
+                        "{} is imported and sampled last, so it has to end up in \
+                         ShaderReadOnlyOptimal",
+                        self.images[image].name
+                    );
+                }
+            }
+        }
+
+        let (physical_of, physical) = self.alias(&uses);
+
+        let passes: Vec<PassPlan> = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(index, _)| self.plan_pass(PassId(index), &order, &position, &uses))
+            .collect();
+        for (image, image_uses) in uses.iter().enumerate() {
+            if self.images[image].kind != ImageKind::Transient {
+                self.check_imported_layouts(ImageId(image), image_uses, &order, &passes);
+            }
+        }
+
+        GraphPlan {
+            order,
+            physical_of,
+            physical,
+            passes,
+        }
+    }
+
+    /// The graph only puts barriers transitioning its own images between passes, render passes
+    /// take imported ones from one layout to the next. Panics if a pass can follow one leaving
+    /// `image` in another layout than it needs, counting passes left out for unbound images.
+    fn check_imported_layouts(
+        &self,
+        image: ImageId,
+        uses: &[(usize, ImageUse)],
+        order: &[PassId],
+        passes: &[PassPlan],
+    ) {
+        let layouts = |position: usize| {
+            passes[order[position].0]
+                .uses()
+                .into_iter()
+                .find(|(used, _, _)| *used == image)
+                .map(|(_, _, layouts)| layouts)
+                .expect("Every use of an image is in its pass's plan")
+        };
+
+        for (index, &(position, _)) in uses.iter().enumerate() {
+            let needed = layouts(position).start;
+            if needed == Layout::Undefined {
+                continue;
+            }
+            let pass = order[position];
+            for &(earlier, _) in uses[..index].iter().rev() {
+                if self.exclusive(order[earlier], pass) {
+                    continue;
+                }
+                let left_in = layouts(earlier).end;
+                assert!(
+                    left_in == needed,
+                    "{} is imported, so it can't be transitioned from {:?} after pass {} to {:?} \
+                     for pass {}",
+                    self.images[image.0].name,
+                    left_in,
+                    self.passes[order[earlier].0].name,
+                    needed,
+                    self.passes[pass.0].name
+                );
+                if !self.can_leave_out(order[earlier], pass) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Whether `pass` can be left out of a frame that `other` is recorded in, because it uses an
+    /// imported image `other` doesn't need bound or is left out for one `other` isn't.
+    fn can_leave_out(&self, pass: PassId, other: PassId) -> bool {
+        let (pass, other) = (&self.passes[pass.0], &self.passes[other.0]);
+        let other_uses = other.uses();
+        let needs_bound = pass.uses().iter().any(|(image, _)| {
+            self.images[image.0].kind != ImageKind::Transient
+                && other_uses
+                    .iter()
+                    .all(|(other_image, _)| other_image != image)
+        });
+        needs_bound
+            || pass
+                .unless
+                .iter()
+                .any(|image| !other.unless.contains(image))
+    }
+
+    /// Whether one of the passes is left out whenever the other runs.
+    fn exclusive(&self, first: PassId, second: PassId) -> bool {
+        let left_out_for = |pass: PassId, other: PassId| {
+            let other_uses = self.passes[other.0].uses();
+            self.passes[pass.0]
+                .unless
+                .iter()
+                .any(|image| other_uses.iter().any(|(used, _)| used == image))
+        };
+        left_out_for(first, second) || left_out_for(second, first)
+    }
+
+    /// Declaration order, except passes come after the passes they're declared to follow and
+    /// after earlier passes using the same images.
+    fn order(&self) -> Vec<PassId> {
+        let mut dependencies: Vec<Vec<usize>> = self
+            .passes
+            .iter()
+            .map(|pass| pass.after.iter().map(|pass| pass.0).collect())
+            .collect();
+        for (index, pass) in self.passes.iter().enumerate() {
+            let images: Vec<ImageId> = pass.uses().iter().map(|(image, _)| *image).collect();
+            for (earlier, earlier_pass) in self.passes[..index].iter().enumerate() {
+                if earlier_pass
+                    .uses()
+                    .iter()
+                    .any(|(image, _)| images.contains(image))
+                {
+                    dependencies[index].push(earlier);
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut done = vec![false; self.passes.len()];
+        while order.len() < self.passes.len() {
+            let next = (0..self.passes.len())
+                .find(|&pass| {
+                    !done[pass]
+                        && dependencies[pass]
+                            .iter()
+                            .all(|&dependency| done[dependency])
+                })
+                .expect("Render graph passes depend on each other in a cycle");
+            done[next] = true;
+            order.push(PassId(next));
+        }
+        order
+    }
+
+    /// Gives each transient image a physical image, sharing one between images with the same
+    /// description that are never used by the same passes.
+    ///
+    /// Only whole images are shared: images with different formats, sample counts or sizes get
+    /// their own allocations even when their lifetimes don't overlap. Binding them to the same
+    /// memory would need the device's memory requirements for each, which `resize` only learns
+    /// once the plan is made.
+    fn alias(&self, uses: &[Vec<(usize, ImageUse)>]) -> (Vec<Option<usize>>, Vec<PhysicalImage>) {
+        let mut transients: Vec<usize> = (0..self.images.len())
+            .filter(|&image| {
+                self.images[image].kind == ImageKind::Transient && !uses[image].is_empty()
+            })
+            .collect();
+        transients.sort_by_key(|&image| uses[image][0].0);
+
+        let mut physical_of = vec![None; self.images.len()];
+        let mut physical: Vec<PhysicalImage> = Vec::new();
+        for image in transients {
+            let desc = self.images[image].desc;
+            let first = uses[image][0].0;
+            let last = uses[image][uses[image].len() - 1].0;
+            let usage = uses[image]
+                .iter()
+                .fold(Usage::empty(), |usage, (_, image_use)| {
+                    usage | image_use.usage()
+                });
+
+            let free = physical
+                .iter()
+                .position(|candidate| candidate.desc == desc && candidate.last_use < first);
+            let index = match free {
+                Some(index) => index,
+                None => {
+                    physical.push(PhysicalImage {
+                        desc,
+                        usage: Usage::empty(),
+                        last_use: 0,
+                    });
+                    physical.len() - 1
+                }
+            };
+            physical[index].usage |= usage;
+            physical[index].last_use = last;
+            physical_of[image] = Some(index);
+        }
+
+        (physical_of, physical)
+    }
+
+    /// The attachments of `pass` and the layouts its render pass takes them through, ready for
+    /// the next pass that can run in the same frame.
+    fn plan_pass(
+        &self,
+        id: PassId,
+        order: &[PassId],
+        position: &HashMap<PassId, usize>,
+        uses: &[Vec<(usize, ImageUse)>],
+    ) -> PassPlan {
+        let pass = &self.passes[id.0];
+        let at = position[&id];
+        let mut attachments = Vec::new();
+        let mut layouts = Vec::new();
+        for (image, image_use) in pass.uses() {
+            if image_use == ImageUse::Sampled {
+                continue;
+            }
+            let decl = &self.images[image.0];
+            let layout = image_use.layout();
+            let load = match image_use {
+                ImageUse::Color(load) | ImageUse::Depth(load) => load,
+                ImageUse::Resolve | ImageUse::Sampled => LoadOp::DontCare,
+            };
+            let mut later_uses = uses[image.0]
+                .iter()
+                .filter(|(later, _)| *later > at && !self.exclusive(id, order[*later]))
+                .peekable();
+            // Leaving an image ready to be sampled saves a barrier transitioning it.
+            let next_layout = match later_uses.peek() {
+                Some((_, ImageUse::Sampled)) => ImageUse::Sampled.layout(),
+                _ => layout,
+            };
+            let (store, final_layout) = match decl.kind {
+                ImageKind::Imported { final_layout } => {
+                    let last = later_uses.peek().is_none();
+                    (true, if last { final_layout } else { next_layout })
+                }
+                ImageKind::Transient => (
+                    later_uses.any(|(_, image_use)| image_use.reads()),
+                    next_layout,
+                ),
+            };
+            let initial_layout = if load == LoadOp::Load {
+                layout
+            } else {
+                Layout::Undefined
+            };
+            let store = if store {
+                AttachmentStoreOp::Store
+            } else {
+                AttachmentStoreOp::DontCare
+            };
+
+            attachments.push(Attachment {
+                format: Some(decl.desc.format),
+                samples: decl.desc.samples,
+                ops: AttachmentOps::new(load.into(), store),
+                stencil_ops: AttachmentOps::DONT_CARE,
+                layouts: initial_layout..final_layout,
+            });
+            layouts.push((image, image_use, initial_layout..final_layout));
+        }
+
+        PassPlan {
+            name: pass.name,
+            attachments,
+            layouts,
+            colors: pass.colors.len(),
+            depth: pass.depth.is_some(),
+            resolves: pass.resolves.len(),
+            sampled: pass.sampled.clone(),
+            unless: pass.unless.clone(),
+        }
+    }
+}
+
+impl Default for RenderGraphBuilder {
+    fn default() -> RenderGraphBuilder {
+        RenderGraphBuilder::new()
+    }
+}
+
+/// Everything the graph works out from its passes before creating anything on the GPU.
+#[derive(Debug)]
+struct GraphPlan {
+    order: Vec<PassId>,
+    /// The physical image behind each transient image.
+    physical_of: Vec<Option<usize>>,
+    physical: Vec<PhysicalImage>,
+    /// Indexed by `PassId`.
+    passes: Vec<PassPlan>,
+}
+
+impl GraphPlan {
+    fn resource(&self, image: ImageId) -> Resource {
+        match self.physical_of[image.0] {
+            Some(physical) => Resource::Physical(physical),
+            None => Resource::Imported(image),
+        }
+    }
+
+    /// The barriers `pass` needs after the passes recorded before it this frame, which left
+    /// their images in `states`. Updates `states` with how `pass` leaves its images.
+    fn barriers(&self, states: &mut HashMap<Resource, ImageState>, pass: PassId) -> PassBarriers {
+        let mut barriers = PassBarriers {
+            stages: PipelineStage::empty()..PipelineStage::empty(),
+            access: Access::empty()..Access::empty(),
+            transitions: Vec::new(),
+        };
+        let mut after = Vec::new();
+        for (image, image_use, layouts) in self.passes[pass.0].uses() {
+            let resource = self.resource(image);
+            let needed = ImageState {
+                stages: image_use.stages(),
+                access: image_use.access(),
+                layout: layouts.start,
+            };
+            if let Some(previous) = states.get(&resource) {
+                barriers.stages.start |= previous.stages;
+                barriers.stages.end |= needed.stages;
+                // An undefined starting layout throws the contents away, there's nothing to
+                // transition. `compile` made sure render passes leave imported images in the
+                // layout they're needed in next.
+                let transition =
+                    needed.layout != Layout::Undefined && needed.layout != previous.layout;
+                match resource {
+                    Resource::Physical(physical) if transition => {
+                        barriers.transitions.push((physical, *previous, needed))
+                    }
+                    _ => {
+                        barriers.access.start |= previous.access;
+                        barriers.access.end |= needed.access;
+                    }
+                }
+            }
+            after.push((
+                resource,
+                ImageState {
+                    layout: layouts.end,
+                    ..needed
+                },
+            ));
+        }
+        states.extend(after);
+
+        barriers
+    }
+}
+
+/// A pass's attachments, before its render pass is created.
+#[derive(Debug)]
+struct PassPlan {
+    name: &'static str,
+    /// What its render pass is created with, in binding order.
+    attachments: Vec<Attachment>,
+    /// Each attachment with the layouts its render pass takes it from and leaves it in.
+    layouts: Vec<(ImageId, ImageUse, std::ops::Range<Layout>)>,
+    colors: usize,
+    depth: bool,
+    resolves: usize,
+    sampled: Vec<ImageId>,
+    unless: Vec<ImageId>,
+}
+
+impl PassPlan {
+    /// Every image the pass uses with the layouts it takes them through, in the order of
+    /// `PassDesc::uses`.
+    fn uses(&self) -> Vec<(ImageId, ImageUse, std::ops::Range<Layout>)> {
+        let layout = Layout::ShaderReadOnlyOptimal;
+        self.layouts
+            .iter()
+            .cloned()
+            .chain(
+                self.sampled
+                    .iter()
+                    .map(|&image| (image, ImageUse::Sampled, layout..layout)),
+            )
+            .collect()
+    }
+
+    unsafe fn create_render_pass(
+        &self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+    ) -> <back::Backend as gfx_hal::Backend>::RenderPass {
+        let colors: Vec<_> = (0..self.colors)
+            .map(|index| (index, Layout::ColorAttachmentOptimal))
+            .collect();
+        let depth = if self.depth {
+            Some((self.colors, Layout::DepthStencilAttachmentOptimal))
+        } else {
+            None
+        };
+        let first_resolve = self.colors + usize::from(self.depth);
+        let resolves: Vec<_> = (0..self.resolves)
+            .map(|index| (first_resolve + index, Layout::ColorAttachmentOptimal))
+            .collect();
+        let subpass = SubpassDesc {
+            colors: &colors,
+            depth_stencil: depth.as_ref(),
+            inputs: &[],
+            resolves: &resolves,
+            preserves: &[],
+        };
+
+        device
+            .create_render_pass(&self.attachments, &[subpass], &[])
+            .expect("Out of memory")
+    }
+}
+
+/// An image shared by alike transient images whose uses don't overlap.
+#[derive(Debug)]
+struct PhysicalImage {
+    desc: ImageDesc,
+    usage: Usage,
+    /// Position of the last pass using any of the images sharing it.
+    last_use: usize,
+}
+
+/// What tracks an image's state through a frame: aliased transient images share theirs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Resource {
+    Physical(usize),
+    Imported(ImageId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ImageState {
+    stages: PipelineStage,
+    access: Access,
+    layout: Layout,
+}
+
+/// What to wait for before a pass. Images changing layout get their own barriers, the rest
+/// share one over `access`.
+#[derive(Debug)]
+struct PassBarriers {
+    stages: std::ops::Range<PipelineStage>,
+    access: std::ops::Range<Access>,
+    /// Physical images with the states they're transitioned from and to.
+    transitions: Vec<(usize, ImageState, ImageState)>,
+}
+
+/// An imported image bound for one frame.
+pub struct ImportedImage<'a> {
+    pub image: ImageId,
+    pub view: &'a <back::Backend as gfx_hal::Backend>::ImageView,
+}
+
+pub struct RenderGraph {
+    images: Vec<ImageDecl>,
+    plan: GraphPlan,
+    /// Indexed by `PassId`.
+    render_passes: Vec<<back::Backend as gfx_hal::Backend>::RenderPass>,
+    /// Allocated for each physical image by `resize`.
+    targets: Vec<RenderTarget>,
+    extent: Extent2D,
+    /// Created for passes recorded since the last `retire_framebuffers`.
+    framebuffers: Vec<<back::Backend as gfx_hal::Backend>::Framebuffer>,
+    /// How the passes recorded so far this frame left each image.
+    states: HashMap<Resource, ImageState>,
+}
+
+impl RenderGraph {
+    pub fn render_pass(&self, pass: PassId) -> &<back::Backend as gfx_hal::Backend>::RenderPass {
+        &self.render_passes[pass.0]
+    }
+
+    pub fn pass_name(&self, pass: PassId) -> &'static str {
+        self.plan.passes[pass.0].name
+    }
+
+    /// Samples per pixel of the pass's attachments, which its pipelines have to match.
+    pub fn samples(&self, pass: PassId) -> NumSamples {
+        self.plan.passes[pass.0]
+            .attachments
+            .first()
+            .map_or(1, |attachment| attachment.samples)
+    }
+
+    /// Recreates the transient images for a swapchain of the given size.
+    ///
+    /// # Safety
+    ///
+    /// No frame recorded with the old images may still be in use by the GPU.
+    pub unsafe fn resize(
+        &mut self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        memory_types: &[MemoryType],
+        gpu_objects: &mut GpuObjectTracker,
+        extent: Extent2D,
+    ) {
+        self.extent = extent;
+        for target in self.targets.drain(..) {
+            target.destroy(device, gpu_objects);
+        }
+        for physical in &self.plan.physical {
+            self.targets.push(RenderTarget::new(
+                device,
+                memory_types,
+                gpu_objects,
+                size_of(physical.desc.size, extent),
+                physical.desc.format,
+                physical.desc.samples,
+                physical.usage,
+            ));
+        }
+    }
+
+    /// The passes to record this frame, in order. Passes using an imported image that isn't
+    /// bound are left out, as are passes left out for one that is.
+    pub fn schedule(&mut self, imported: &[ImportedImage]) -> Vec<PassId> {
+        self.states.clear();
+        let images = &self.images;
+        let passes = &self.plan.passes;
+        let bound = |image: &ImageId| imported.iter().any(|bound| bound.image == *image);
+        self.plan
+            .order
+            .iter()
+            .copied()
+            .filter(|pass| {
+                let plan = &passes[pass.0];
+                plan.layouts
+                    .iter()
+                    .map(|(image, _, _)| image)
+                    .chain(&plan.sampled)
+                    .all(|image| images[image.0].kind == ImageKind::Transient || bound(image))
+                    && !plan.unless.iter().any(bound)
+            })
+            .collect()
+    }
+
+    /// Records the barriers `pass` needs after the passes recorded before it this frame, then
+    /// begins its render pass. Returns the viewport covering its attachments, which is also set.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer must be recording, outside of any render pass.
+    pub unsafe fn begin_pass(
+        &mut self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+        command_buffer: &mut <back::Backend as gfx_hal::Backend>::CommandBuffer,
+        pass: PassId,
+        imported: &[ImportedImage],
+        clear_color: [f32; 4],
+    ) -> Viewport {
+        let barriers = self.plan.barriers(&mut self.states, pass);
+        if !barriers.stages.start.is_empty() {
+            let mut image_barriers: Vec<Barrier<back::Backend>> = barriers
+                .transitions
+                .iter()
+                .map(|(physical, previous, needed)| Barrier::Image {
+                    states: (previous.access, previous.layout)..(needed.access, needed.layout),
+                    target: self
+                        .targets
+                        .get(*physical)
+                        .expect("Render graph images are allocated by resize")
+                        .image(),
+                    families: None,
+                    range: whole_image(self.plan.physical[*physical].desc.format),
+                })
+                .collect();
+            if !barriers.access.start.is_empty() || !barriers.access.end.is_empty() {
+                image_barriers.push(Barrier::AllImages(barriers.access));
+            }
+            command_buffer.pipeline_barrier(barriers.stages, Dependencies::empty(), image_barriers);
+        }
+
+        let plan = &self.plan.passes[pass.0];
+        let render_pass = &self.render_passes[pass.0];
+        let views: Vec<_> = plan
+            .layouts
+            .iter()
+            .map(|(image, _, _)| self.view(*image, imported))
+            .collect();
+        let extent = plan
+            .layouts
+            .first()
+            .map(|(image, _, _)| size_of(self.images[image.0].desc.size, self.extent))
+            .unwrap_or(self.extent);
+        let framebuffer = device
+            .create_framebuffer(
+                render_pass,
+                views,
+                Extent {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                },
+            )
+            .expect("Out of memory");
+        gpu_objects.created(GpuObject::Framebuffer);
+
+        let clear_values: Vec<ClearValue> = plan
+            .layouts
+            .iter()
+            .map(|(_, image_use, _)| match image_use {
+                ImageUse::Depth(_) => ClearValue {
+                    depth_stencil: ClearDepthStencil {
+                        depth: 1.0,
+                        stencil: 0,
+                    },
+                },
+                _ => ClearValue {
+                    color: ClearColor {
+                        float32: clear_color,
+                    },
+                },
+            })
+            .collect();
+        let viewport = Viewport {
+            rect: Rect {
+                x: 0,
+                y: 0,
+                w: extent.width as i16,
+                h: extent.height as i16,
+            },
+            depth: 0.0..1.0,
+        };
+        command_buffer.set_viewports(0, std::slice::from_ref(&viewport));
+        command_buffer.set_scissors(0, [viewport.rect]);
+        command_buffer.begin_render_pass(
+            render_pass,
+            &framebuffer,
+            viewport.rect,
+            &clear_values,
+            SubpassContents::Inline,
+        );
+        self.framebuffers.push(framebuffer);
+
+        viewport
+    }
+
+    /// # Safety
+    ///
+    /// Must follow `begin_pass` on the same command buffer.
+    pub unsafe fn end_pass(
+        &self,
+        command_buffer: &mut <back::Backend as gfx_hal::Backend>::CommandBuffer,
+    ) {
+        command_buffer.end_render_pass();
+    }
+
+    /// Destroys the framebuffers of the frames recorded so far.
+    ///
+    /// # Safety
+    ///
+    /// Those frames must be done on the GPU.
+    pub unsafe fn retire_framebuffers(
+        &mut self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) {
+        for framebuffer in self.framebuffers.drain(..) {
+            device.destroy_framebuffer(framebuffer);
+            gpu_objects.destroyed(GpuObject::Framebuffer);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Nothing recorded with the graph may still be in use by the GPU.
+    pub unsafe fn destroy(
+        mut self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) {
+        self.retire_framebuffers(device, gpu_objects);
+        for target in self.targets {
+            target.destroy(device, gpu_objects);
+        }
+        for render_pass in self.render_passes {
+            device.destroy_render_pass(render_pass);
+            gpu_objects.destroyed(GpuObject::RenderPass);
+        }
+    }
+
+    fn view<'a>(
+        &'a self,
+        image: ImageId,
+        imported: &[ImportedImage<'a>],
+    ) -> &'a <back::Backend as gfx_hal::Backend>::ImageView {
+        match self.plan.resource(image) {
+            Resource::Physical(physical) => self
+                .targets
+                .get(physical)
+                .expect("Render graph images are allocated by resize")
+                .view(),
+            Resource::Imported(image) => {
+                imported
+                    .iter()
+                    .find(|bound| bound.image == image)
+                    .expect("Scheduled passes only use bound images")
+                    .view
+            }
+        }
+    }
+}
+
+fn size_of(size: ImageSize, swapchain: Extent2D) -> Extent2D {
+    match size {
+        ImageSize::Swapchain => swapchain,
+        ImageSize::Fixed(extent) => extent,
+    }
+}
+
+fn whole_image(format: Format) -> SubresourceRange {
+    SubresourceRange {
+        aspects: format.surface_desc().aspects,
+        level_start: 0,
+        level_count: Some(1),
+        layer_start: 0,
+        layer_count: Some(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR: ImageDesc = ImageDesc {
+        format: Format::Rgba8Srgb,
+        samples: 1,
+        size: ImageSize::Swapchain,
+    };
+    const DEPTH: ImageDesc = ImageDesc {
+        format: Format::D32Sfloat,
+        samples: 1,
+        size: ImageSize::Swapchain,
+    };
+
+    #[test]
+    fn passes_run_after_the_passes_they_follow() {
+        let mut graph = RenderGraphBuilder::new();
+        let shadow = graph.transient("shadow", DEPTH);
+        let color = graph.transient("color", COLOR);
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        let shadows = graph.add_pass(PassDesc::new("shadows").depth(shadow, LoadOp::Clear));
+        let sky = graph.add_pass(PassDesc::new("sky").color(color, LoadOp::Clear));
+        let scene = graph.add_pass(
+            PassDesc::new("scene")
+                .sample(shadow)
+                .color(color, LoadOp::Load),
+        );
+        let ui = graph.add_pass(
+            PassDesc::new("ui")
+                .color(swapchain, LoadOp::Clear)
+                .after(scene),
+        );
+
+        assert_eq!(graph.compile().order, vec![shadows, sky, scene, ui]);
+    }
+
+    #[test]
+    fn alike_transient_images_share_when_their_uses_dont_overlap() {
+        let mut graph = RenderGraphBuilder::new();
+        let first = graph.transient("first", COLOR);
+        let second = graph.transient("second", COLOR);
+        let third = graph.transient("third", COLOR);
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        graph.add_pass(PassDesc::new("scene").color(first, LoadOp::Clear));
+        graph.add_pass(
+            PassDesc::new("blur")
+                .sample(first)
+                .color(second, LoadOp::DontCare),
+        );
+        graph.add_pass(
+            PassDesc::new("blur again")
+                .sample(second)
+                .color(third, LoadOp::DontCare),
+        );
+        graph.add_pass(
+            PassDesc::new("present")
+                .sample(third)
+                .color(swapchain, LoadOp::DontCare),
+        );
+        let plan = graph.compile();
+
+        assert_eq!(plan.physical.len(), 2);
+        assert_eq!(plan.physical_of[third.0], plan.physical_of[first.0]);
+        assert_ne!(plan.physical_of[second.0], plan.physical_of[first.0]);
+        assert_eq!(plan.physical_of[swapchain.0], None);
+        let shared = &plan.physical[plan.physical_of[first.0].unwrap()];
+        assert_eq!(shared.usage, Usage::COLOR_ATTACHMENT | Usage::SAMPLED);
+        assert_eq!(shared.last_use, 3);
+    }
+
+    #[test]
+    fn transient_images_with_different_descriptions_never_share() {
+        let mut graph = RenderGraphBuilder::new();
+        let color = graph.transient("color", COLOR);
+        let depth = graph.transient("depth", DEPTH);
+        graph.add_pass(PassDesc::new("color").color(color, LoadOp::Clear));
+        graph.add_pass(PassDesc::new("depth").depth(depth, LoadOp::Clear));
+        let plan = graph.compile();
+
+        assert_eq!(plan.physical.len(), 2);
+        assert_ne!(plan.physical_of[color.0], plan.physical_of[depth.0]);
+    }
+
+    #[test]
+    fn render_passes_leave_images_ready_for_their_next_use() {
+        let mut graph = RenderGraphBuilder::new();
+        let color = graph.transient("color", COLOR);
+        let depth = graph.transient("depth", DEPTH);
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        let scene = graph.add_pass(
+            PassDesc::new("scene")
+                .color(color, LoadOp::Clear)
+                .depth(depth, LoadOp::Clear),
+        );
+        let present = graph.add_pass(
+            PassDesc::new("present")
+                .sample(color)
+                .color(swapchain, LoadOp::DontCare),
+        );
+        let plan = graph.compile();
+
+        let scene = &plan.passes[scene.0];
+        assert_eq!(
+            scene.layouts,
+            vec![
+                (
+                    color,
+                    ImageUse::Color(LoadOp::Clear),
+                    Layout::Undefined..Layout::ShaderReadOnlyOptimal
+                ),
+                (
+                    depth,
+                    ImageUse::Depth(LoadOp::Clear),
+                    Layout::Undefined..Layout::DepthStencilAttachmentOptimal
+                ),
+            ]
+        );
+        // Only the colour is read later.
+        assert_eq!(scene.attachments[0].ops.store, AttachmentStoreOp::Store);
+        assert_eq!(scene.attachments[1].ops.store, AttachmentStoreOp::DontCare);
+
+        let present = &plan.passes[present.0];
+        assert_eq!(
+            present.layouts,
+            vec![(
+                swapchain,
+                ImageUse::Color(LoadOp::DontCare),
+                Layout::Undefined..Layout::Present
+            )]
+        );
+        assert_eq!(present.attachments[0].ops.store, AttachmentStoreOp::Store);
+        assert_eq!(present.sampled, vec![color]);
+    }
+
+    #[test]
+    fn barriers_only_transition_images_render_passes_leave_elsewhere() {
+        let mut graph = RenderGraphBuilder::new();
+        let color = graph.transient("color", COLOR);
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        let scene = graph.add_pass(PassDesc::new("scene").color(color, LoadOp::Clear));
+        let blur = graph.add_pass(
+            PassDesc::new("blur")
+                .sample(color)
+                .color(swapchain, LoadOp::DontCare),
+        );
+        let overlay = graph.add_pass(PassDesc::new("overlay").color(color, LoadOp::Load));
+        let plan = graph.compile();
+        let mut states = HashMap::new();
+
+        let first = plan.barriers(&mut states, scene);
+        assert!(first.stages.start.is_empty());
+        assert!(first.transitions.is_empty());
+
+        // The scene's render pass already left the colour ready to be sampled.
+        let sampled = plan.barriers(&mut states, blur);
+        assert_eq!(
+            sampled.stages,
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::FRAGMENT_SHADER
+        );
+        assert_eq!(
+            sampled.access,
+            Access::COLOR_ATTACHMENT_READ | Access::COLOR_ATTACHMENT_WRITE..Access::SHADER_READ
+        );
+        assert!(sampled.transitions.is_empty());
+
+        let drawn_again = plan.barriers(&mut states, overlay);
+        assert_eq!(
+            drawn_again.stages,
+            PipelineStage::FRAGMENT_SHADER..PipelineStage::COLOR_ATTACHMENT_OUTPUT
+        );
+        let physical = plan.physical_of[color.0].unwrap();
+        assert_eq!(drawn_again.transitions.len(), 1);
+        let (transitioned, from, to) = drawn_again.transitions[0];
+        assert_eq!(transitioned, physical);
+        assert_eq!(from.layout, Layout::ShaderReadOnlyOptimal);
+        assert_eq!(to.layout, Layout::ColorAttachmentOptimal);
+    }
+
+    #[test]
+    #[should_panic(expected = "second is read before any pass draws into it")]
+    fn reading_an_image_before_drawing_into_it_panics() {
+        let mut graph = RenderGraphBuilder::new();
+        let first = graph.transient("first", COLOR);
+        let second = graph.transient("second", COLOR);
+        graph.add_pass(
+            PassDesc::new("blur")
+                .sample(second)
+                .color(first, LoadOp::Clear),
+        );
+        graph.compile();
+    }
+
+    #[test]
+    #[should_panic(expected = "it can't be drawn into after being sampled")]
+    fn drawing_into_a_sampled_imported_image_panics() {
+        let mut graph = RenderGraphBuilder::new();
+        let screenshot = graph.import("screenshot", COLOR, Layout::ShaderReadOnlyOptimal);
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        graph.add_pass(PassDesc::new("scene").color(screenshot, LoadOp::Clear));
+        graph.add_pass(
+            PassDesc::new("present")
+                .sample(screenshot)
+                .color(swapchain, LoadOp::DontCare),
+        );
+        graph.add_pass(PassDesc::new("overlay").color(screenshot, LoadOp::Load));
+        graph.compile();
+    }
+
+    #[test]
+    #[should_panic(expected = "Pass blur uses color more than once")]
+    fn sampling_an_image_a_pass_draws_into_panics() {
+        let mut graph = RenderGraphBuilder::new();
+        let color = graph.transient("color", COLOR);
+        graph.add_pass(PassDesc::new("scene").color(color, LoadOp::Clear));
+        graph.add_pass(
+            PassDesc::new("blur")
+                .sample(color)
+                .color(color, LoadOp::Load),
+        );
+        graph.compile();
+    }
+
+    #[test]
+    #[should_panic(expected = "screenshot is imported, so it can't be transitioned from \
+                               ColorAttachmentOptimal after pass scene to ShaderReadOnlyOptimal \
+                               for pass present")]
+    fn leaving_out_a_pass_that_transitions_an_imported_image_panics() {
+        let mut graph = RenderGraphBuilder::new();
+        let screenshot = graph.import("screenshot", COLOR, Layout::ShaderReadOnlyOptimal);
+        let overlay = graph.import("overlay", COLOR, Layout::ShaderReadOnlyOptimal);
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        graph.add_pass(PassDesc::new("scene").color(screenshot, LoadOp::Clear));
+        // Left out on frames without an overlay bound, which would leave the screenshot in the
+        // wrong layout for presenting.
+        graph.add_pass(
+            PassDesc::new("ui")
+                .color(screenshot, LoadOp::Load)
+                .color(overlay, LoadOp::Clear),
+        );
+        graph.add_pass(
+            PassDesc::new("present")
+                .sample(screenshot)
+                .color(swapchain, LoadOp::DontCare),
+        );
+        graph.compile();
+    }
+
+    #[test]
+    fn imported_images_need_no_barriers_when_no_pass_can_be_left_out() {
+        let mut graph = RenderGraphBuilder::new();
+        let screenshot = graph.import("screenshot", COLOR, Layout::ShaderReadOnlyOptimal);
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        let scene = graph.add_pass(PassDesc::new("scene").color(screenshot, LoadOp::Clear));
+        let ui = graph.add_pass(PassDesc::new("ui").color(screenshot, LoadOp::Load));
+        let present = graph.add_pass(
+            PassDesc::new("present")
+                .sample(screenshot)
+                .color(swapchain, LoadOp::DontCare),
+        );
+        let plan = graph.compile();
+
+        assert_eq!(plan.order, vec![scene, ui, present]);
+        let mut states = HashMap::new();
+        for pass in plan.order.iter().copied() {
+            assert!(plan.barriers(&mut states, pass).transitions.is_empty());
+        }
+    }
+
+    #[test]
+    fn passes_left_out_for_a_bound_image_share_imported_images_with_its_users() {
+        let mut graph = RenderGraphBuilder::new();
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        let screenshot = graph.import("screenshot", COLOR, Layout::ShaderReadOnlyOptimal);
+        let scene = graph.add_pass(
+            PassDesc::new("scene")
+                .color(swapchain, LoadOp::Clear)
+                .unless(screenshot),
+        );
+        let capture = graph.add_pass(PassDesc::new("capture").color(screenshot, LoadOp::Clear));
+        let present = graph.add_pass(
+            PassDesc::new("present")
+                .sample(screenshot)
+                .color(swapchain, LoadOp::DontCare),
+        );
+        let plan = graph.compile();
+
+        assert_eq!(plan.order, vec![scene, capture, present]);
+        // The present pass never runs after the scene pass, so it's left ready to present.
+        assert_eq!(
+            plan.passes[scene.0].layouts,
+            vec![(
+                swapchain,
+                ImageUse::Color(LoadOp::Clear),
+                Layout::Undefined..Layout::Present
+            )]
+        );
+        assert_eq!(plan.passes[scene.0].unless, vec![screenshot]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pass scene can only be left out for imported images, color isn't")]
+    fn leaving_out_a_pass_for_a_transient_image_panics() {
+        let mut graph = RenderGraphBuilder::new();
+        let color = graph.transient("color", COLOR);
+        let swapchain = graph.import("swapchain", COLOR, Layout::Present);
+        graph.add_pass(
+            PassDesc::new("scene")
+                .color(swapchain, LoadOp::Clear)
+                .unless(color),
+        );
+        graph.add_pass(PassDesc::new("blur").color(color, LoadOp::Clear));
+        graph.compile();
+    }
+}
@@ -0,0 +1,175 @@
+use super::{
+    back,
+    buffer::find_memory_type,
+    gpu_objects::{GpuObject, GpuObjectTracker},
+};
+use gfx_hal::{
+    adapter::MemoryType,
+    device::Device,
+    format::{Aspects, Format, Swizzle},
+    image::{Kind, NumSamples, SubresourceRange, Tiling, Usage, ViewCapabilities, ViewKind},
+    memory::Properties,
+    window::Extent2D,
+    Limits,
+};
+
+/// Supported by every desktop GPU we target, so it isn't queried.
+pub const DEPTH_FORMAT: Format = Format::D32Sfloat;
+
+/// The most samples up to `requested` that both colour and depth attachments support. A single
+/// sample always works.
+pub fn supported_samples(limits: &Limits, requested: NumSamples) -> NumSamples {
+    highest_samples(
+        limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts,
+        requested,
+    )
+}
+
+/// The most samples up to `requested` with their bit set in `supported`.
+fn highest_samples(supported: NumSamples, requested: NumSamples) -> NumSamples {
+    [8, 4, 2]
+        .iter()
+        .copied()
+        .find(|&samples| samples <= requested && supported & samples != 0)
+        .unwrap_or(1)
+}
+
+/// An image the render graph draws into, with its own memory and a view of all of it.
+pub struct RenderTarget {
+    image: <back::Backend as gfx_hal::Backend>::Image,
+    memory: <back::Backend as gfx_hal::Backend>::Memory,
+    memory_size: u64,
+    view: <back::Backend as gfx_hal::Backend>::ImageView,
+}
+
+impl RenderTarget {
+    pub fn new(
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        memory_types: &[MemoryType],
+        gpu_objects: &mut GpuObjectTracker,
+        extent: Extent2D,
+        format: Format,
+        samples: NumSamples,
+        usage: Usage,
+    ) -> RenderTarget {
+        // Images only ever used as attachments never have to leave the GPU's tile memory.
+        let usage = if usage.contains(Usage::SAMPLED) {
+            usage
+        } else {
+            usage | Usage::TRANSIENT_ATTACHMENT
+        };
+        let aspects = if usage.contains(Usage::DEPTH_STENCIL_ATTACHMENT) {
+            Aspects::DEPTH
+        } else {
+            Aspects::COLOR
+        };
+
+        unsafe {
+            let mut image = device
+                .create_image(
+                    Kind::D2(extent.width, extent.height, 1, samples),
+                    1,
+                    format,
+                    Tiling::Optimal,
+                    usage,
+                    ViewCapabilities::empty(),
+                )
+                .expect("Failed to create image");
+            let requirements = device.get_image_requirements(&image);
+            // Tiled GPUs never have to back transient attachments with real memory.
+            let memory_type = find_memory_type(
+                memory_types,
+                requirements.type_mask,
+                Properties::DEVICE_LOCAL | Properties::LAZILY_ALLOCATED,
+            )
+            .filter(|_| usage.contains(Usage::TRANSIENT_ATTACHMENT))
+            .or_else(|| {
+                find_memory_type(
+                    memory_types,
+                    requirements.type_mask,
+                    Properties::DEVICE_LOCAL,
+                )
+            })
+            .or_else(|| find_memory_type(memory_types, requirements.type_mask, Properties::empty()))
+            .expect("No memory for image");
+            let memory = device
+                .allocate_memory(memory_type, requirements.size)
+                .expect("Out of memory");
+            device
+                .bind_image_memory(&memory, 0, &mut image)
+                .expect("Failed to bind image memory");
+            let view = device
+                .create_image_view(
+                    &image,
+                    ViewKind::D2,
+                    format,
+                    Swizzle::NO,
+                    SubresourceRange {
+                        aspects,
+                        level_start: 0,
+                        level_count: Some(1),
+                        layer_start: 0,
+                        layer_count: Some(1),
+                    },
+                )
+                .expect("Failed to create image view");
+
+            gpu_objects.created(GpuObject::Image);
+            gpu_objects.allocated(requirements.size);
+            gpu_objects.created(GpuObject::ImageView);
+
+            Self {
+                image,
+                memory,
+                memory_size: requirements.size,
+                view,
+            }
+        }
+    }
+
+    pub fn image(&self) -> &<back::Backend as gfx_hal::Backend>::Image {
+        &self.image
+    }
+
+    pub fn view(&self) -> &<back::Backend as gfx_hal::Backend>::ImageView {
+        &self.view
+    }
+
+    /// # Safety
+    ///
+    /// No command buffer using the image may still be in use by the GPU.
+    pub unsafe fn destroy(
+        self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) {
+        device.destroy_image_view(self.view);
+        gpu_objects.destroyed(GpuObject::ImageView);
+        device.destroy_image(self.image);
+        gpu_objects.destroyed(GpuObject::Image);
+        device.free_memory(self.memory);
+        gpu_objects.freed(self.memory_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_counts_round_down_to_a_supported_one() {
+        let up_to_four = 1 | 2 | 4;
+        assert_eq!(highest_samples(up_to_four, 8), 4);
+        assert_eq!(highest_samples(up_to_four, 4), 4);
+        assert_eq!(highest_samples(up_to_four, 3), 2);
+        assert_eq!(highest_samples(up_to_four, 1), 1);
+        assert_eq!(highest_samples(up_to_four, 0), 1);
+    }
+
+    #[test]
+    fn unsupported_counts_fall_back_to_one_sample() {
+        assert_eq!(highest_samples(1, 8), 1);
+        assert_eq!(highest_samples(1 | 8, 4), 1);
+        assert_eq!(highest_samples(1 | 8, 8), 8);
+    }
+}
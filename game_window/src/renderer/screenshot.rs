@@ -0,0 +1,460 @@
+use super::{
+    back,
+    buffer::{find_memory_type, HostBuffer},
+    gpu_objects::{GpuObject, GpuObjectTracker},
+    pipeline::PipelineDesc,
+};
+use common::paths::data_dir;
+use gfx_hal::{
+    adapter::MemoryType,
+    buffer::{self, SubRange},
+    command::{BufferImageCopy, CommandBuffer},
+    device::Device,
+    format::{Aspects, Format, Swizzle},
+    image::{
+        Access, Extent, Filter, Kind, Layout, Offset, SamplerDesc, SubresourceLayers,
+        SubresourceRange, Tiling, Usage, ViewCapabilities, ViewKind, WrapMode,
+    },
+    memory::{Barrier, Dependencies, Properties},
+    pso::{DescriptorType, Face, ImageDescriptorType, PipelineStage, ShaderStageFlags},
+    window::Extent2D,
+};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{error, info};
+
+const COLOR_RANGE: SubresourceRange = SubresourceRange {
+    aspects: Aspects::COLOR,
+    level_start: 0,
+    level_count: Some(1),
+    layer_start: 0,
+    layer_count: Some(1),
+};
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    UnsupportedFormat(Format),
+    Io(io::Error),
+    Encoding(png::EncodingError),
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenshotError::UnsupportedFormat(format) => {
+                write!(f, "can't convert {:?} pixels to RGBA8", format)
+            }
+            ScreenshotError::Io(error) => write!(f, "{}", error),
+            ScreenshotError::Encoding(error) => write!(f, "PNG encoding failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+impl From<io::Error> for ScreenshotError {
+    fn from(error: io::Error) -> ScreenshotError {
+        ScreenshotError::Io(error)
+    }
+}
+
+impl From<png::EncodingError> for ScreenshotError {
+    fn from(error: png::EncodingError) -> ScreenshotError {
+        ScreenshotError::Encoding(error)
+    }
+}
+
+/// Frames drawn offscreen by the render graph, which then draws them onto the swapchain image, and
+/// read back once the GPU is done with them. Kept for every frame of a capture, until its size
+/// changes.
+pub struct Screenshot {
+    image: <back::Backend as gfx_hal::Backend>::Image,
+    memory: <back::Backend as gfx_hal::Backend>::Memory,
+    memory_size: u64,
+    view: <back::Backend as gfx_hal::Backend>::ImageView,
+    sampler: <back::Backend as gfx_hal::Backend>::Sampler,
+    readback: HostBuffer,
+    extent: Extent2D,
+    format: Format,
+}
+
+impl Screenshot {
+    pub fn new(
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        memory_types: &[MemoryType],
+        gpu_objects: &mut GpuObjectTracker,
+        extent: Extent2D,
+        format: Format,
+    ) -> Screenshot {
+        let texel_bytes = u64::from(format.surface_desc().bits / 8);
+        let size = u64::from(extent.width) * u64::from(extent.height) * texel_bytes;
+
+        unsafe {
+            let mut image = device
+                .create_image(
+                    Kind::D2(extent.width, extent.height, 1, 1),
+                    1,
+                    format,
+                    Tiling::Optimal,
+                    Usage::COLOR_ATTACHMENT | Usage::SAMPLED | Usage::TRANSFER_SRC,
+                    ViewCapabilities::empty(),
+                )
+                .expect("Failed to create image");
+            let requirements = device.get_image_requirements(&image);
+            let memory_type = find_memory_type(
+                memory_types,
+                requirements.type_mask,
+                Properties::DEVICE_LOCAL,
+            )
+            .or_else(|| find_memory_type(memory_types, requirements.type_mask, Properties::empty()))
+            .expect("No memory for image");
+            let memory = device
+                .allocate_memory(memory_type, requirements.size)
+                .expect("Out of memory");
+            device
+                .bind_image_memory(&memory, 0, &mut image)
+                .expect("Failed to bind image memory");
+            let view = device
+                .create_image_view(&image, ViewKind::D2, format, Swizzle::NO, COLOR_RANGE)
+                .expect("Failed to create image view");
+            let sampler = device
+                .create_sampler(&SamplerDesc::new(Filter::Nearest, WrapMode::Clamp))
+                .expect("Out of memory");
+
+            gpu_objects.created(GpuObject::Image);
+            gpu_objects.allocated(requirements.size);
+            gpu_objects.created(GpuObject::ImageView);
+            gpu_objects.created(GpuObject::Sampler);
+
+            let readback = HostBuffer::new(
+                device,
+                memory_types,
+                gpu_objects,
+                size,
+                buffer::Usage::TRANSFER_DST,
+            );
+
+            Self {
+                image,
+                memory,
+                memory_size: requirements.size,
+                view,
+                sampler,
+                readback,
+                extent,
+                format,
+            }
+        }
+    }
+
+    pub fn view(&self) -> &<back::Backend as gfx_hal::Backend>::ImageView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &<back::Backend as gfx_hal::Backend>::Sampler {
+        &self.sampler
+    }
+
+    /// Records copying the drawn image to the readback buffer. Goes after the render pass that
+    /// presented `view` by sampling it.
+    pub fn record_copy(
+        &self,
+        command_buffer: &mut <back::Backend as gfx_hal::Backend>::CommandBuffer,
+    ) {
+        unsafe {
+            command_buffer.pipeline_barrier(
+                PipelineStage::FRAGMENT_SHADER..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                &[Barrier::Image {
+                    states: (Access::SHADER_READ, Layout::ShaderReadOnlyOptimal)
+                        ..(Access::TRANSFER_READ, Layout::TransferSrcOptimal),
+                    target: &self.image,
+                    families: None,
+                    range: COLOR_RANGE,
+                }],
+            );
+            command_buffer.copy_image_to_buffer(
+                &self.image,
+                Layout::TransferSrcOptimal,
+                self.readback.buffer(),
+                &[BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_width: self.extent.width,
+                    buffer_height: self.extent.height,
+                    image_layers: SubresourceLayers {
+                        aspects: Aspects::COLOR,
+                        level: 0,
+                        layers: 0..1,
+                    },
+                    image_offset: Offset::ZERO,
+                    image_extent: Extent {
+                        width: self.extent.width,
+                        height: self.extent.height,
+                        depth: 1,
+                    },
+                }],
+            );
+            command_buffer.pipeline_barrier(
+                PipelineStage::TRANSFER..PipelineStage::HOST,
+                Dependencies::empty(),
+                &[Barrier::Buffer {
+                    states: buffer::Access::TRANSFER_WRITE..buffer::Access::HOST_READ,
+                    target: self.readback.buffer(),
+                    families: None,
+                    range: SubRange::WHOLE,
+                }],
+            );
+        }
+    }
+
+    pub fn extent(&self) -> Extent2D {
+        self.extent
+    }
+
+    /// Reads back the pixels the last `record_copy` copied.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer that recorded the copy must have finished.
+    pub unsafe fn read(
+        &self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+    ) -> CapturedFrame {
+        CapturedFrame {
+            pixels: self.readback.read(device),
+            extent: self.extent,
+            format: self.format,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// No frame drawing into or copying from the screenshot may still be in use by the GPU.
+    pub unsafe fn destroy(
+        self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) {
+        device.destroy_sampler(self.sampler);
+        device.destroy_image_view(self.view);
+        device.destroy_image(self.image);
+        device.free_memory(self.memory);
+        self.readback.destroy(device, gpu_objects);
+        gpu_objects.destroyed(GpuObject::Sampler);
+        gpu_objects.destroyed(GpuObject::ImageView);
+        gpu_objects.destroyed(GpuObject::Image);
+        gpu_objects.freed(self.memory_size);
+    }
+}
+
+/// Pixels read back from the GPU, tightly packed in the swapchain format.
+pub struct CapturedFrame {
+    pub pixels: Vec<u8>,
+    pub extent: Extent2D,
+    pub format: Format,
+}
+
+impl CapturedFrame {
+    pub fn to_srgb_rgba8(&self) -> Result<Vec<u8>, ScreenshotError> {
+        to_srgb_rgba8(&self.pixels, self.format)
+    }
+}
+
+/// Draws a screenshot onto the swapchain image with one fullscreen triangle, so frames with a
+/// screenshot don't draw the scene twice.
+pub fn present_pipeline() -> PipelineDesc {
+    PipelineDesc::new("present-screenshot.vert", "present-screenshot.frag")
+        .cull(Face::NONE)
+        .descriptor_set(&[(
+            0,
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Sampled { with_sampler: true },
+            },
+            ShaderStageFlags::FRAGMENT,
+        )])
+}
+
+pub fn directory() -> PathBuf {
+    data_dir().join("screenshots")
+}
+
+/// Writes the frame to a timestamped PNG in the screenshots directory on another thread.
+pub fn save_async(frame: CapturedFrame) {
+    // Converting and compressing a full frame takes longer than rendering one.
+    let spawned = thread::Builder::new()
+        .name("screenshot".to_owned())
+        .spawn(move || match save(&frame) {
+            Ok(path) => info!("Saved screenshot to {}", path.display()),
+            Err(error) => error!("Could not save screenshot: {}", error),
+        });
+    if let Err(error) = spawned {
+        error!("Could not start saving the screenshot: {}", error);
+    }
+}
+
+fn save(frame: &CapturedFrame) -> Result<PathBuf, ScreenshotError> {
+    let rgba = frame.to_srgb_rgba8()?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or(0);
+    let directory = directory();
+    fs::create_dir_all(&directory)?;
+    let path = directory.join(format!("screenshot-{}.png", timestamp));
+    write_png(&path, frame.extent, &rgba)?;
+
+    Ok(path)
+}
+
+pub fn write_png(path: &Path, extent: Extent2D, rgba: &[u8]) -> Result<(), ScreenshotError> {
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, extent.width, extent.height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgba)?;
+
+    Ok(())
+}
+
+/// Converts tightly packed pixels in a swapchain format to sRGB encoded RGBA with 8 bits per
+/// channel. UNORM swapchains show the stored values as they are, so those are kept unchanged.
+fn to_srgb_rgba8(pixels: &[u8], format: Format) -> Result<Vec<u8>, ScreenshotError> {
+    let rgba = match format {
+        Format::Rgba8Srgb | Format::Rgba8Unorm => pixels.to_vec(),
+        Format::Bgra8Srgb | Format::Bgra8Unorm => {
+            let mut rgba = Vec::with_capacity(pixels.len());
+            for texel in pixels.chunks_exact(4) {
+                rgba.extend_from_slice(&[texel[2], texel[1], texel[0], texel[3]]);
+            }
+            rgba
+        }
+        Format::A2b10g10r10Unorm => {
+            let mut rgba = Vec::with_capacity(pixels.len());
+            for texel in pixels.chunks_exact(4) {
+                let packed = u32::from_le_bytes([texel[0], texel[1], texel[2], texel[3]]);
+                let channel = |shift: u32| ((packed >> shift & 0x3ff) >> 2) as u8;
+                rgba.extend_from_slice(&[
+                    channel(0),
+                    channel(10),
+                    channel(20),
+                    ((packed >> 30) * 85) as u8,
+                ]);
+            }
+            rgba
+        }
+        Format::Rgba16Sfloat => {
+            let mut rgba = Vec::with_capacity(pixels.len() / 2);
+            for (index, half) in pixels.chunks_exact(2).enumerate() {
+                let value = half_to_f32(u16::from_le_bytes([half[0], half[1]]));
+                // Alpha is never gamma encoded.
+                let encoded = if index % 4 == 3 {
+                    value
+                } else {
+                    linear_to_srgb(value)
+                };
+                rgba.push((encoded.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+            rgba
+        }
+        other => return Err(ScreenshotError::UnsupportedFormat(other)),
+    };
+
+    Ok(rgba)
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from(half >> 10 & 0x1f);
+    let mantissa = f32::from(half & 0x3ff);
+
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgba8_is_kept_as_is() {
+        let pixels = [10, 20, 30, 40, 50, 60, 70, 80];
+        for &format in &[Format::Rgba8Srgb, Format::Rgba8Unorm] {
+            assert_eq!(to_srgb_rgba8(&pixels, format).unwrap(), pixels);
+        }
+    }
+
+    #[test]
+    fn bgra8_is_swizzled() {
+        let pixels = [10, 20, 30, 40, 50, 60, 70, 80];
+        for &format in &[Format::Bgra8Srgb, Format::Bgra8Unorm] {
+            assert_eq!(
+                to_srgb_rgba8(&pixels, format).unwrap(),
+                [30, 20, 10, 40, 70, 60, 50, 80]
+            );
+        }
+    }
+
+    #[test]
+    fn a2b10g10r10_drops_the_low_bits() {
+        let texel =
+            |r: u32, g: u32, b: u32, a: u32| (r | g << 10 | b << 20 | a << 30).to_le_bytes();
+        let mut pixels = texel(0x3ff, 0x200, 0x003, 3).to_vec();
+        pixels.extend_from_slice(&texel(0, 0x004, 0x1ff, 1));
+
+        assert_eq!(
+            to_srgb_rgba8(&pixels, Format::A2b10g10r10Unorm).unwrap(),
+            [255, 128, 0, 255, 0, 1, 127, 85]
+        );
+    }
+
+    #[test]
+    fn half_floats_are_gamma_encoded_except_alpha() {
+        // 1.0, 0.5, -1.0 and 0.5 as halves.
+        let pixels: Vec<u8> = [0x3c00_u16, 0x3800, 0xbc00, 0x3800]
+            .iter()
+            .flat_map(|half| half.to_le_bytes())
+            .collect();
+
+        assert_eq!(
+            to_srgb_rgba8(&pixels, Format::Rgba16Sfloat).unwrap(),
+            [255, 188, 0, 128]
+        );
+    }
+
+    #[test]
+    fn half_to_f32_decodes_every_kind_of_half() {
+        assert_eq!(half_to_f32(0x0000), 0.0);
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(half_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(half_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(half_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn other_formats_are_rejected() {
+        assert!(matches!(
+            to_srgb_rgba8(&[0; 4], Format::R32Sfloat),
+            Err(ScreenshotError::UnsupportedFormat(Format::R32Sfloat))
+        ));
+    }
+}
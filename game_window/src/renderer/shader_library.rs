@@ -0,0 +1,254 @@
+use shaderc::{CompileOptions, Compiler, IncludeType, ResolvedInclude, ShaderKind};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// Nested includes deeper than this are almost certainly a cycle.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Every shader in `src/renderer/shaders`, so the game runs without the source tree next to it.
+const EMBEDDED: &[(&str, &str)] = &[
+    ("debug-line.frag", include_str!("shaders/debug-line.frag")),
+    ("debug-line.vert", include_str!("shaders/debug-line.vert")),
+    ("part-1.frag", include_str!("shaders/part-1.frag")),
+    ("part-1.vert", include_str!("shaders/part-1.vert")),
+    (
+        "present-screenshot.frag",
+        include_str!("shaders/present-screenshot.frag"),
+    ),
+    (
+        "present-screenshot.vert",
+        include_str!("shaders/present-screenshot.vert"),
+    ),
+    ("text.frag", include_str!("shaders/text.frag")),
+    ("text.vert", include_str!("shaders/text.vert")),
+];
+
+/// `#define`s a shader is compiled with, like `FOG` or `ALPHA_TEST`. Kept sorted so the same set
+/// always hits the same cache entry.
+pub type Defines = BTreeMap<String, Option<String>>;
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io { path: PathBuf, error: io::Error },
+    UnknownStage(String),
+    Compile { name: String, message: String },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, error } => {
+                write!(f, "could not read {}: {}", path.display(), error)
+            }
+            ShaderError::UnknownStage(name) => {
+                write!(
+                    f,
+                    "can't tell which stage {} is for from its extension",
+                    name
+                )
+            }
+            ShaderError::Compile { name, message } => {
+                write!(f, "could not compile {}:\n{}", name, message)
+            }
+        }
+    }
+}
+
+impl Error for ShaderError {}
+
+/// Where a library reads shader sources from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaderSource {
+    /// The shaders compiled into the binary.
+    Embedded,
+    /// A directory read on every compile, for editing shaders without rebuilding the game.
+    Directory(PathBuf),
+}
+
+impl ShaderSource {
+    /// Reads a shader or include by its path relative to the shader root.
+    fn read(&self, name: &Path) -> Result<String, ShaderError> {
+        match self {
+            ShaderSource::Embedded => EMBEDDED
+                .iter()
+                .find(|(embedded, _)| Path::new(embedded) == name)
+                .map(|(_, source)| (*source).to_owned())
+                .ok_or_else(|| ShaderError::Io {
+                    path: name.to_owned(),
+                    error: io::Error::new(io::ErrorKind::NotFound, "no such embedded shader"),
+                }),
+            ShaderSource::Directory(root) => {
+                let path = root.join(name);
+                fs::read_to_string(&path).map_err(|error| ShaderError::Io { path, error })
+            }
+        }
+    }
+}
+
+/// Compiles shaders, resolving `#include`s against the same source and caching every permutation
+/// of defines that was asked for.
+///
+/// `#include <lighting.glsl>` is looked up from the library root, `#include "block.glsl"` next to
+/// the file that includes it.
+pub struct ShaderLibrary {
+    source: ShaderSource,
+    compiler: Compiler,
+    cache: HashMap<(String, Defines), Vec<u32>>,
+}
+
+impl ShaderLibrary {
+    pub fn new(source: ShaderSource) -> ShaderLibrary {
+        Self {
+            source,
+            compiler: Compiler::new().expect("Failed to create shader compiler"),
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn source(&self) -> &ShaderSource {
+        &self.source
+    }
+
+    /// Returns the SPIR-V for `name` compiled with `defines`, compiling it only the first time.
+    pub fn compile(&mut self, name: &str, defines: &Defines) -> Result<&[u32], ShaderError> {
+        let key = (name.to_owned(), defines.clone());
+        if !self.cache.contains_key(&key) {
+            let spirv = self.compile_uncached(name, defines)?;
+            self.cache.insert(key.clone(), spirv);
+        }
+
+        Ok(&self.cache[&key])
+    }
+
+    /// Forgets every compiled permutation, so edited shaders get picked up.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    fn compile_uncached(&mut self, name: &str, defines: &Defines) -> Result<Vec<u32>, ShaderError> {
+        let kind = shader_kind(name).ok_or_else(|| ShaderError::UnknownStage(name.to_owned()))?;
+        let source = self.source.read(Path::new(name))?;
+
+        let mut options = CompileOptions::new().expect("Failed to create shader compile options");
+        for (define, value) in defines {
+            options.add_macro_definition(define, value.as_deref());
+        }
+
+        // Names stay relative to the root, so both sources resolve includes the same way.
+        let shader_source = self.source.clone();
+        options.set_include_callback(move |requested, include_type, requesting, depth| {
+            if depth > MAX_INCLUDE_DEPTH {
+                return Err(format!("includes nested deeper than {}", MAX_INCLUDE_DEPTH));
+            }
+
+            let path = match include_type {
+                IncludeType::Standard => PathBuf::from(requested),
+                IncludeType::Relative => Path::new(requesting)
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(requested),
+            };
+            let content = shader_source
+                .read(&path)
+                .map_err(|error| error.to_string())?;
+
+            Ok(ResolvedInclude {
+                resolved_name: path.to_string_lossy().into_owned(),
+                content,
+            })
+        });
+
+        debug!("Compiling shader {} with {:?}", name, defines);
+        let artifact = self
+            .compiler
+            .compile_into_spirv(&source, kind, name, "main", Some(&options))
+            .map_err(|error| ShaderError::Compile {
+                name: name.to_owned(),
+                message: error.to_string(),
+            })?;
+
+        Ok(artifact.as_binary().to_vec())
+    }
+}
+
+fn shader_kind(name: &str) -> Option<ShaderKind> {
+    match Path::new(name).extension()?.to_str()? {
+        "vert" => Some(ShaderKind::Vertex),
+        "frag" => Some(ShaderKind::Fragment),
+        "comp" => Some(ShaderKind::Compute),
+        "geom" => Some(ShaderKind::Geometry),
+        "tesc" => Some(ShaderKind::TessControl),
+        "tese" => Some(ShaderKind::TessEvaluation),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn every_shader_in_the_source_tree_is_embedded() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/renderer/shaders");
+        let mut on_disk = 0;
+        for entry in fs::read_dir(&root).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let embedded = ShaderSource::Embedded.read(Path::new(name)).unwrap();
+            assert_eq!(embedded, fs::read_to_string(&path).unwrap(), "{}", name);
+            on_disk += 1;
+        }
+        assert_eq!(on_disk, EMBEDDED.len());
+
+        assert!(matches!(
+            ShaderSource::Embedded.read(Path::new("missing.frag")),
+            Err(ShaderError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn directories_resolve_includes_relative_to_the_root_and_the_includer() {
+        let root = env::temp_dir().join(format!("qbd-shaders-{}", process::id()));
+        fs::create_dir_all(root.join("common")).unwrap();
+        fs::write(
+            root.join("common/color.glsl"),
+            "#include \"tint.glsl\"\nvec4 color() { return tint() * 0.5; }\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("common/tint.glsl"),
+            "vec4 tint() { return vec4(TINT); }\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("flat.frag"),
+            "#version 450\n#extension GL_GOOGLE_include_directive : require\n\
+             #include <common/color.glsl>\n\
+             layout(location = 0) out vec4 fragment_color;\n\
+             void main() { fragment_color = color(); }\n",
+        )
+        .unwrap();
+
+        let mut library = ShaderLibrary::new(ShaderSource::Directory(root.clone()));
+        let mut defines = Defines::new();
+        defines.insert("TINT".to_owned(), Some("0.25".to_owned()));
+        let compiled = library.compile("flat.frag", &defines).map(<[u32]>::to_vec);
+        let missing = library
+            .compile("missing.frag", &defines)
+            .map(<[u32]>::to_vec);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(!compiled.unwrap().is_empty());
+        assert!(matches!(missing, Err(ShaderError::Io { .. })));
+        assert!(matches!(
+            library.compile("flat.glsl", &defines),
+            Err(ShaderError::UnknownStage(_))
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+} push_constants;
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+
+layout(location = 0) out vec4 vertex_color;
+
+void main() {
+    vertex_color = color;
+    gl_Position = push_constants.view_projection * vec4(position, 1.0);
+    // Vulkan clip space Y points down.
+    gl_Position.y = -gl_Position.y;
+}
\ No newline at end of file
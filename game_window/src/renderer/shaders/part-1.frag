@@ -2,9 +2,28 @@
 #extension GL_ARB_separate_shader_objects : enable
 
 layout(location = 0) in vec4 vertex_color;
+#ifdef BARYCENTRIC
+layout(location = 1) in vec3 barycentric;
+#endif
 
 layout(location = 0) out vec4 fragment_color;
 
 void main() {
+#if defined(WIREFRAME)
+#ifdef BARYCENTRIC
+    // Keep only the fragments within about a pixel of an edge.
+    vec3 edge_distance = barycentric / fwidth(barycentric);
+    if (min(edge_distance.x, min(edge_distance.y, edge_distance.z)) > 1.0) {
+        discard;
+    }
+#endif
+    fragment_color = vec4(1.0);
+#elif defined(NORMALS)
+    vec3 normal = normalize(cross(dFdx(gl_FragCoord.xyz), dFdy(gl_FragCoord.xyz)));
+    fragment_color = vec4(normal * 0.5 + 0.5, 1.0);
+#elif defined(OVERDRAW)
+    fragment_color = vec4(0.1, 0.04, 0.02, 1.0);
+#else
     fragment_color = vertex_color;
+#endif
 }
\ No newline at end of file
@@ -8,6 +8,9 @@ layout(push_constant) uniform PushConstants {
 } push_constants;
 
 layout(location = 0) out vec4 vertex_color;
+#ifdef BARYCENTRIC
+layout(location = 1) out vec3 barycentric;
+#endif
 
 vec2 positions[3] = vec2[](
     vec2(0.0, -0.5),
@@ -19,4 +22,9 @@ void main() {
     vec2 pos = positions[gl_VertexIndex] * push_constants.scale;
     vertex_color = push_constants.color;
     gl_Position = vec4((pos + push_constants.pos), 0.0, 1.0);
+
+#ifdef BARYCENTRIC
+    barycentric = vec3(0.0);
+    barycentric[gl_VertexIndex % 3] = 1.0;
+#endif
 }
\ No newline at end of file
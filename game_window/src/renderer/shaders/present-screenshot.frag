@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D screenshot;
+
+layout(location = 0) in vec2 vertex_uv;
+
+layout(location = 0) out vec4 fragment_color;
+
+void main() {
+    fragment_color = texture(screenshot, vertex_uv);
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) out vec2 vertex_uv;
+
+void main() {
+    // One triangle covering the whole screen, the parts outside it are clipped.
+    vertex_uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(vertex_uv * 2.0 - 1.0, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D atlas;
+
+layout(location = 0) in vec2 vertex_uv;
+layout(location = 1) in vec4 vertex_color;
+
+layout(location = 0) out vec4 fragment_color;
+
+void main() {
+    // The atlas holds signed distances, 0.5 is the outline. Smoothing over one screen pixel
+    // keeps edges sharp at any size.
+    float distance = texture(atlas, vertex_uv).r;
+    float smoothing = fwidth(distance) * 0.5;
+    // Negative UVs mark solid rectangles. The atlas is still sampled for them so fwidth stays in
+    // uniform control flow.
+    float alpha = vertex_uv.x < 0.0 ? 1.0 : smoothstep(0.5 - smoothing, 0.5 + smoothing, distance);
+    fragment_color = vec4(vertex_color.rgb, vertex_color.a * alpha);
+}
\ No newline at end of file
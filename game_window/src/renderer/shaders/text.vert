@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+    vec2 screen_size;
+} push_constants;
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec2 vertex_uv;
+layout(location = 1) out vec4 vertex_color;
+
+void main() {
+    vertex_uv = uv;
+    vertex_color = color;
+    // Pixels from the top left, clip space Y already points down.
+    gl_Position = vec4(position / push_constants.screen_size * 2.0 - 1.0, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,270 @@
+use super::{
+    font::{Font, BASE_SIZE},
+    gpu_data::GpuPod,
+    pipeline::PipelineDesc,
+};
+use bytemuck::{Pod, Zeroable};
+use common::math::{Vec2, Vec4};
+use gfx_hal::{
+    format::Format,
+    pso::{
+        BlendState, DescriptorType, Face, ImageDescriptorType, ShaderStageFlags, VertexInputRate,
+    },
+};
+use std::mem::size_of;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// Font size in logical pixels.
+    pub size: f32,
+    pub color: Vec4,
+    pub align: Align,
+    /// Lines are wrapped at word boundaries to fit, and aligned within this width.
+    pub max_width: Option<f32>,
+}
+
+impl TextStyle {
+    pub fn new(size: f32, color: Vec4) -> TextStyle {
+        Self {
+            size,
+            color,
+            align: Align::Left,
+            max_width: None,
+        }
+    }
+
+    pub fn align(mut self, align: Align) -> TextStyle {
+        self.align = align;
+        self
+    }
+
+    pub fn max_width(mut self, max_width: f32) -> TextStyle {
+        self.max_width = Some(max_width);
+        self
+    }
+}
+
+/// Where one glyph of a layout goes, relative to the layout's top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlacedGlyph {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+}
+
+/// A string broken into lines and positioned, in logical pixels.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<PlacedGlyph>,
+    pub size: Vec2,
+}
+
+impl TextLayout {
+    pub fn new(font: &Font, text: &str, style: &TextStyle) -> TextLayout {
+        let scale = style.size / BASE_SIZE;
+        let lines: Vec<Vec<char>> = text
+            .split('\n')
+            .flat_map(|paragraph| wrap(font, paragraph, scale, style.max_width))
+            .collect();
+
+        let widths: Vec<f32> = lines
+            .iter()
+            .map(|line| line_width(font, line) * scale)
+            .collect();
+        let width = style
+            .max_width
+            .unwrap_or_else(|| widths.iter().cloned().fold(0.0, f32::max));
+        let line_height = font.line_height() * scale;
+
+        let mut glyphs = Vec::new();
+        for (index, (line, line_width)) in lines.iter().zip(&widths).enumerate() {
+            let mut x = match style.align {
+                Align::Left => 0.0,
+                Align::Center => (width - line_width) * 0.5,
+                Align::Right => width - line_width,
+            };
+            let baseline = index as f32 * line_height + font.ascent() * scale;
+
+            let mut previous = None;
+            for &character in line {
+                if let Some(previous) = previous {
+                    x += font.kerning(previous, character) * scale;
+                }
+                let glyph = font.glyph(character);
+                if glyph.size != Vec2::ZERO {
+                    let min = Vec2::new(x, baseline) + glyph.offset * scale;
+                    glyphs.push(PlacedGlyph {
+                        min,
+                        max: min + glyph.size * scale,
+                        uv_min: glyph.uv_min,
+                        uv_max: glyph.uv_max,
+                    });
+                }
+                x += glyph.advance * scale;
+                previous = Some(character);
+            }
+        }
+
+        Self {
+            glyphs,
+            size: Vec2::new(width, lines.len() as f32 * line_height),
+        }
+    }
+}
+
+/// Breaks a paragraph into lines no wider than `max_width`. Words that don't fit on a line of
+/// their own are broken between characters.
+fn wrap(font: &Font, paragraph: &str, scale: f32, max_width: Option<f32>) -> Vec<Vec<char>> {
+    let max_width = match max_width {
+        Some(max_width) => max_width / scale,
+        None => return vec![paragraph.chars().collect()],
+    };
+
+    let mut lines = Vec::new();
+    let mut line: Vec<char> = Vec::new();
+    for word in paragraph.split(' ') {
+        let word: Vec<char> = word.chars().collect();
+        let mut candidate = line.clone();
+        if !candidate.is_empty() {
+            candidate.push(' ');
+        }
+        candidate.extend(&word);
+
+        if line_width(font, &candidate) <= max_width {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for character in word {
+            line.push(character);
+            if line.len() > 1 && line_width(font, &line) > max_width {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, vec![character]));
+            }
+        }
+    }
+    lines.push(line);
+
+    lines
+}
+
+/// In base size pixels.
+fn line_width(font: &Font, line: &[char]) -> f32 {
+    let advances: f32 = line
+        .iter()
+        .map(|&character| font.glyph(character).advance)
+        .sum();
+    let kerning: f32 = line
+        .windows(2)
+        .map(|pair| font.kerning(pair[0], pair[1]))
+        .sum();
+
+    advances + kerning
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Pod, Zeroable)]
+pub struct TextVertex {
+    /// In physical pixels from the top left of the screen.
+    pub position: Vec2,
+    pub uv: Vec2,
+    pub color: Vec4,
+}
+
+impl GpuPod for TextVertex {}
+
+/// Overlay vertices with this UV are drawn in solid color instead of sampling the atlas.
+pub const SOLID_UV: Vec2 = Vec2::new(-1.0, -1.0);
+
+/// Two triangles per glyph of `layout`, placed at `origin` and scaled from logical to physical
+/// pixels by `scale_factor`.
+pub fn vertices(
+    layout: &TextLayout,
+    origin: Vec2,
+    scale_factor: f32,
+    color: Vec4,
+    out: &mut Vec<TextVertex>,
+) {
+    for glyph in &layout.glyphs {
+        quad(
+            (origin + glyph.min) * scale_factor,
+            (origin + glyph.max) * scale_factor,
+            (glyph.uv_min, glyph.uv_max),
+            color,
+            out,
+        );
+    }
+}
+
+/// Two triangles covering a solid rectangle from `min` to `max`, in logical pixels.
+pub fn rect_vertices(
+    min: Vec2,
+    max: Vec2,
+    scale_factor: f32,
+    color: Vec4,
+    out: &mut Vec<TextVertex>,
+) {
+    quad(
+        min * scale_factor,
+        max * scale_factor,
+        (SOLID_UV, SOLID_UV),
+        color,
+        out,
+    );
+}
+
+fn quad(
+    min: Vec2,
+    max: Vec2,
+    (uv_min, uv_max): (Vec2, Vec2),
+    color: Vec4,
+    out: &mut Vec<TextVertex>,
+) {
+    let corner = |x: bool, y: bool| TextVertex {
+        position: Vec2::new(if x { max.x } else { min.x }, if y { max.y } else { min.y }),
+        uv: Vec2::new(
+            if x { uv_max.x } else { uv_min.x },
+            if y { uv_max.y } else { uv_min.y },
+        ),
+        color,
+    };
+
+    out.extend_from_slice(&[
+        corner(false, false),
+        corner(false, true),
+        corner(true, false),
+        corner(true, false),
+        corner(false, true),
+        corner(true, true),
+    ]);
+}
+
+/// Pixel space overlay text and solid rectangles, sampling the font atlas at set 0, binding 0.
+/// The push constant is the screen size in physical pixels.
+pub fn pipeline() -> PipelineDesc {
+    PipelineDesc::new("text.vert", "text.frag")
+        .cull(Face::NONE)
+        .blend(BlendState::ALPHA)
+        .vertex_buffer(size_of::<TextVertex>() as u32, VertexInputRate::Vertex)
+        .attribute(0, Format::Rg32Sfloat, 0)
+        .attribute(1, Format::Rg32Sfloat, size_of::<Vec2>() as u32)
+        .attribute(2, Format::Rgba32Sfloat, 2 * size_of::<Vec2>() as u32)
+        .descriptor_set(&[(
+            0,
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Sampled { with_sampler: true },
+            },
+            ShaderStageFlags::FRAGMENT,
+        )])
+        .push_constants::<Vec2>(ShaderStageFlags::VERTEX)
+}
@@ -0,0 +1,195 @@
+use super::{
+    back,
+    buffer::{find_memory_type, HostBuffer},
+    gpu_objects::{GpuObject, GpuObjectTracker},
+};
+use gfx_hal::{
+    adapter::MemoryType,
+    buffer,
+    command::{BufferImageCopy, CommandBuffer},
+    device::Device,
+    format::{Aspects, Format, Swizzle},
+    image::{
+        Access, Extent, Filter, Kind, Layout, Offset, SamplerDesc, SubresourceLayers,
+        SubresourceRange, Tiling, Usage, ViewCapabilities, ViewKind, WrapMode,
+    },
+    memory::{Barrier, Dependencies, Properties},
+    pso::PipelineStage,
+};
+
+const COLOR_RANGE: SubresourceRange = SubresourceRange {
+    aspects: Aspects::COLOR,
+    level_start: 0,
+    level_count: Some(1),
+    layer_start: 0,
+    layer_count: Some(1),
+};
+
+/// A sampled 2D image. Its pixels are copied in by the first command buffer that calls
+/// `record_upload`.
+pub struct Texture {
+    image: <back::Backend as gfx_hal::Backend>::Image,
+    memory: <back::Backend as gfx_hal::Backend>::Memory,
+    memory_size: u64,
+    view: <back::Backend as gfx_hal::Backend>::ImageView,
+    sampler: <back::Backend as gfx_hal::Backend>::Sampler,
+    width: u32,
+    height: u32,
+    staging: Option<HostBuffer>,
+}
+
+impl Texture {
+    /// `pixels` are tightly packed rows of `format` texels.
+    pub fn new(
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        memory_types: &[MemoryType],
+        gpu_objects: &mut GpuObjectTracker,
+        (width, height): (u32, u32),
+        format: Format,
+        pixels: &[u8],
+    ) -> Texture {
+        let staging = HostBuffer::new(
+            device,
+            memory_types,
+            gpu_objects,
+            pixels.len() as u64,
+            buffer::Usage::TRANSFER_SRC,
+        );
+        staging.write(device, pixels);
+
+        unsafe {
+            let mut image = device
+                .create_image(
+                    Kind::D2(width, height, 1, 1),
+                    1,
+                    format,
+                    Tiling::Optimal,
+                    Usage::TRANSFER_DST | Usage::SAMPLED,
+                    ViewCapabilities::empty(),
+                )
+                .expect("Failed to create image");
+            let requirements = device.get_image_requirements(&image);
+            let memory_type = find_memory_type(
+                memory_types,
+                requirements.type_mask,
+                Properties::DEVICE_LOCAL,
+            )
+            .or_else(|| find_memory_type(memory_types, requirements.type_mask, Properties::empty()))
+            .expect("No memory for image");
+            let memory = device
+                .allocate_memory(memory_type, requirements.size)
+                .expect("Out of memory");
+            device
+                .bind_image_memory(&memory, 0, &mut image)
+                .expect("Failed to bind image memory");
+            let view = device
+                .create_image_view(&image, ViewKind::D2, format, Swizzle::NO, COLOR_RANGE)
+                .expect("Failed to create image view");
+            let sampler = device
+                .create_sampler(&SamplerDesc::new(Filter::Linear, WrapMode::Clamp))
+                .expect("Out of memory");
+
+            gpu_objects.created(GpuObject::Image);
+            gpu_objects.allocated(requirements.size);
+            gpu_objects.created(GpuObject::ImageView);
+            gpu_objects.created(GpuObject::Sampler);
+
+            Self {
+                image,
+                memory,
+                memory_size: requirements.size,
+                view,
+                sampler,
+                width,
+                height,
+                staging: Some(staging),
+            }
+        }
+    }
+
+    pub fn view(&self) -> &<back::Backend as gfx_hal::Backend>::ImageView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &<back::Backend as gfx_hal::Backend>::Sampler {
+        &self.sampler
+    }
+
+    /// Records copying the pixels into the image, outside of any render pass, if that hasn't
+    /// happened yet. The returned staging buffer has to live until the command buffer is done.
+    pub fn record_upload(
+        &mut self,
+        command_buffer: &mut <back::Backend as gfx_hal::Backend>::CommandBuffer,
+    ) -> Option<HostBuffer> {
+        let staging = self.staging.take()?;
+
+        unsafe {
+            command_buffer.pipeline_barrier(
+                PipelineStage::TOP_OF_PIPE..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                &[Barrier::Image {
+                    states: (Access::empty(), Layout::Undefined)
+                        ..(Access::TRANSFER_WRITE, Layout::TransferDstOptimal),
+                    target: &self.image,
+                    families: None,
+                    range: COLOR_RANGE,
+                }],
+            );
+            command_buffer.copy_buffer_to_image(
+                staging.buffer(),
+                &self.image,
+                Layout::TransferDstOptimal,
+                &[BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_width: self.width,
+                    buffer_height: self.height,
+                    image_layers: SubresourceLayers {
+                        aspects: Aspects::COLOR,
+                        level: 0,
+                        layers: 0..1,
+                    },
+                    image_offset: Offset::ZERO,
+                    image_extent: Extent {
+                        width: self.width,
+                        height: self.height,
+                        depth: 1,
+                    },
+                }],
+            );
+            command_buffer.pipeline_barrier(
+                PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
+                Dependencies::empty(),
+                &[Barrier::Image {
+                    states: (Access::TRANSFER_WRITE, Layout::TransferDstOptimal)
+                        ..(Access::SHADER_READ, Layout::ShaderReadOnlyOptimal),
+                    target: &self.image,
+                    families: None,
+                    range: COLOR_RANGE,
+                }],
+            );
+        }
+
+        Some(staging)
+    }
+
+    /// # Safety
+    ///
+    /// The GPU must be done with the texture.
+    pub unsafe fn destroy(
+        self,
+        device: &<back::Backend as gfx_hal::Backend>::Device,
+        gpu_objects: &mut GpuObjectTracker,
+    ) {
+        if let Some(staging) = self.staging {
+            staging.destroy(device, gpu_objects);
+        }
+        device.destroy_sampler(self.sampler);
+        device.destroy_image_view(self.view);
+        device.destroy_image(self.image);
+        device.free_memory(self.memory);
+        gpu_objects.destroyed(GpuObject::Sampler);
+        gpu_objects.destroyed(GpuObject::ImageView);
+        gpu_objects.destroyed(GpuObject::Image);
+        gpu_objects.freed(self.memory_size);
+    }
+}
@@ -1,57 +1,344 @@
-use crate::{global_state::GlobalState, renderer, types::EventLoop};
+use crate::{
+    global_state::GlobalState,
+    input::recording::{RecordEntry, Replay},
+    renderer::{
+        self, capture::FrameCapture, debug_overlay, draw_list::DrawList,
+        shader_library::ShaderSource,
+    },
+    state::{main_menu::MainMenu, StateStack, Transition},
+    types::EventLoop,
+};
+use common::{consts::TICKS_PER_SECOND, debug_draw, logging::LogGuard, math::Vec2, metrics};
 use gfx_hal::window::Extent2D;
 use renderer::{ResourceHolder, Resources};
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+use tracing::{debug_span, error, info};
 use winit::{
-    event::{Event, WindowEvent},
+    event::{DeviceEvent, ElementState, Event, WindowEvent},
     event_loop::ControlFlow,
 };
 
-pub fn run(global_state: GlobalState, event_loop: EventLoop) {
+pub struct RunOptions {
+    /// Drives input, ticks and frame times from a recording instead of the live devices.
+    pub replay: Option<Replay>,
+    /// Writes out every rendered frame, with time advancing by a fixed step per frame instead of
+    /// following the wall clock.
+    pub capture: Option<FrameCapture>,
+    pub shaders: ShaderSource,
+    /// Dropped last on shutdown so everything logged while exiting still reaches the file.
+    pub log_guard: LogGuard,
+}
+
+pub fn run(mut global_state: GlobalState, event_loop: EventLoop, options: RunOptions) {
+    let RunOptions {
+        mut replay,
+        capture,
+        shaders,
+        log_guard,
+    } = options;
+    let mut log_guard = Some(log_guard);
+
     let mut should_configure_swapchain = true;
-    let mut game_window = global_state.window;
-    let mut resource_holder: ResourceHolder =
-        ResourceHolder::new(&event_loop, &global_state.settings, game_window.window());
+    let mut configured_present_mode = global_state.settings.graphics().present_mode();
+    let mut configured_msaa_samples = global_state.settings.graphics().msaa_samples();
+    let mut resource_holder = Some(ResourceHolder::new(
+        &event_loop,
+        &global_state.settings,
+        global_state
+            .window
+            .window()
+            .expect("Only headless runs have no window"),
+        &global_state.font,
+        shaders,
+    ));
 
-    let start_time = std::time::Instant::now();
+    let frame_step = capture.as_ref().map(FrameCapture::frame_duration);
+    // A capture's time only moves on once the last frame made it into the capture.
+    let mut frame_submitted = true;
+    if let (Some(resource_holder), Some(capture)) = (&mut resource_holder, capture) {
+        resource_holder.0.start_capture(capture);
+    }
+
+    let start_time = Instant::now();
+    let mut frame_time = Duration::from_secs(0);
+    let mut last_frame_time = Duration::from_secs(0);
+    // Wall clock time of the last presented frame, for the frame time graph even during replays.
+    let mut last_present = Instant::now();
+    let mut ticks: u64 = 0;
+
+    let tick_duration = Duration::from_secs(1) / TICKS_PER_SECOND;
+    let mut last_update = Instant::now();
+    let mut tick_accumulator = Duration::from_secs(0);
+
+    let scale_factor = global_state.window.scale_factor();
+    global_state.input.set_scale_factor(scale_factor as f64);
+    let mut states = StateStack::new(Box::new(MainMenu::new()), &mut global_state);
 
     event_loop.run(move |event, _, control_flow| {
         match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::Resized(dims) => {
-                    game_window.update_surface_extent(Extent2D {
-                        width: dims.width,
-                        height: dims.height,
-                    });
-                    should_configure_swapchain = true;
+            Event::WindowEvent { event, .. } => {
+                // Clicking back into the window only recaptures the cursor, it shouldn't also
+                // break a block.
+                let recapture = matches!(
+                    event,
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        ..
+                    }
+                ) && global_state.capture_cursor()
+                    && !global_state.window.cursor_grabbed();
+
+                if recapture {
+                    global_state.window.set_cursor_grabbed(true);
+                } else if replay.is_none() {
+                    global_state.input.handle_window_event(&event);
                 }
-                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                    game_window.update_surface_extent(Extent2D {
-                        width: new_inner_size.width,
-                        height: new_inner_size.height,
-                    });
-                    should_configure_swapchain = true;
+
+                match event {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::Focused(focused) => {
+                        global_state
+                            .window
+                            .set_cursor_grabbed(focused && global_state.capture_cursor());
+                    }
+                    WindowEvent::Resized(dims) => {
+                        global_state.window.update_surface_extent(Extent2D {
+                            width: dims.width,
+                            height: dims.height,
+                        });
+                        should_configure_swapchain = true;
+                    }
+                    WindowEvent::ScaleFactorChanged {
+                        new_inner_size,
+                        scale_factor,
+                    } => {
+                        global_state.input.set_scale_factor(scale_factor);
+                        global_state.window.update_surface_extent(Extent2D {
+                            width: new_inner_size.width,
+                            height: new_inner_size.height,
+                        });
+                        should_configure_swapchain = true;
+                    }
+                    _ => (),
                 }
-                _ => (),
-            },
-            Event::MainEventsCleared => game_window.window().request_redraw(),
+            }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } if global_state.window.cursor_grabbed() && replay.is_none() => {
+                global_state.input.handle_mouse_motion(delta);
+            }
+            Event::MainEventsCleared => {
+                let step = frame_step.map(|step| {
+                    if std::mem::take(&mut frame_submitted) {
+                        step
+                    } else {
+                        Duration::from_secs(0)
+                    }
+                });
+                if let Some(replay) = &mut replay {
+                    // Plays back up to the next recorded frame. A capture ignores the recorded
+                    // frames and plays everything recorded up to its simulated time instead.
+                    if let Some(step) = step {
+                        frame_time += step;
+                    }
+                    let mut reached_frame = false;
+                    while !reached_frame {
+                        if step.is_some()
+                            && matches!(replay.peek(), Some(record) if record.time > frame_time)
+                        {
+                            break;
+                        }
+                        match replay.next().map(|record| record.entry) {
+                            Some(RecordEntry::Input(event)) => {
+                                global_state.input.handle_event(event)
+                            }
+                            Some(RecordEntry::Config(config)) => {
+                                global_state.input.set_config(config)
+                            }
+                            Some(RecordEntry::Tick) => {
+                                let _span = debug_span!("tick", tick = ticks).entered();
+                                let input = global_state.input.tick();
+                                if !states.update(&mut global_state, &input) {
+                                    *control_flow = ControlFlow::Exit;
+                                }
+                                ticks += 1;
+                            }
+                            Some(RecordEntry::Frame(elapsed)) => {
+                                if step.is_none() {
+                                    frame_time = elapsed;
+                                    reached_frame = true;
+                                }
+                            }
+                            None => {
+                                log_replay_finished(&global_state, ticks);
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                        }
+                    }
+                } else {
+                    let now = Instant::now();
+                    tick_accumulator += step.unwrap_or(now - last_update);
+                    last_update = now;
+
+                    while tick_accumulator >= tick_duration {
+                        tick_accumulator -= tick_duration;
+
+                        let _span = debug_span!("tick").entered();
+                        let input = global_state.input.tick();
+                        if !states.update(&mut global_state, &input) {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+
+                    frame_time = match step {
+                        Some(step) => frame_time + step,
+                        None => start_time.elapsed(),
+                    };
+                }
+
+                if let Some(window) = global_state.window.window() {
+                    window.request_redraw();
+                }
+            }
             Event::RedrawRequested(_) => {
                 // Here's where we'll perform our rendering.
 
-                let res: &mut Resources = &mut resource_holder.0;
-                res.reset_fence_and_command_pool();
-                if should_configure_swapchain {
-                    res.reconfigure_swap(game_window.surface_extent());
-                    should_configure_swapchain = false;
-                }
+                if let Some(resource_holder) = &mut resource_holder {
+                    let _span = debug_span!("frame").entered();
+                    let res: &mut Resources = &mut resource_holder.0;
+                    res.reset_fence_and_command_pool();
+                    let graphics = global_state.settings.graphics();
+                    if graphics.present_mode() != configured_present_mode
+                        || graphics.msaa_samples() != configured_msaa_samples
+                    {
+                        should_configure_swapchain = true;
+                    }
+                    if should_configure_swapchain {
+                        let present_mode = graphics.present_mode();
+                        let msaa_samples = graphics.msaa_samples();
+                        res.reconfigure_swap(
+                            global_state.window.surface_extent(),
+                            present_mode,
+                            msaa_samples,
+                        );
+                        configured_present_mode = present_mode;
+                        configured_msaa_samples = msaa_samples;
+                        should_configure_swapchain = false;
+                    }
+
+                    let mut draw_list = DrawList::new();
+                    draw_list.set_debug_mode(global_state.debug_mode);
+                    let extent = *global_state.window.surface_extent();
+                    let aspect = extent.width as f32 / extent.height.max(1) as f32;
+                    let scale_factor = global_state.window.scale_factor();
+                    draw_list.set_view_projection(
+                        global_state.camera.camera().view_projection(
+                            aspect,
+                            global_state.settings.graphics().field_of_view(),
+                        ),
+                    );
+                    draw_list.set_screen(
+                        Vec2::new(extent.width as f32, extent.height as f32) * (1.0 / scale_factor),
+                        scale_factor,
+                    );
+                    states.render(&global_state, frame_time, &mut draw_list);
+                    draw_list.add_debug_frame(
+                        debug_draw::take_frame(frame_time.saturating_sub(last_frame_time)),
+                        &global_state.font,
+                    );
+                    last_frame_time = frame_time;
+                    if global_state.debug_overlay {
+                        debug_overlay::draw(&global_state.font, &mut draw_list);
+                    }
 
-                res.render(
-                    &mut should_configure_swapchain,
-                    game_window.surface_extent(),
-                    start_time,
-                );
+                    global_state.input.record_frame(frame_time);
+                    if std::mem::take(&mut global_state.screenshot_requested) {
+                        res.request_screenshot();
+                    }
+                    frame_submitted = res.render(
+                        &mut should_configure_swapchain,
+                        global_state.window.surface_extent(),
+                        &draw_list,
+                    );
+                    if frame_submitted {
+                        let now = Instant::now();
+                        metrics::push_sample(
+                            metrics::FRAME_TIME,
+                            (now - last_present).as_secs_f32() * 1000.0,
+                        );
+                        last_present = now;
+                    }
+                }
+            }
+            Event::LoopDestroyed => {
+                shutdown(&mut global_state, &mut states, &mut resource_holder);
+                log_guard.take();
             }
             _ => (),
         }
     });
 }
+
+/// Plays a whole replay as fast as possible without a window or renderer, so it runs without a
+/// display server.
+pub fn run_headless(mut global_state: GlobalState, replay: Replay, log_guard: LogGuard) {
+    let mut states = StateStack::new(Box::new(MainMenu::new()), &mut global_state);
+    let mut ticks: u64 = 0;
+
+    for record in replay {
+        match record.entry {
+            RecordEntry::Input(event) => global_state.input.handle_event(event),
+            RecordEntry::Config(config) => global_state.input.set_config(config),
+            RecordEntry::Tick => {
+                let _span = debug_span!("tick", tick = ticks).entered();
+                let input = global_state.input.tick();
+                ticks += 1;
+                if !states.update(&mut global_state, &input) {
+                    break;
+                }
+            }
+            RecordEntry::Frame(_) => (),
+        }
+    }
+    log_replay_finished(&global_state, ticks);
+
+    shutdown(&mut global_state, &mut states, &mut None);
+    drop(log_guard);
+}
+
+fn log_replay_finished(global_state: &GlobalState, ticks: u64) {
+    info!(
+        "Replay finished after {} ticks, camera at {:?}",
+        ticks,
+        global_state.camera.camera().position
+    );
+}
+
+fn shutdown(
+    global_state: &mut GlobalState,
+    states: &mut StateStack,
+    resource_holder: &mut Option<ResourceHolder>,
+) {
+    // Leaving every state gives them the chance to save whatever they own. The world isn't kept
+    // anywhere yet, so there is no world to save; once it is, `InGame::on_exit` saves it here.
+    states.apply(Transition::Quit, global_state);
+
+    // Dropping the renderer waits for the GPU to go idle before destroying anything.
+    resource_holder.take();
+
+    if let Err(error) = global_state.save_settings() {
+        error!("Could not save settings: {}", error);
+    }
+    if let Err(error) = global_state.input.stop_recording() {
+        error!("Could not finish input recording: {}", error);
+    }
+
+    info!("Shut down");
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+}
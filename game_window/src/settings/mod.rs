@@ -0,0 +1,302 @@
+pub mod schema;
+
+use crate::{
+    camera::DEFAULT_FOV_Y_DEGREES,
+    input::{binding::InputMap, gamepad::GamepadSettings, mouse::MouseSettings},
+};
+use common::paths::data_dir;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    graphics: GraphicsSettings,
+    key_bindings: InputMap,
+    mouse: MouseSettings,
+    gamepad: GamepadSettings,
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Self {
+            graphics: GraphicsSettings::new(),
+            key_bindings: InputMap::default(),
+            mouse: MouseSettings::new(),
+            gamepad: GamepadSettings::new(),
+        }
+    }
+
+    pub fn path() -> PathBuf {
+        data_dir().join("settings.ron")
+    }
+
+    /// Loads the stored settings, falling back to the defaults when there are none or they can't
+    /// be read.
+    pub fn load() -> Settings {
+        Settings::load_from(&Settings::path())
+    }
+
+    /// A file that can't be parsed is moved aside to `settings.ron.bad`, so saving the defaults
+    /// later doesn't throw away everything a typo made unreadable.
+    fn load_from(path: &Path) -> Settings {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Settings::new(),
+            Err(error) => {
+                warn!("Could not read settings, using the defaults: {}", error);
+                return Settings::new();
+            }
+        };
+
+        let mut settings: Settings = match ron::from_str(&contents) {
+            Ok(settings) => settings,
+            Err(error) => {
+                let bad = path.with_extension("ron.bad");
+                match fs::rename(path, &bad) {
+                    Ok(()) => warn!(
+                        "Could not parse settings, moved them to {} and using the defaults: {}",
+                        bad.display(),
+                        error
+                    ),
+                    Err(rename_error) => warn!(
+                        "Could not parse settings, using the defaults: {} (moving them aside \
+                         failed: {})",
+                        error, rename_error
+                    ),
+                }
+                return Settings::new();
+            }
+        };
+
+        settings.key_bindings.add_missing_defaults();
+        for conflict in settings.key_bindings.conflicts() {
+            warn!("Key binding conflict: {}", conflict);
+        }
+
+        settings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        fs::create_dir_all(data_dir())?;
+        fs::write(Settings::path(), contents)
+    }
+
+    pub fn graphics(&self) -> &GraphicsSettings {
+        &self.graphics
+    }
+
+    pub fn graphics_mut(&mut self) -> &mut GraphicsSettings {
+        &mut self.graphics
+    }
+
+    pub fn key_bindings(&self) -> &InputMap {
+        &self.key_bindings
+    }
+
+    pub fn key_bindings_mut(&mut self) -> &mut InputMap {
+        &mut self.key_bindings
+    }
+
+    pub fn mouse(&self) -> &MouseSettings {
+        &self.mouse
+    }
+
+    pub fn mouse_mut(&mut self) -> &mut MouseSettings {
+        &mut self.mouse
+    }
+
+    pub fn gamepad(&self) -> &GamepadSettings {
+        &self.gamepad
+    }
+
+    pub fn gamepad_mut(&mut self) -> &mut GamepadSettings {
+        &mut self.gamepad
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    window_size: [u16; 2],
+    maximized: bool,
+    present_mode: PresentMode,
+    backend: GraphicsBackend,
+    field_of_view: f32,
+    msaa_samples: u8,
+}
+
+impl GraphicsSettings {
+    pub fn new() -> GraphicsSettings {
+        Self {
+            window_size: [1280, 720],
+            maximized: false,
+            present_mode: PresentMode::Mailbox,
+            backend: GraphicsBackend::compiled(),
+            field_of_view: DEFAULT_FOV_Y_DEGREES,
+            msaa_samples: 4,
+        }
+    }
+
+    /// In logical pixels.
+    pub fn window_size(&self) -> [u16; 2] {
+        self.window_size
+    }
+
+    pub fn set_window_size(&mut self, size: [u16; 2]) {
+        self.window_size = size;
+    }
+
+    pub fn maximized(&self) -> bool {
+        self.maximized
+    }
+
+    pub fn set_maximized(&mut self, maximized: bool) {
+        self.maximized = maximized;
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.present_mode = present_mode;
+    }
+
+    /// The backend the player asked for. Backends are chosen when building, so this only takes
+    /// effect with a build that includes it.
+    pub fn backend(&self) -> GraphicsBackend {
+        self.backend
+    }
+
+    pub fn set_backend(&mut self, backend: GraphicsBackend) {
+        self.backend = backend;
+    }
+
+    /// Vertical field of view in degrees.
+    pub fn field_of_view(&self) -> f32 {
+        self.field_of_view
+    }
+
+    pub fn set_field_of_view(&mut self, degrees: f32) {
+        self.field_of_view = degrees;
+    }
+
+    /// Samples per pixel for multisample anti-aliasing, one of `MSAA_SAMPLES`. The renderer
+    /// lowers it to what the device supports.
+    pub fn msaa_samples(&self) -> u8 {
+        self.msaa_samples
+    }
+
+    pub fn set_msaa_samples(&mut self, samples: u8) {
+        self.msaa_samples = samples;
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> GraphicsSettings {
+        GraphicsSettings::new()
+    }
+}
+
+pub const MSAA_SAMPLES: [u8; 4] = [1, 2, 4, 8];
+
+/// How finished frames are handed to the screen. Falls back to FIFO where the surface doesn't
+/// support the choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PresentMode {
+    /// Waits for vertical sync, never tears.
+    Fifo,
+    /// Replaces the queued frame with newer ones, lower latency without tearing.
+    Mailbox,
+    /// Presents right away, can tear.
+    Immediate,
+}
+
+impl PresentMode {
+    pub const ALL: [PresentMode; 3] = [
+        PresentMode::Fifo,
+        PresentMode::Mailbox,
+        PresentMode::Immediate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PresentMode::Fifo => "V-sync",
+            PresentMode::Mailbox => "Mailbox",
+            PresentMode::Immediate => "Immediate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GraphicsBackend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+    Empty,
+}
+
+impl GraphicsBackend {
+    /// The backend this build renders with.
+    pub fn compiled() -> GraphicsBackend {
+        if cfg!(feature = "vulkan") {
+            GraphicsBackend::Vulkan
+        } else if cfg!(feature = "metal") {
+            GraphicsBackend::Metal
+        } else if cfg!(feature = "dx12") {
+            GraphicsBackend::Dx12
+        } else if cfg!(feature = "gl") {
+            GraphicsBackend::Gl
+        } else {
+            GraphicsBackend::Empty
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GraphicsBackend::Vulkan => "Vulkan",
+            GraphicsBackend::Metal => "Metal",
+            GraphicsBackend::Dx12 => "DirectX 12",
+            GraphicsBackend::Gl => "OpenGL",
+            GraphicsBackend::Empty => "None",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn unparseable_settings_are_moved_aside_instead_of_lost() {
+        let directory = env::temp_dir().join(format!("qbd-settings-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("settings.ron");
+        fs::write(&path, "(key_bindings: {jump: [oops").unwrap();
+
+        let settings = Settings::load_from(&path);
+        let bad = fs::read_to_string(directory.join("settings.ron.bad"));
+        let moved = !path.exists();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(settings, Settings::new());
+        assert_eq!(bad.unwrap(), "(key_bindings: {jump: [oops");
+        assert!(moved);
+    }
+}
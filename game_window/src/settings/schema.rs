@@ -0,0 +1,418 @@
+use super::{GraphicsBackend, PresentMode, Settings, MSAA_SAMPLES};
+
+/// Window sizes offered in logical pixels, besides whatever size the window has now.
+const WINDOW_SIZES: [[u16; 2]; 6] = [
+    [1280, 720],
+    [1366, 768],
+    [1600, 900],
+    [1920, 1080],
+    [2560, 1440],
+    [3840, 2160],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingValue {
+    Bool(bool),
+    Number(f32),
+    /// Index into the field's options.
+    Choice(usize),
+}
+
+impl SettingValue {
+    pub fn as_bool(self) -> bool {
+        match self {
+            SettingValue::Bool(value) => value,
+            other => panic!("Expected a toggle value, got {:?}", other),
+        }
+    }
+
+    pub fn as_number(self) -> f32 {
+        match self {
+            SettingValue::Number(value) => value,
+            other => panic!("Expected a number value, got {:?}", other),
+        }
+    }
+
+    pub fn as_choice(self) -> usize {
+        match self {
+            SettingValue::Choice(value) => value,
+            other => panic!("Expected a choice value, got {:?}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SettingKind {
+    Toggle,
+    Slider {
+        min: f32,
+        max: f32,
+    },
+    /// Names of the options, which can depend on the current settings.
+    Choice {
+        options: fn(&Settings) -> Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SettingSection {
+    Graphics,
+    Mouse,
+    Gamepad,
+}
+
+impl SettingSection {
+    pub const ALL: [SettingSection; 3] = [
+        SettingSection::Graphics,
+        SettingSection::Mouse,
+        SettingSection::Gamepad,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SettingSection::Graphics => "Graphics",
+            SettingSection::Mouse => "Mouse",
+            SettingSection::Gamepad => "Gamepad",
+        }
+    }
+}
+
+/// One editable setting, with everything needed to build a widget for it. Labels are unique
+/// across every section.
+#[derive(Debug, Clone, Copy)]
+pub struct SettingField {
+    pub section: SettingSection,
+    pub label: &'static str,
+    pub kind: SettingKind,
+    /// Changes only take effect the next time the game starts.
+    pub requires_restart: bool,
+    pub get: fn(&Settings) -> SettingValue,
+    pub set: fn(&mut Settings, SettingValue),
+}
+
+impl SettingField {
+    /// Whether `before` and `after` disagree on this field.
+    pub fn differs(&self, before: &Settings, after: &Settings) -> bool {
+        (self.get)(before) != (self.get)(after)
+    }
+}
+
+/// Every setting that can be changed from the options screen, keybinds aside.
+pub fn fields() -> Vec<SettingField> {
+    vec![
+        SettingField {
+            section: SettingSection::Graphics,
+            label: "Window size",
+            kind: SettingKind::Choice {
+                options: |settings| {
+                    window_sizes(settings)
+                        .iter()
+                        .map(|[width, height]| format!("{}x{}", width, height))
+                        .collect()
+                },
+            },
+            requires_restart: false,
+            get: |settings| {
+                let current = settings.graphics.window_size;
+                let index = window_sizes(settings)
+                    .iter()
+                    .position(|size| *size == current)
+                    .unwrap_or(0);
+                SettingValue::Choice(index)
+            },
+            set: |settings, value| {
+                let sizes = window_sizes(settings);
+                if let Some(size) = sizes.get(value.as_choice()) {
+                    settings.graphics.window_size = *size;
+                }
+            },
+        },
+        SettingField {
+            section: SettingSection::Graphics,
+            label: "Maximized",
+            kind: SettingKind::Toggle,
+            requires_restart: false,
+            get: |settings| SettingValue::Bool(settings.graphics.maximized),
+            set: |settings, value| settings.graphics.maximized = value.as_bool(),
+        },
+        SettingField {
+            section: SettingSection::Graphics,
+            label: "Present mode",
+            kind: SettingKind::Choice {
+                options: |_| {
+                    PresentMode::ALL
+                        .iter()
+                        .map(|mode| mode.name().to_owned())
+                        .collect()
+                },
+            },
+            requires_restart: false,
+            get: |settings| {
+                let current = settings.graphics.present_mode;
+                let index = PresentMode::ALL
+                    .iter()
+                    .position(|mode| *mode == current)
+                    .unwrap_or(0);
+                SettingValue::Choice(index)
+            },
+            set: |settings, value| {
+                if let Some(mode) = PresentMode::ALL.get(value.as_choice()) {
+                    settings.graphics.present_mode = *mode;
+                }
+            },
+        },
+        SettingField {
+            section: SettingSection::Graphics,
+            label: "Backend",
+            kind: SettingKind::Choice {
+                options: |settings| {
+                    backends(settings)
+                        .iter()
+                        .map(|&backend| {
+                            if backend == GraphicsBackend::compiled() {
+                                backend.name().to_owned()
+                            } else {
+                                format!("{} (requires a build with it)", backend.name())
+                            }
+                        })
+                        .collect()
+                },
+            },
+            // Backends are chosen when building, restarting the same build changes nothing.
+            requires_restart: false,
+            get: |settings| {
+                let current = settings.graphics.backend;
+                let index = backends(settings)
+                    .iter()
+                    .position(|backend| *backend == current)
+                    .unwrap_or(0);
+                SettingValue::Choice(index)
+            },
+            set: |settings, value| {
+                if let Some(backend) = backends(settings).get(value.as_choice()) {
+                    settings.graphics.backend = *backend;
+                }
+            },
+        },
+        SettingField {
+            section: SettingSection::Graphics,
+            label: "Field of view",
+            kind: SettingKind::Slider {
+                min: 50.0,
+                max: 110.0,
+            },
+            requires_restart: false,
+            get: |settings| SettingValue::Number(settings.graphics.field_of_view),
+            set: |settings, value| settings.graphics.field_of_view = value.as_number(),
+        },
+        SettingField {
+            section: SettingSection::Graphics,
+            label: "Anti-aliasing",
+            kind: SettingKind::Choice {
+                options: |_| {
+                    MSAA_SAMPLES
+                        .iter()
+                        .map(|&samples| match samples {
+                            1 => "Off".to_owned(),
+                            samples => format!("{}x MSAA", samples),
+                        })
+                        .collect()
+                },
+            },
+            requires_restart: false,
+            get: |settings| {
+                let current = settings.graphics.msaa_samples;
+                // Hand edited counts show as the closest choice below them.
+                let index = MSAA_SAMPLES
+                    .iter()
+                    .rposition(|&samples| samples <= current)
+                    .unwrap_or(0);
+                SettingValue::Choice(index)
+            },
+            set: |settings, value| {
+                if let Some(samples) = MSAA_SAMPLES.get(value.as_choice()) {
+                    settings.graphics.msaa_samples = *samples;
+                }
+            },
+        },
+        SettingField {
+            section: SettingSection::Mouse,
+            label: "Mouse sensitivity",
+            kind: SettingKind::Slider { min: 0.1, max: 5.0 },
+            requires_restart: false,
+            get: |settings| SettingValue::Number(settings.mouse.sensitivity()),
+            set: |settings, value| settings.mouse.set_sensitivity(value.as_number()),
+        },
+        SettingField {
+            section: SettingSection::Mouse,
+            label: "Invert Y",
+            kind: SettingKind::Toggle,
+            requires_restart: false,
+            get: |settings| SettingValue::Bool(settings.mouse.invert_y()),
+            set: |settings, value| settings.mouse.set_invert_y(value.as_bool()),
+        },
+        SettingField {
+            section: SettingSection::Gamepad,
+            label: "Gamepad sensitivity",
+            kind: SettingKind::Slider { min: 0.1, max: 5.0 },
+            requires_restart: false,
+            get: |settings| SettingValue::Number(settings.gamepad.sensitivity()),
+            set: |settings, value| settings.gamepad.set_sensitivity(value.as_number()),
+        },
+        SettingField {
+            section: SettingSection::Gamepad,
+            label: "Stick dead zone",
+            kind: SettingKind::Slider { min: 0.0, max: 0.5 },
+            requires_restart: false,
+            get: |settings| SettingValue::Number(settings.gamepad.stick_dead_zone()),
+            set: |settings, value| settings.gamepad.set_stick_dead_zone(value.as_number()),
+        },
+        SettingField {
+            section: SettingSection::Gamepad,
+            label: "Trigger dead zone",
+            kind: SettingKind::Slider { min: 0.0, max: 0.5 },
+            requires_restart: false,
+            get: |settings| SettingValue::Number(settings.gamepad.trigger_dead_zone()),
+            set: |settings, value| settings.gamepad.set_trigger_dead_zone(value.as_number()),
+        },
+        SettingField {
+            section: SettingSection::Gamepad,
+            label: "Response curve",
+            kind: SettingKind::Slider { min: 1.0, max: 4.0 },
+            requires_restart: false,
+            get: |settings| SettingValue::Number(settings.gamepad.response_curve()),
+            set: |settings, value| settings.gamepad.set_response_curve(value.as_number()),
+        },
+    ]
+}
+
+fn window_sizes(settings: &Settings) -> Vec<[u16; 2]> {
+    let mut sizes = WINDOW_SIZES.to_vec();
+    if !sizes.contains(&settings.graphics.window_size) {
+        sizes.push(settings.graphics.window_size);
+        sizes.sort_unstable_by_key(|[width, height]| (*width, *height));
+    }
+    sizes
+}
+
+/// Only the backend built in can actually be used, a different stored choice stays selectable
+/// so it isn't lost.
+fn backends(settings: &Settings) -> Vec<GraphicsBackend> {
+    let mut backends = vec![GraphicsBackend::compiled()];
+    if settings.graphics.backend != GraphicsBackend::compiled() {
+        backends.push(settings.graphics.backend);
+    }
+    backends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(label: &str) -> SettingField {
+        fields()
+            .into_iter()
+            .find(|field| field.label == label)
+            .unwrap_or_else(|| panic!("No field called {}", label))
+    }
+
+    fn options(field: &SettingField, settings: &Settings) -> Vec<String> {
+        match field.kind {
+            SettingKind::Choice { options } => options(settings),
+            kind => panic!("{} is a {:?}, not a choice", field.label, kind),
+        }
+    }
+
+    #[test]
+    fn setting_what_was_read_changes_nothing() {
+        let mut settings = Settings::new();
+        settings.graphics.window_size = [1000, 700];
+        settings.graphics.msaa_samples = 8;
+        settings.mouse.set_sensitivity(2.5);
+
+        for field in fields() {
+            let mut written = settings.clone();
+            (field.set)(&mut written, (field.get)(&settings));
+            assert_eq!(written, settings, "{}", field.label);
+        }
+    }
+
+    #[test]
+    fn every_value_reads_back() {
+        for field in fields() {
+            let mut settings = Settings::new();
+            let values = match field.kind {
+                SettingKind::Toggle => vec![SettingValue::Bool(true), SettingValue::Bool(false)],
+                SettingKind::Slider { min, max } => vec![
+                    SettingValue::Number(min),
+                    SettingValue::Number((min + max) / 2.0),
+                    SettingValue::Number(max),
+                ],
+                SettingKind::Choice { options } => (0..options(&settings).len())
+                    .map(SettingValue::Choice)
+                    .collect(),
+            };
+
+            for value in values {
+                (field.set)(&mut settings, value);
+                assert_eq!((field.get)(&settings), value, "{}", field.label);
+            }
+        }
+    }
+
+    #[test]
+    fn a_non_standard_window_size_is_offered_in_order() {
+        let window_size = field("Window size");
+        let mut settings = Settings::new();
+        settings.graphics.window_size = [1440, 900];
+
+        let offered = options(&window_size, &settings);
+        assert_eq!(offered.len(), WINDOW_SIZES.len() + 1);
+        assert_eq!(offered[2], "1440x900");
+        assert_eq!((window_size.get)(&settings), SettingValue::Choice(2));
+
+        // Indices refer to the options shown, which still include the custom size.
+        (window_size.set)(&mut settings, SettingValue::Choice(3));
+        assert_eq!(settings.graphics.window_size, [1600, 900]);
+        assert_eq!(options(&window_size, &settings).len(), WINDOW_SIZES.len());
+        assert_eq!((window_size.get)(&settings), SettingValue::Choice(2));
+    }
+
+    #[test]
+    fn hand_edited_sample_counts_round_down() {
+        let msaa = field("Anti-aliasing");
+        let mut settings = Settings::new();
+
+        for &(samples, choice) in &[(0, 0), (1, 0), (3, 1), (4, 2), (7, 2), (16, 3)] {
+            settings.graphics.msaa_samples = samples;
+            assert_eq!(
+                (msaa.get)(&settings),
+                SettingValue::Choice(choice),
+                "{} samples",
+                samples
+            );
+        }
+    }
+
+    #[test]
+    fn backends_not_in_this_build_say_so() {
+        let backend = field("Backend");
+        let mut settings = Settings::new();
+        let other = if GraphicsBackend::compiled() == GraphicsBackend::Metal {
+            GraphicsBackend::Vulkan
+        } else {
+            GraphicsBackend::Metal
+        };
+        settings.graphics.backend = other;
+
+        assert!(!backend.requires_restart);
+        assert_eq!(
+            options(&backend, &settings),
+            vec![
+                GraphicsBackend::compiled().name().to_owned(),
+                format!("{} (requires a build with it)", other.name()),
+            ]
+        );
+        assert_eq!((backend.get)(&settings), SettingValue::Choice(1));
+    }
+}
@@ -0,0 +1,140 @@
+use super::{paused::Paused, AppState, Transition};
+use crate::{
+    camera::Camera,
+    global_state::GlobalState,
+    input::{action::Action, TickInput},
+    renderer::{debug_mode::DebugMode, draw_list::DrawList},
+};
+use common::{
+    debug_draw,
+    math::{Aabb, BlockPos, ChunkPos, Vec2, Vec3, Vec4, CHUNK_SIZE},
+    metrics,
+};
+use std::time::Duration;
+
+pub struct InGame;
+
+impl AppState for InGame {
+    fn name(&self) -> &'static str {
+        "in_game"
+    }
+
+    fn on_enter(&mut self, global_state: &mut GlobalState) {
+        global_state.set_capture_cursor(true);
+    }
+
+    fn on_exit(&mut self, global_state: &mut GlobalState) {
+        global_state.set_capture_cursor(false);
+        for name in [metrics::POSITION, metrics::CHUNK, metrics::FACING].iter() {
+            metrics::remove(name);
+        }
+    }
+
+    fn update(&mut self, global_state: &mut GlobalState, input: &TickInput) -> Transition {
+        if input.pressed(Action::Pause) {
+            return Transition::Push(Box::new(Paused::new()));
+        }
+
+        global_state.camera.update(input);
+        publish_metrics(global_state.camera.camera());
+        Transition::None
+    }
+
+    fn render(&self, global_state: &GlobalState, elapsed: Duration, draw_list: &mut DrawList) {
+        if global_state.debug_mode == DebugMode::ChunkBoundaries {
+            chunk_boundaries(global_state.camera.camera().position);
+        }
+
+        let anim = elapsed.as_secs_f32().sin() * 0.5 + 0.5;
+
+        let small = Vec2::splat(0.33);
+
+        // Red triangle
+        draw_list.triangle(Vec4::new(1.0, 0.0, 0.0, 1.0), Vec2::new(-0.5, -0.5), small);
+        // Green triangle
+        draw_list.triangle(Vec4::new(0.0, 1.0, 0.0, 1.0), Vec2::new(0.0, -0.5), small);
+        // Blue triangle
+        draw_list.triangle(Vec4::new(0.0, 0.0, 1.0, 1.0), Vec2::new(0.5, -0.5), small);
+        // Blue <-> cyan animated triangle
+        draw_list.triangle(Vec4::new(0.0, anim, 1.0, 1.0), Vec2::new(-0.5, 0.5), small);
+        // Down <-> up animated triangle
+        draw_list.triangle(Vec4::ONE, Vec2::new(0.0, 0.5 - anim * 0.5), small);
+        // Small <-> big animated triangle
+        draw_list.triangle(
+            Vec4::ONE,
+            Vec2::new(0.5, 0.5),
+            Vec2::splat(0.33 + anim * 0.33),
+        );
+
+        crosshair(draw_list);
+    }
+}
+
+/// A plus in the middle of the screen, where blocks are placed and broken.
+fn crosshair(draw_list: &mut DrawList) {
+    const LENGTH: f32 = 16.0;
+    const THICKNESS: f32 = 2.0;
+
+    let center = draw_list.screen_size() * 0.5;
+    let color = Vec4::new(1.0, 1.0, 1.0, 0.8);
+    draw_list.rect(
+        center - Vec2::new(LENGTH, THICKNESS) * 0.5,
+        Vec2::new(LENGTH, THICKNESS),
+        color,
+    );
+    draw_list.rect(
+        center - Vec2::new(THICKNESS, LENGTH) * 0.5,
+        Vec2::new(THICKNESS, LENGTH),
+        color,
+    );
+}
+
+/// Outlines the chunk the camera is in and the ones around it.
+fn chunk_boundaries(camera: Vec3) {
+    let center = ChunkPos::from_world(camera);
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let chunk = ChunkPos::new(center.x + x, center.y + y, center.z + z);
+                let min = chunk.origin().to_world();
+                let color = if chunk == center {
+                    Vec4::new(1.0, 1.0, 0.0, 1.0)
+                } else {
+                    Vec4::new(0.2, 0.6, 1.0, 0.5)
+                };
+
+                debug_draw::aabb(
+                    &Aabb::new(min, min + Vec3::splat(CHUNK_SIZE as f32)),
+                    color,
+                    debug_draw::ONE_FRAME,
+                );
+            }
+        }
+    }
+}
+
+fn publish_metrics(camera: &Camera) {
+    let position = camera.position;
+    let chunk = BlockPos::from_world(position).chunk();
+    metrics::set_text(
+        metrics::POSITION,
+        format!("{:.2} {:.2} {:.2}", position.x, position.y, position.z),
+    );
+    metrics::set_text(
+        metrics::CHUNK,
+        format!("{} {} {}", chunk.x, chunk.y, chunk.z),
+    );
+
+    // Yaw 0 looks down -Z, turning towards +X.
+    let quarter = (camera.yaw / std::f32::consts::FRAC_PI_2).round() as i32;
+    let direction = ["north", "east", "south", "west"][quarter.rem_euclid(4) as usize];
+    metrics::set_text(
+        metrics::FACING,
+        format!(
+            "{} (yaw {:.0}, pitch {:.0})",
+            direction,
+            camera.yaw.to_degrees(),
+            camera.pitch.to_degrees()
+        ),
+    );
+}
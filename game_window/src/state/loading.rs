@@ -0,0 +1,58 @@
+use super::{in_game::InGame, AppState, Transition};
+use crate::{global_state::GlobalState, input::TickInput, renderer::draw_list::DrawList};
+use common::{
+    consts::TICKS_PER_SECOND,
+    math::{Vec2, Vec4},
+};
+use std::time::Duration;
+
+// There is no world to generate yet, so loading only lasts a fixed time. Generation gets a
+// `world_generation` span once it exists.
+const LOADING_TICKS: u32 = TICKS_PER_SECOND;
+
+pub struct Loading {
+    ticks: u32,
+}
+
+impl Loading {
+    pub fn new() -> Loading {
+        Self { ticks: 0 }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.ticks as f32 / LOADING_TICKS as f32
+    }
+}
+
+impl Default for Loading {
+    fn default() -> Loading {
+        Loading::new()
+    }
+}
+
+impl AppState for Loading {
+    fn name(&self) -> &'static str {
+        "loading"
+    }
+
+    fn update(&mut self, _global_state: &mut GlobalState, _input: &TickInput) -> Transition {
+        self.ticks += 1;
+
+        if self.ticks >= LOADING_TICKS {
+            Transition::Switch(Box::new(InGame))
+        } else {
+            Transition::None
+        }
+    }
+
+    fn render(&self, _global_state: &GlobalState, _elapsed: Duration, draw_list: &mut DrawList) {
+        let progress = self.progress();
+
+        draw_list.set_clear_color([0.05, 0.05, 0.1, 1.0]);
+        draw_list.triangle(
+            Vec4::new(0.2, 0.8, 0.2, 1.0),
+            Vec2::new(-1.0 + progress, 0.0),
+            Vec2::new(progress * 2.0, 0.1),
+        );
+    }
+}
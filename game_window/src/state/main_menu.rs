@@ -0,0 +1,86 @@
+use super::{loading::Loading, settings::SettingsScreen, AppState, Transition};
+use crate::{
+    global_state::GlobalState,
+    input::TickInput,
+    renderer::{
+        draw_list::DrawList,
+        text::{Align, TextStyle},
+    },
+    ui::{UiContext, UiFrame},
+};
+use common::{
+    consts::APP_NAME,
+    math::{Vec2, Vec3, Vec4},
+};
+use std::time::Duration;
+
+const MENU_WIDTH: f32 = 260.0;
+
+pub struct MainMenu {
+    ui: UiContext,
+    ui_frame: UiFrame,
+}
+
+impl MainMenu {
+    pub fn new() -> MainMenu {
+        Self {
+            ui: UiContext::default(),
+            ui_frame: UiFrame::default(),
+        }
+    }
+}
+
+impl Default for MainMenu {
+    fn default() -> MainMenu {
+        MainMenu::new()
+    }
+}
+
+impl AppState for MainMenu {
+    fn name(&self) -> &'static str {
+        "main_menu"
+    }
+
+    fn on_enter(&mut self, global_state: &mut GlobalState) {
+        global_state.set_capture_cursor(false);
+    }
+
+    fn update(&mut self, global_state: &mut GlobalState, input: &TickInput) -> Transition {
+        let screen = global_state.window.screen_size();
+        let mut ui = self.ui.begin(&global_state.font, input.ui(), screen);
+        ui.begin_panel("", screen * 0.5, Vec2::splat(0.5), MENU_WIDTH);
+        let play = ui.button("Play");
+        let settings = ui.button("Settings");
+        let quit = ui.button("Quit");
+        ui.end();
+        self.ui_frame = ui.finish();
+
+        if play {
+            Transition::Switch(Box::new(Loading::new()))
+        } else if settings {
+            Transition::Push(Box::new(SettingsScreen::new()))
+        } else if quit {
+            Transition::Quit
+        } else {
+            Transition::None
+        }
+    }
+
+    fn render(&self, global_state: &GlobalState, elapsed: Duration, draw_list: &mut DrawList) {
+        let pulse = elapsed.as_secs_f32().sin() * 0.1 + 0.9;
+
+        draw_list.set_clear_color([0.05, 0.05, 0.1, 1.0]);
+        draw_list.triangle(Vec3::splat(pulse).extend(1.0), Vec2::ZERO, Vec2::splat(0.5));
+
+        let screen = draw_list.screen_size();
+        draw_list.text(
+            &global_state.font,
+            APP_NAME,
+            Vec2::new(0.0, screen.y * 0.1),
+            &TextStyle::new(64.0, Vec4::ONE)
+                .align(Align::Center)
+                .max_width(screen.x),
+        );
+        self.ui_frame.draw(draw_list);
+    }
+}
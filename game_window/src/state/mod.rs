@@ -0,0 +1,129 @@
+pub mod in_game;
+pub mod loading;
+pub mod main_menu;
+pub mod paused;
+pub mod settings;
+
+use crate::{
+    global_state::GlobalState,
+    input::{action::Action, TickInput},
+    renderer::draw_list::DrawList,
+};
+use std::time::Duration;
+use tracing::{debug, info};
+
+pub enum Transition {
+    None,
+    /// Puts a state on top of the current one, which stops updating until it is uncovered.
+    Push(Box<dyn AppState>),
+    Pop,
+    /// Replaces the current state.
+    Switch(Box<dyn AppState>),
+    Quit,
+}
+
+pub trait AppState {
+    fn name(&self) -> &'static str;
+
+    fn on_enter(&mut self, _global_state: &mut GlobalState) {}
+
+    fn on_exit(&mut self, _global_state: &mut GlobalState) {}
+
+    /// Runs once per fixed tick, only for the state on top of the stack.
+    fn update(&mut self, global_state: &mut GlobalState, input: &TickInput) -> Transition;
+
+    fn render(&self, _global_state: &GlobalState, _elapsed: Duration, _draw_list: &mut DrawList) {}
+
+    /// Overlays are drawn over the state below them instead of replacing it.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+pub struct StateStack {
+    states: Vec<Box<dyn AppState>>,
+}
+
+impl StateStack {
+    pub fn new(mut initial: Box<dyn AppState>, global_state: &mut GlobalState) -> StateStack {
+        debug!("Entering {}", initial.name());
+        initial.on_enter(global_state);
+        Self {
+            states: vec![initial],
+        }
+    }
+
+    pub fn current(&self) -> Option<&dyn AppState> {
+        self.states.last().map(|state| state.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Updates the top state and applies the transition it asks for. Returns false once the stack
+    /// is empty and the application should exit.
+    pub fn update(&mut self, global_state: &mut GlobalState, input: &TickInput) -> bool {
+        if input.pressed(Action::CycleDebugMode) {
+            global_state.debug_mode = global_state.debug_mode.next();
+            info!("Debug mode: {}", global_state.debug_mode.name());
+        }
+        if input.pressed(Action::ToggleDebugOverlay) {
+            global_state.debug_overlay = !global_state.debug_overlay;
+        }
+        if input.pressed(Action::Screenshot) {
+            global_state.screenshot_requested = true;
+        }
+
+        let transition = match self.states.last_mut() {
+            Some(state) => state.update(global_state, input),
+            None => return false,
+        };
+        self.apply(transition, global_state);
+
+        !self.states.is_empty()
+    }
+
+    pub fn render(&self, global_state: &GlobalState, elapsed: Duration, draw_list: &mut DrawList) {
+        // Start from the topmost state that covers the whole screen.
+        let first_visible = self
+            .states
+            .iter()
+            .rposition(|state| !state.is_overlay())
+            .unwrap_or(0);
+
+        for state in &self.states[first_visible..] {
+            state.render(global_state, elapsed, draw_list);
+        }
+    }
+
+    pub fn apply(&mut self, transition: Transition, global_state: &mut GlobalState) {
+        match transition {
+            Transition::None => (),
+            Transition::Push(mut state) => {
+                debug!("Entering {}", state.name());
+                state.on_enter(global_state);
+                self.states.push(state);
+            }
+            Transition::Pop => self.pop(global_state),
+            Transition::Switch(mut state) => {
+                self.pop(global_state);
+                debug!("Entering {}", state.name());
+                state.on_enter(global_state);
+                self.states.push(state);
+            }
+            Transition::Quit => {
+                while !self.states.is_empty() {
+                    self.pop(global_state);
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self, global_state: &mut GlobalState) {
+        if let Some(mut state) = self.states.pop() {
+            debug!("Leaving {}", state.name());
+            state.on_exit(global_state);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use super::{settings::SettingsScreen, AppState, Transition};
+use crate::{
+    global_state::GlobalState,
+    input::{action::Action, TickInput},
+    renderer::draw_list::DrawList,
+    ui::{UiContext, UiFrame},
+};
+use common::math::{Vec2, Vec4};
+use std::time::Duration;
+
+const MENU_WIDTH: f32 = 260.0;
+
+/// Freezes the game underneath and gives the cursor back until unpaused.
+pub struct Paused {
+    ui: UiContext,
+    ui_frame: UiFrame,
+}
+
+impl Paused {
+    pub fn new() -> Paused {
+        Self {
+            ui: UiContext::default(),
+            ui_frame: UiFrame::default(),
+        }
+    }
+}
+
+impl Default for Paused {
+    fn default() -> Paused {
+        Paused::new()
+    }
+}
+
+impl AppState for Paused {
+    fn name(&self) -> &'static str {
+        "paused"
+    }
+
+    fn on_enter(&mut self, global_state: &mut GlobalState) {
+        global_state.set_capture_cursor(false);
+    }
+
+    fn on_exit(&mut self, global_state: &mut GlobalState) {
+        global_state.set_capture_cursor(true);
+    }
+
+    fn update(&mut self, global_state: &mut GlobalState, input: &TickInput) -> Transition {
+        let screen = global_state.window.screen_size();
+        let mut ui = self.ui.begin(&global_state.font, input.ui(), screen);
+        ui.begin_panel("Paused", screen * 0.5, Vec2::splat(0.5), MENU_WIDTH);
+        let resume = ui.button("Resume");
+        let settings = ui.button("Settings");
+        let quit = ui.button("Quit game");
+        ui.end();
+        self.ui_frame = ui.finish();
+
+        if quit {
+            Transition::Quit
+        } else if settings {
+            Transition::Push(Box::new(SettingsScreen::new()))
+        } else if resume || input.pressed(Action::Pause) {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+
+    fn render(&self, _global_state: &GlobalState, _elapsed: Duration, draw_list: &mut DrawList) {
+        // A single oversized triangle dims the whole screen.
+        draw_list.triangle(Vec4::new(0.0, 0.0, 0.0, 0.6), Vec2::ZERO, Vec2::splat(8.0));
+        self.ui_frame.draw(draw_list);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,344 @@
+use super::{AppState, Transition};
+use crate::{
+    crash,
+    global_state::GlobalState,
+    input::{
+        action::Action,
+        binding::{Binding, Trigger},
+        scancode, InputConfig, TickInput,
+    },
+    renderer::draw_list::DrawList,
+    settings::{
+        schema::{self, SettingField, SettingKind, SettingSection, SettingValue},
+        Settings,
+    },
+    ui::{input::UiInput, theme::Theme, Ui, UiContext, UiFrame},
+};
+use common::math::Vec2;
+use std::time::Duration;
+use tracing::error;
+
+const PANEL_WIDTH: f32 = 640.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Section(SettingSection),
+    KeyBindings,
+}
+
+impl Tab {
+    fn name(self) -> &'static str {
+        match self {
+            Tab::Section(section) => section.name(),
+            Tab::KeyBindings => "Key bindings",
+        }
+    }
+}
+
+/// Which bindings of an action a binding button edits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+    KeyboardMouse,
+    Gamepad,
+}
+
+impl Device {
+    fn owns(self, trigger: Trigger) -> bool {
+        let gamepad = matches!(
+            trigger,
+            Trigger::GamepadButton(_) | Trigger::GamepadAxis(..)
+        );
+        gamepad == (self == Device::Gamepad)
+    }
+}
+
+/// Edits a copy of the settings, with a widget for every field of the settings schema. Nothing
+/// takes effect until the changes are applied.
+pub struct SettingsScreen {
+    draft: Settings,
+    fields: Vec<SettingField>,
+    tab: Tab,
+    /// The action and device waiting for the next press to bind.
+    capturing: Option<(Action, Device)>,
+    /// Applied fields that only take effect after a restart.
+    restart_required: Vec<&'static str>,
+    message: Option<String>,
+    ui: UiContext,
+    ui_frame: UiFrame,
+}
+
+impl SettingsScreen {
+    pub fn new() -> SettingsScreen {
+        Self {
+            draft: Settings::new(),
+            fields: schema::fields(),
+            tab: Tab::Section(SettingSection::Graphics),
+            capturing: None,
+            restart_required: Vec::new(),
+            message: None,
+            // Tighter than the default so every key binding fits on a 720p screen.
+            ui: UiContext::new(Theme {
+                padding: Vec2::new(10.0, 3.0),
+                spacing: 3.0,
+                ..Theme::default()
+            }),
+            ui_frame: UiFrame::default(),
+        }
+    }
+
+    fn apply(&mut self, global_state: &mut GlobalState) {
+        let previous = std::mem::replace(&mut global_state.settings, self.draft.clone());
+        for field in &self.fields {
+            if field.requires_restart
+                && field.differs(&previous, &self.draft)
+                && !self.restart_required.contains(&field.label)
+            {
+                self.restart_required.push(field.label);
+            }
+        }
+
+        // Present mode and field of view are read from the settings every frame.
+        global_state.input.set_config(InputConfig {
+            key_bindings: self.draft.key_bindings().clone(),
+            mouse: *self.draft.mouse(),
+            gamepad: *self.draft.gamepad(),
+        });
+        global_state.window.apply_settings(self.draft.graphics());
+
+        crash::set_settings(&global_state.settings);
+        if let Err(error) = global_state.save_settings() {
+            error!("Could not save settings: {}", error);
+        }
+        self.message = None;
+    }
+
+    /// Binds the first press from the device being captured, Escape cancels.
+    fn capture(&mut self, input: &TickInput) {
+        let (action, device) = match self.capturing {
+            Some(capturing) => capturing,
+            None => return,
+        };
+        let trigger = match input
+            .triggers()
+            .iter()
+            .copied()
+            .find(|trigger| *trigger == Trigger::Key(scancode::ESCAPE) || device.owns(*trigger))
+        {
+            Some(trigger) => trigger,
+            None => return,
+        };
+        self.capturing = None;
+        if trigger == Trigger::Key(scancode::ESCAPE) {
+            return;
+        }
+
+        let map = self.draft.key_bindings_mut();
+        let replaced: Vec<Binding> = map
+            .bindings_for(action)
+            .iter()
+            .copied()
+            .filter(|binding| device.owns(binding.trigger))
+            .collect();
+        for binding in replaced {
+            map.unbind(action, binding);
+        }
+        let binding = Binding::new(trigger);
+        self.message = map
+            .rebind(action, binding)
+            .map(|previous| format!("{} was unbound from {}", binding, action_label(previous)));
+    }
+
+    fn fields(&mut self, ui: &mut Ui, section: SettingSection) {
+        for field in self.fields.iter().filter(|field| field.section == section) {
+            let label = if field.requires_restart {
+                format!("{} (requires restart)", field.label)
+            } else {
+                field.label.to_owned()
+            };
+
+            let value = (field.get)(&self.draft);
+            let changed = match field.kind {
+                SettingKind::Toggle => {
+                    let mut value = value.as_bool();
+                    ui.checkbox(&label, &mut value)
+                        .then_some(SettingValue::Bool(value))
+                }
+                SettingKind::Slider { min, max } => {
+                    let mut value = value.as_number();
+                    ui.slider(&label, &mut value, min..=max)
+                        .then_some(SettingValue::Number(value))
+                }
+                SettingKind::Choice { options } => {
+                    let mut value = value.as_choice();
+                    ui.dropdown(&label, &options(&self.draft), &mut value)
+                        .then_some(SettingValue::Choice(value))
+                }
+            };
+            if let Some(value) = changed {
+                (field.set)(&mut self.draft, value);
+            }
+        }
+    }
+
+    fn key_bindings(&mut self, ui: &mut Ui) {
+        ui.begin_columns(3);
+        ui.label("Action");
+        ui.label("Keyboard and mouse");
+        ui.label("Gamepad");
+        ui.end();
+
+        for action in Action::ALL.iter().copied() {
+            ui.push_id(action.name());
+            ui.begin_columns(3);
+            ui.label(&action_label(action));
+            for device in [Device::KeyboardMouse, Device::Gamepad].iter().copied() {
+                let text = if self.capturing == Some((action, device)) {
+                    "Press to bind, Escape cancels".to_owned()
+                } else {
+                    let bindings: Vec<String> = self
+                        .draft
+                        .key_bindings()
+                        .bindings_for(action)
+                        .iter()
+                        .filter(|binding| device.owns(binding.trigger))
+                        .map(Binding::to_string)
+                        .collect();
+                    if bindings.is_empty() {
+                        "Unbound".to_owned()
+                    } else {
+                        bindings.join(", ")
+                    }
+                };
+
+                // The text changes with the binding, the ID shouldn't.
+                ui.push_id(match device {
+                    Device::KeyboardMouse => "keyboard_mouse",
+                    Device::Gamepad => "gamepad",
+                });
+                if ui.button(&text) {
+                    self.capturing = Some((action, device));
+                    self.message = None;
+                }
+                ui.pop_id();
+            }
+            ui.end();
+            ui.pop_id();
+        }
+
+        if ui.button("Reset bindings") {
+            self.draft.key_bindings_mut().reset_all();
+        }
+    }
+}
+
+impl Default for SettingsScreen {
+    fn default() -> SettingsScreen {
+        SettingsScreen::new()
+    }
+}
+
+impl AppState for SettingsScreen {
+    fn name(&self) -> &'static str {
+        "settings"
+    }
+
+    fn on_enter(&mut self, global_state: &mut GlobalState) {
+        self.draft = global_state.settings.clone();
+    }
+
+    fn update(&mut self, global_state: &mut GlobalState, input: &TickInput) -> Transition {
+        // While capturing, presses only go to the binding and the UI just follows the cursor.
+        let capturing = self.capturing.is_some();
+        self.capture(input);
+        let ui_input = if capturing {
+            UiInput {
+                cursor: input.ui().cursor,
+                ..UiInput::new()
+            }
+        } else {
+            input.ui().clone()
+        };
+
+        let screen = global_state.window.screen_size();
+        let mut context = std::mem::take(&mut self.ui);
+        let mut ui = context.begin(&global_state.font, &ui_input, screen);
+        // Hung from the top, so opening a dropdown or switching tabs only moves what's below.
+        let anchor = Vec2::new(screen.x * 0.5, screen.y * 0.05);
+        ui.begin_panel("Settings", anchor, Vec2::new(0.5, 0.0), PANEL_WIDTH);
+
+        let tabs = [
+            Tab::Section(SettingSection::Graphics),
+            Tab::Section(SettingSection::Mouse),
+            Tab::Section(SettingSection::Gamepad),
+            Tab::KeyBindings,
+        ];
+        ui.begin_columns(tabs.len());
+        for tab in tabs.iter().copied() {
+            if ui.button(tab.name()) {
+                self.tab = tab;
+            }
+        }
+        ui.end();
+
+        ui.heading(self.tab.name());
+        match self.tab {
+            Tab::Section(section) => self.fields(&mut ui, section),
+            Tab::KeyBindings => self.key_bindings(&mut ui),
+        }
+
+        ui.space(ui.theme().spacing);
+        if let Some(message) = &self.message {
+            ui.label(message);
+        }
+        if self.draft != global_state.settings {
+            ui.label("Unapplied changes");
+        }
+        if !self.restart_required.is_empty() {
+            ui.label(&format!(
+                "Restart the game to apply: {}",
+                self.restart_required.join(", ")
+            ));
+        }
+
+        ui.begin_columns(4);
+        let apply = ui.button("Apply");
+        let revert = ui.button("Revert");
+        let defaults = ui.button("Defaults");
+        let back = ui.button("Back");
+        ui.end();
+        ui.end();
+        self.ui_frame = ui.finish();
+        self.ui = context;
+
+        if apply {
+            self.apply(global_state);
+        } else if revert {
+            self.draft = global_state.settings.clone();
+            self.message = None;
+        } else if defaults {
+            self.draft = Settings::new();
+            self.message = None;
+        }
+
+        if back || (!capturing && input.pressed(Action::Pause)) {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+
+    fn render(&self, _global_state: &GlobalState, _elapsed: Duration, draw_list: &mut DrawList) {
+        draw_list.set_clear_color([0.05, 0.05, 0.1, 1.0]);
+        self.ui_frame.draw(draw_list);
+    }
+}
+
+/// `move_forward` becomes "Move forward".
+fn action_label(action: Action) -> String {
+    let name = action.name().replace('_', " ");
+    let mut characters = name.chars();
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => name,
+    }
+}
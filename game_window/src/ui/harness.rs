@@ -0,0 +1,341 @@
+use super::{
+    input::{UiInput, UiKey},
+    Ui, UiContext, UiFrame, WidgetInfo,
+};
+use crate::renderer::font::Font;
+use common::math::Vec2;
+
+/// Drives a UI without a window or renderer. Synthetic input is queued up, `run` builds a frame
+/// from it, and the widget tree that frame produced can then be inspected.
+pub struct UiHarness {
+    context: UiContext,
+    font: Font,
+    screen_size: Vec2,
+    input: UiInput,
+    frame: UiFrame,
+}
+
+impl UiHarness {
+    pub fn new(context: UiContext, font: Font, screen_size: Vec2) -> UiHarness {
+        Self {
+            context,
+            font,
+            screen_size,
+            input: UiInput::new(),
+            frame: UiFrame::default(),
+        }
+    }
+
+    pub fn context(&self) -> &UiContext {
+        &self.context
+    }
+
+    /// The frame built by the last `run`.
+    pub fn frame(&self) -> &UiFrame {
+        &self.frame
+    }
+
+    /// Builds one frame with the input queued since the previous one.
+    pub fn run<F: FnOnce(&mut Ui)>(&mut self, build: F) -> &UiFrame {
+        let input = self.input.take_frame();
+        let mut ui = self.context.begin(&self.font, &input, self.screen_size);
+        build(&mut ui);
+        self.frame = ui.finish();
+        &self.frame
+    }
+
+    /// The widget labelled `label` in the last frame.
+    ///
+    /// # Panics
+    ///
+    /// If there is no such widget.
+    pub fn widget(&self, label: &str) -> &WidgetInfo {
+        self.frame
+            .find(label)
+            .unwrap_or_else(|| panic!("No widget labelled {:?} in the last UI frame", label))
+    }
+
+    pub fn move_cursor(&mut self, position: Option<Vec2>) {
+        self.input.cursor = position;
+    }
+
+    /// Moves the cursor to the center of the widget labelled `label` in the last frame.
+    pub fn hover(&mut self, label: &str) {
+        let center = self.widget(label).rect.center();
+        self.move_cursor(Some(center));
+    }
+
+    pub fn press(&mut self) {
+        self.input.set_pointer(true);
+    }
+
+    pub fn release(&mut self) {
+        self.input.set_pointer(false);
+    }
+
+    /// Presses and releases over the widget labelled `label`, within a single frame.
+    pub fn click(&mut self, label: &str) {
+        self.hover(label);
+        self.press();
+        self.release();
+    }
+
+    pub fn key(&mut self, key: UiKey) {
+        self.input.keys.push(key);
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        self.input.text.push_str(text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{WidgetKind, WidgetValue};
+
+    const MODES: [&str; 3] = ["Easy", "Normal", "Hard"];
+
+    #[derive(Default)]
+    struct Form {
+        plays: u32,
+        volume: f32,
+        name: String,
+        mode: usize,
+    }
+
+    impl Form {
+        fn build(&mut self, ui: &mut Ui) {
+            ui.begin_panel("Settings", Vec2::ZERO, Vec2::ZERO, 400.0);
+            if ui.button("Play") {
+                self.plays += 1;
+            }
+            ui.slider("Volume", &mut self.volume, 0.0..=1.0);
+            ui.text_field("Name", &mut self.name);
+            ui.dropdown("Mode", &MODES, &mut self.mode);
+            ui.button("Quit");
+            ui.end();
+        }
+    }
+
+    fn harness() -> (UiHarness, Form) {
+        let mut harness = UiHarness::new(
+            UiContext::default(),
+            Font::embedded(),
+            Vec2::new(800.0, 600.0),
+        );
+        let mut form = Form::default();
+        // Widgets can only be found once a frame laid them out.
+        harness.run(|ui| form.build(ui));
+        (harness, form)
+    }
+
+    fn focused(harness: &UiHarness) -> Option<&str> {
+        harness
+            .frame()
+            .focused()
+            .map(|widget| widget.label.as_str())
+    }
+
+    #[test]
+    fn clicking_a_button_activates_and_focuses_it() {
+        let (mut harness, mut form) = harness();
+
+        harness.click("Play");
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.plays, 1);
+        assert_eq!(focused(&harness), Some("Play"));
+
+        // Releasing somewhere else cancels the click, and nothing else lights up meanwhile.
+        harness.hover("Play");
+        harness.press();
+        harness.run(|ui| form.build(ui));
+        assert!(harness.widget("Play").active);
+        harness.hover("Quit");
+        harness.run(|ui| form.build(ui));
+        assert!(!harness.widget("Quit").hovered);
+        harness.release();
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.plays, 1);
+
+        // Enter activates the focused button.
+        harness.key(UiKey::Enter);
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.plays, 2);
+
+        // Clicking empty space drops the focus.
+        harness.move_cursor(Some(Vec2::new(700.0, 500.0)));
+        harness.press();
+        harness.release();
+        harness.run(|ui| form.build(ui));
+        assert_eq!(focused(&harness), None);
+    }
+
+    #[test]
+    fn keyboard_focus_follows_layout_order() {
+        let (mut harness, mut form) = harness();
+        let mut step = |key| {
+            harness.key(key);
+            harness.run(|ui| form.build(ui));
+            focused(&harness).map(str::to_owned)
+        };
+
+        assert_eq!(step(UiKey::Tab).as_deref(), Some("Play"));
+        assert_eq!(step(UiKey::Tab).as_deref(), Some("Volume"));
+        assert_eq!(step(UiKey::Down).as_deref(), Some("Name"));
+        assert_eq!(step(UiKey::Down).as_deref(), Some("Mode"));
+        assert_eq!(step(UiKey::Tab).as_deref(), Some("Quit"));
+        assert_eq!(step(UiKey::Tab).as_deref(), Some("Play"));
+        assert_eq!(step(UiKey::BackTab).as_deref(), Some("Quit"));
+        assert_eq!(step(UiKey::Up).as_deref(), Some("Mode"));
+
+        // Without a focused widget, backwards starts at the last one.
+        let (mut harness, mut form) = self::harness();
+        harness.key(UiKey::BackTab);
+        harness.run(|ui| form.build(ui));
+        assert_eq!(focused(&harness), Some("Quit"));
+    }
+
+    #[test]
+    fn dragging_a_slider_follows_the_cursor() {
+        let (mut harness, mut form) = harness();
+        let rect = harness.widget("Volume").rect;
+        let at = |fraction: f32| {
+            Some(Vec2::new(
+                rect.min.x + rect.size().x * fraction,
+                rect.center().y,
+            ))
+        };
+        let close = |value: f32, expected: f32| (value - expected).abs() < 1e-4;
+
+        harness.move_cursor(at(0.5));
+        harness.press();
+        harness.run(|ui| form.build(ui));
+        assert!(close(form.volume, 0.5));
+
+        harness.move_cursor(at(0.25));
+        harness.run(|ui| form.build(ui));
+        assert!(close(form.volume, 0.25));
+        assert_eq!(
+            harness.widget("Volume").value,
+            WidgetValue::Number(form.volume)
+        );
+
+        // The drag keeps going outside the slider and clamps to its range.
+        harness.move_cursor(Some(Vec2::new(rect.max.x + 100.0, rect.max.y + 100.0)));
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.volume, 1.0);
+
+        harness.release();
+        harness.run(|ui| form.build(ui));
+        harness.move_cursor(at(0.0));
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.volume, 1.0);
+
+        // The focused slider steps with the arrow keys.
+        harness.key(UiKey::Left);
+        harness.key(UiKey::Left);
+        harness.run(|ui| form.build(ui));
+        assert!(close(form.volume, 0.9));
+    }
+
+    #[test]
+    fn text_fields_edit_at_the_caret() {
+        let (mut harness, mut form) = harness();
+
+        // Typing goes nowhere until the field is focused.
+        harness.type_text("ignored");
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.name, "");
+
+        harness.click("Name");
+        harness.run(|ui| form.build(ui));
+        harness.type_text("steve");
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.name, "steve");
+        assert_eq!(
+            harness.widget("Name").value,
+            WidgetValue::Text("steve".to_owned())
+        );
+
+        harness.key(UiKey::Backspace);
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.name, "stev");
+
+        harness.key(UiKey::Home);
+        harness.run(|ui| form.build(ui));
+        harness.type_text("É");
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.name, "Éstev");
+
+        // Backspace at the start does nothing, delete takes the next character.
+        harness.key(UiKey::Home);
+        harness.key(UiKey::Backspace);
+        harness.key(UiKey::Delete);
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.name, "stev");
+
+        harness.key(UiKey::End);
+        harness.run(|ui| form.build(ui));
+        harness.type_text("e!");
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.name, "steve!");
+
+        // The arrow keys move the caret instead of the focus.
+        harness.key(UiKey::Left);
+        harness.key(UiKey::Backspace);
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.name, "stev!");
+        assert_eq!(focused(&harness), Some("Name"));
+    }
+
+    #[test]
+    fn dropdowns_open_and_select() {
+        let (mut harness, mut form) = harness();
+        assert!(harness.frame().find("Hard").is_none());
+
+        harness.click("Mode");
+        harness.run(|ui| form.build(ui));
+        let options: Vec<_> = harness
+            .frame()
+            .widgets()
+            .iter()
+            .filter(|widget| widget.kind == WidgetKind::ListItem)
+            .map(|widget| (widget.label.as_str(), widget.value.clone()))
+            .collect();
+        assert_eq!(
+            options,
+            vec![
+                ("Easy", WidgetValue::Bool(true)),
+                ("Normal", WidgetValue::Bool(false)),
+                ("Hard", WidgetValue::Bool(false)),
+            ]
+        );
+
+        harness.click("Hard");
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.mode, 2);
+        assert_eq!(harness.widget("Mode").value, WidgetValue::Selected(2));
+        harness.run(|ui| form.build(ui));
+        assert!(harness.frame().find("Hard").is_none());
+        assert_eq!(focused(&harness), Some("Mode"));
+
+        // With the keyboard, up and down move through the open options instead of the focus.
+        harness.key(UiKey::Enter);
+        harness.run(|ui| form.build(ui));
+        assert!(harness.frame().find("Easy").is_some());
+        harness.key(UiKey::Up);
+        harness.key(UiKey::Up);
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.mode, 0);
+        assert_eq!(focused(&harness), Some("Mode"));
+        harness.key(UiKey::Enter);
+        harness.run(|ui| form.build(ui));
+        assert!(harness.frame().find("Easy").is_none());
+
+        // Closed, left and right step through the options.
+        harness.key(UiKey::Right);
+        harness.run(|ui| form.build(ui));
+        assert_eq!(form.mode, 1);
+    }
+}
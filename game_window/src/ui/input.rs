@@ -0,0 +1,61 @@
+use common::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// Keys the UI reacts to regardless of the action bindings. Repeats arrive as extra presses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UiKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    Tab,
+    /// Tab with shift held.
+    BackTab,
+    Enter,
+    Backspace,
+    Delete,
+    Home,
+    End,
+}
+
+/// Pointer, navigation keys and typed text for one UI frame. Built from window events by the
+/// input system, or by hand to drive the UI without a window.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiInput {
+    /// In logical pixels from the top left of the screen, `None` while the cursor is outside the
+    /// window.
+    pub cursor: Option<Vec2>,
+    pub pointer_held: bool,
+    /// The primary mouse button went down since the previous frame.
+    pub pointer_pressed: bool,
+    /// The primary mouse button went up since the previous frame. Both can happen in one frame.
+    pub pointer_released: bool,
+    pub keys: Vec<UiKey>,
+    pub text: String,
+}
+
+impl UiInput {
+    pub fn new() -> UiInput {
+        UiInput::default()
+    }
+
+    pub fn set_pointer(&mut self, held: bool) {
+        if held && !self.pointer_held {
+            self.pointer_pressed = true;
+        } else if !held && self.pointer_held {
+            self.pointer_released = true;
+        }
+        self.pointer_held = held;
+    }
+
+    /// Hands out everything gathered for this frame, keeping only the cursor and held pointer
+    /// for the next one.
+    pub fn take_frame(&mut self) -> UiInput {
+        let next = UiInput {
+            cursor: self.cursor,
+            pointer_held: self.pointer_held,
+            ..UiInput::default()
+        };
+        std::mem::replace(self, next)
+    }
+}
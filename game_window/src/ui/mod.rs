@@ -0,0 +1,711 @@
+pub mod harness;
+pub mod input;
+pub mod theme;
+mod widgets;
+
+use self::{
+    input::{UiInput, UiKey},
+    theme::Theme,
+};
+use crate::renderer::{
+    draw_list::DrawList,
+    font::Font,
+    text::{Align, TextLayout, TextStyle},
+};
+use common::math::{Vec2, Vec4};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+/// An axis aligned rectangle in logical pixels, Y pointing down.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Rect {
+    pub fn new(min: Vec2, size: Vec2) -> Rect {
+        Self {
+            min,
+            max: min + size,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.min.x
+            && point.x < self.max.x
+            && point.y >= self.min.y
+            && point.y < self.max.y
+    }
+
+    /// Moves every edge inwards by `amount`.
+    pub fn shrink(&self, amount: Vec2) -> Rect {
+        Self {
+            min: self.min + amount,
+            max: self.max - amount,
+        }
+    }
+}
+
+/// Identifies a widget across frames. Derived from its label and the panel it is in, so labels
+/// have to be unique within a panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WidgetId(u64);
+
+impl WidgetId {
+    const ROOT: WidgetId = WidgetId(0);
+
+    fn child(self, label: &str) -> WidgetId {
+        let mut hasher = DefaultHasher::new();
+        self.0.hash(&mut hasher);
+        label.hash(&mut hasher);
+        WidgetId(hasher.finish())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WidgetKind {
+    Panel,
+    Row,
+    Label,
+    Button,
+    Checkbox,
+    Slider,
+    TextField,
+    List,
+    ListItem,
+    Dropdown,
+    Heading,
+}
+
+impl WidgetKind {
+    /// Whether keyboard navigation stops at this kind of widget.
+    pub fn focusable(self) -> bool {
+        matches!(
+            self,
+            WidgetKind::Button
+                | WidgetKind::Checkbox
+                | WidgetKind::Slider
+                | WidgetKind::TextField
+                | WidgetKind::List
+                | WidgetKind::Dropdown
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WidgetValue {
+    None,
+    Bool(bool),
+    Number(f32),
+    Text(String),
+    Selected(usize),
+}
+
+/// One node of the widget tree a frame produced, as it was at the end of the frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WidgetInfo {
+    pub id: WidgetId,
+    pub kind: WidgetKind,
+    pub label: String,
+    pub rect: Rect,
+    /// Index of the containing panel, row or list in the frame's widgets.
+    pub parent: Option<usize>,
+    pub hovered: bool,
+    /// The pointer went down on the widget and hasn't been released yet.
+    pub active: bool,
+    pub focused: bool,
+    pub value: WidgetValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    Rect {
+        rect: Rect,
+        color: Vec4,
+    },
+    Text {
+        layout: TextLayout,
+        position: Vec2,
+        color: Vec4,
+    },
+}
+
+/// What building the UI produced: the widget tree for inspection and the shapes to draw.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiFrame {
+    widgets: Vec<WidgetInfo>,
+    shapes: Vec<Shape>,
+}
+
+impl UiFrame {
+    /// Every widget in the order it was added, parents before their children.
+    pub fn widgets(&self) -> &[WidgetInfo] {
+        &self.widgets
+    }
+
+    /// The first widget with `label`.
+    pub fn find(&self, label: &str) -> Option<&WidgetInfo> {
+        self.widgets.iter().find(|widget| widget.label == label)
+    }
+
+    pub fn focused(&self) -> Option<&WidgetInfo> {
+        self.widgets.iter().find(|widget| widget.focused)
+    }
+
+    pub fn children(&self, parent: usize) -> impl Iterator<Item = &WidgetInfo> + '_ {
+        self.widgets
+            .iter()
+            .filter(move |widget| widget.parent == Some(parent))
+    }
+
+    pub fn draw(&self, draw_list: &mut DrawList) {
+        for shape in &self.shapes {
+            match shape {
+                Shape::Rect { rect, color } => draw_list.rect(rect.min, rect.size(), *color),
+                Shape::Text {
+                    layout,
+                    position,
+                    color,
+                } => draw_list.text_layout(layout, *position, *color),
+            }
+        }
+    }
+}
+
+/// UI state that lives across frames: focus, the widget being dragged or pressed, and what the
+/// previous frame laid out.
+pub struct UiContext {
+    theme: Theme,
+    focused: Option<WidgetId>,
+    active: Option<WidgetId>,
+    /// Caret of the focused text field in characters, clamped to its length when used.
+    text_cursor: usize,
+    /// Focusable widgets of the previous frame in navigation order.
+    focus_order: Vec<(WidgetId, WidgetKind)>,
+    /// Panels are placed with their height from the previous frame, as it's only known once
+    /// their contents are laid out.
+    panel_heights: HashMap<WidgetId, f32>,
+    /// The dropdown showing its options.
+    open_dropdown: Option<WidgetId>,
+}
+
+impl UiContext {
+    pub fn new(theme: Theme) -> UiContext {
+        Self {
+            theme,
+            focused: None,
+            active: None,
+            text_cursor: 0,
+            focus_order: Vec::new(),
+            panel_heights: HashMap::new(),
+            open_dropdown: None,
+        }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn focused(&self) -> Option<WidgetId> {
+        self.focused
+    }
+
+    /// Starts building a frame covering a screen of `screen_size` logical pixels. Tab, and the up
+    /// and down keys outside of lists and open dropdowns, move the focus before any widget sees
+    /// them.
+    pub fn begin<'a>(
+        &'a mut self,
+        font: &'a Font,
+        input: &'a UiInput,
+        screen_size: Vec2,
+    ) -> Ui<'a> {
+        let mut keys = Vec::new();
+        for &key in &input.keys {
+            let in_list = match self.focused {
+                Some(focused) => {
+                    self.open_dropdown == Some(focused)
+                        || self.focus_order.contains(&(focused, WidgetKind::List))
+                }
+                None => false,
+            };
+            match key {
+                UiKey::Tab => self.move_focus(1),
+                UiKey::BackTab => self.move_focus(-1),
+                UiKey::Down if !in_list => self.move_focus(1),
+                UiKey::Up if !in_list => self.move_focus(-1),
+                _ => keys.push(key),
+            }
+        }
+
+        let screen = Rect::new(Vec2::ZERO, screen_size);
+        Ui {
+            context: self,
+            font,
+            input,
+            keys,
+            widgets: Vec::new(),
+            shapes: Vec::new(),
+            focus_order: Vec::new(),
+            containers: vec![Container {
+                id: WidgetId::ROOT,
+                widget: None,
+                content: screen,
+                cursor: screen.min,
+                horizontal: false,
+                columns: 0,
+                bottom: screen.min.y,
+                background: None,
+            }],
+            id_scopes: Vec::new(),
+            pointer_claimed: false,
+        }
+    }
+
+    /// Focuses the widget `step` places away from the focused one, wrapping around. Without a
+    /// focused widget, forward steps start at the first and backward ones at the last.
+    fn move_focus(&mut self, step: isize) {
+        let count = self.focus_order.len() as isize;
+        if count == 0 {
+            return;
+        }
+
+        let current = self
+            .focused
+            .and_then(|focused| self.focus_order.iter().position(|(id, _)| *id == focused));
+        let next = match current {
+            Some(index) => (index as isize + step).rem_euclid(count),
+            None if step > 0 => 0,
+            None => count - 1,
+        };
+        self.set_focus(self.focus_order[next as usize].0);
+    }
+
+    fn set_focus(&mut self, id: WidgetId) {
+        if self.focused != Some(id) {
+            self.focused = Some(id);
+            self.text_cursor = usize::MAX;
+        }
+    }
+}
+
+impl Default for UiContext {
+    fn default() -> UiContext {
+        UiContext::new(Theme::default())
+    }
+}
+
+/// A panel or row widgets are being laid out in.
+struct Container {
+    id: WidgetId,
+    widget: Option<usize>,
+    content: Rect,
+    cursor: Vec2,
+    horizontal: bool,
+    /// Rows with columns give every widget the same width, regardless of its contents.
+    columns: usize,
+    /// Lowest edge of anything laid out so far.
+    bottom: f32,
+    /// Index of the background shape, resized once the contents are known.
+    background: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Interaction {
+    hovered: bool,
+    active: bool,
+    focused: bool,
+    clicked: bool,
+}
+
+/// Builds one frame of immediate mode UI. Widgets are laid out top to bottom, or left to right
+/// inside rows, and report how they were interacted with as they are added.
+pub struct Ui<'a> {
+    context: &'a mut UiContext,
+    font: &'a Font,
+    input: &'a UiInput,
+    /// Keys left over after focus navigation, for the focused widget.
+    keys: Vec<UiKey>,
+    widgets: Vec<WidgetInfo>,
+    shapes: Vec<Shape>,
+    focus_order: Vec<(WidgetId, WidgetKind)>,
+    containers: Vec<Container>,
+    /// Container depth and ID to restore for every `push_id`.
+    id_scopes: Vec<(usize, WidgetId)>,
+    pointer_claimed: bool,
+}
+
+impl<'a> Ui<'a> {
+    pub fn theme(&self) -> &Theme {
+        &self.context.theme
+    }
+
+    pub fn font(&self) -> &Font {
+        self.font
+    }
+
+    pub fn input(&self) -> &UiInput {
+        self.input
+    }
+
+    /// A panel with `title` on top and its contents below. The panel is `width` wide and as tall
+    /// as its contents, with the point at `pivot`, from (0, 0) top left to (1, 1) bottom right,
+    /// placed at `anchor`.
+    pub fn begin_panel(&mut self, title: &str, anchor: Vec2, pivot: Vec2, width: f32) {
+        let theme = self.context.theme;
+        let id = self.id(title);
+        let height = self.context.panel_heights.get(&id).copied().unwrap_or(0.0);
+        let size = Vec2::new(width, height);
+        let rect = Rect::new(anchor - size * pivot, size);
+
+        let background = self.shapes.len();
+        self.shape_rect(rect, theme.panel);
+        let widget = self.add_widget(
+            id,
+            WidgetKind::Panel,
+            title,
+            rect,
+            Interaction::default(),
+            WidgetValue::None,
+        );
+
+        let content = Rect {
+            min: rect.min + theme.padding,
+            max: Vec2::new(rect.max.x - theme.padding.x, f32::INFINITY),
+        };
+        let mut cursor = content.min;
+        if !title.is_empty() {
+            let layout = self.layout_text(title, theme.title_size, Some(content.size().x));
+            let title_rect = Rect::new(cursor, Vec2::new(content.size().x, layout.size.y));
+            self.shape_text(layout, title_rect, Align::Center, theme.text);
+            cursor.y = title_rect.max.y + theme.spacing;
+        }
+
+        self.containers.push(Container {
+            id,
+            widget: Some(widget),
+            content,
+            cursor,
+            horizontal: false,
+            columns: 0,
+            bottom: cursor.y,
+            background: Some(background),
+        });
+    }
+
+    /// Lays out the following widgets left to right in a single row, until `end`.
+    pub fn begin_row(&mut self) {
+        self.begin_horizontal(0);
+    }
+
+    /// A row split into `count` columns of equal width, one widget each.
+    pub fn begin_columns(&mut self, count: usize) {
+        self.begin_horizontal(count);
+    }
+
+    fn begin_horizontal(&mut self, columns: usize) {
+        let rect = self.allocate(Vec2::new(0.0, self.context.theme.row_height()));
+        let parent_id = self.container().id;
+        let widget = self.add_widget(
+            parent_id,
+            WidgetKind::Row,
+            "",
+            rect,
+            Interaction::default(),
+            WidgetValue::None,
+        );
+
+        // Rows share their panel's ID so the widgets in them keep theirs when rows come and go.
+        self.containers.push(Container {
+            id: parent_id,
+            widget: Some(widget),
+            content: rect,
+            cursor: rect.min,
+            horizontal: true,
+            columns,
+            bottom: rect.max.y,
+            background: None,
+        });
+    }
+
+    /// Closes the innermost panel or row.
+    pub fn end(&mut self) {
+        assert!(self.containers.len() > 1, "UI end without a matching begin");
+        let container = self.containers.pop().unwrap();
+        let widget = match container.widget {
+            Some(widget) => widget,
+            None => return,
+        };
+
+        if self.widgets[widget].kind == WidgetKind::Panel {
+            let theme = self.context.theme;
+            let rect = &mut self.widgets[widget].rect;
+            rect.max.y = container.bottom + theme.padding.y;
+            let rect = *rect;
+            self.context
+                .panel_heights
+                .insert(container.id, rect.size().y);
+            if let Some(Shape::Rect {
+                rect: background, ..
+            }) = container
+                .background
+                .and_then(|background| self.shapes.get_mut(background))
+            {
+                *background = rect;
+            }
+        }
+    }
+
+    /// Mixes `label` into the IDs of the following widgets until `pop_id`, so widgets with the
+    /// same text, or text that changes, can be told apart.
+    pub fn push_id(&mut self, label: &str) {
+        let depth = self.containers.len();
+        let container = self.containers.last_mut().unwrap();
+        self.id_scopes.push((depth, container.id));
+        container.id = container.id.child(label);
+    }
+
+    pub fn pop_id(&mut self) {
+        let (depth, id) = self
+            .id_scopes
+            .pop()
+            .expect("UI pop_id without a matching push_id");
+        assert_eq!(
+            depth,
+            self.containers.len(),
+            "UI pop_id in a different container"
+        );
+        self.containers.last_mut().unwrap().id = id;
+    }
+
+    /// Empty space of `height`, or width inside rows.
+    pub fn space(&mut self, amount: f32) {
+        self.allocate(Vec2::splat(amount));
+    }
+
+    /// Ends the frame, returning what was built.
+    pub fn finish(self) -> UiFrame {
+        assert_eq!(
+            self.containers.len(),
+            1,
+            "UI finished with unclosed panels or rows"
+        );
+        assert!(self.id_scopes.is_empty(), "UI finished with a push_id left");
+
+        let context = self.context;
+        // Clicking anywhere without a widget drops the focus.
+        if self.input.pointer_pressed && !self.pointer_claimed {
+            context.focused = None;
+        }
+        if !self.input.pointer_held {
+            context.active = None;
+        }
+        if let Some(focused) = context.focused {
+            if !self.focus_order.iter().any(|(id, _)| *id == focused) {
+                context.focused = None;
+            }
+        }
+        if context.open_dropdown != context.focused {
+            context.open_dropdown = None;
+        }
+        context.focus_order = self.focus_order;
+
+        // Widgets can hand the focus on after earlier ones were added, so the tree is brought up
+        // to date once the frame is done.
+        let mut widgets = self.widgets;
+        for widget in &mut widgets {
+            widget.focused = widget.kind.focusable() && context.focused == Some(widget.id);
+        }
+
+        UiFrame {
+            widgets,
+            shapes: self.shapes,
+        }
+    }
+
+    fn container(&self) -> &Container {
+        self.containers.last().unwrap()
+    }
+
+    fn id(&self, label: &str) -> WidgetId {
+        self.container().id.child(label)
+    }
+
+    /// Width text wraps at, inside vertical layouts.
+    fn wrap_width(&self) -> Option<f32> {
+        let container = self.container();
+        if container.horizontal {
+            None
+        } else {
+            Some(container.content.size().x)
+        }
+    }
+
+    /// Space for the next widget. Vertical layouts stretch it to their full width, rows to their
+    /// full height.
+    fn allocate(&mut self, size: Vec2) -> Rect {
+        let spacing = self.context.theme.spacing;
+        let container = self.containers.last_mut().unwrap();
+        let rect = if container.horizontal {
+            let width = if container.columns > 0 {
+                let columns = container.columns as f32;
+                (container.content.size().x - spacing * (columns - 1.0)) / columns
+            } else {
+                size.x
+            };
+            let rect = Rect::new(
+                container.cursor,
+                Vec2::new(width, container.content.size().y),
+            );
+            container.cursor.x = rect.max.x + spacing;
+            rect
+        } else {
+            let rect = Rect::new(
+                container.cursor,
+                Vec2::new(container.content.size().x, size.y),
+            );
+            container.cursor.y = rect.max.y + spacing;
+            rect
+        };
+        container.bottom = container.bottom.max(rect.max.y);
+        rect
+    }
+
+    fn add_widget(
+        &mut self,
+        id: WidgetId,
+        kind: WidgetKind,
+        label: &str,
+        rect: Rect,
+        interaction: Interaction,
+        value: WidgetValue,
+    ) -> usize {
+        self.widgets.push(WidgetInfo {
+            id,
+            kind,
+            label: label.to_owned(),
+            rect,
+            parent: self.container().widget,
+            hovered: interaction.hovered,
+            active: interaction.active,
+            focused: interaction.focused,
+            value,
+        });
+        self.widgets.len() - 1
+    }
+
+    /// Pointer hover, press and click on `rect`. Pressing a focusable widget also focuses it.
+    fn interact(&mut self, id: WidgetId, kind: WidgetKind, rect: Rect) -> Interaction {
+        let inside = matches!(self.input.cursor, Some(cursor) if rect.contains(cursor));
+        // While another widget is being pressed or dragged nothing else lights up.
+        let hovered = inside && self.context.active.unwrap_or(id) == id;
+
+        if hovered && self.input.pointer_pressed && !self.pointer_claimed {
+            self.pointer_claimed = true;
+            self.context.active = Some(id);
+            if kind.focusable() {
+                self.context.set_focus(id);
+            }
+        }
+        if kind.focusable() {
+            self.focus_order.push((id, kind));
+        }
+
+        let active = self.context.active == Some(id);
+        Interaction {
+            hovered,
+            active,
+            focused: self.context.focused == Some(id),
+            clicked: active && inside && self.input.pointer_released,
+        }
+    }
+
+    /// Takes one press of `key`, for the focused widget to react to.
+    fn take_key(&mut self, key: UiKey) -> bool {
+        match self.keys.iter().position(|&pending| pending == key) {
+            Some(index) => {
+                self.keys.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn layout_text(&self, text: &str, size: f32, max_width: Option<f32>) -> TextLayout {
+        let mut style = TextStyle::new(size, Vec4::ONE);
+        if let Some(max_width) = max_width {
+            style = style.max_width(max_width);
+        }
+        TextLayout::new(self.font, text, &style)
+    }
+
+    fn shape_rect(&mut self, rect: Rect, color: Vec4) {
+        self.shapes.push(Shape::Rect { rect, color });
+    }
+
+    fn shape_outline(&mut self, rect: Rect, width: f32, color: Vec4) {
+        let size = rect.size();
+        self.shape_rect(Rect::new(rect.min, Vec2::new(size.x, width)), color);
+        self.shape_rect(
+            Rect::new(
+                Vec2::new(rect.min.x, rect.max.y - width),
+                Vec2::new(size.x, width),
+            ),
+            color,
+        );
+        self.shape_rect(Rect::new(rect.min, Vec2::new(width, size.y)), color);
+        self.shape_rect(
+            Rect::new(
+                Vec2::new(rect.max.x - width, rect.min.y),
+                Vec2::new(width, size.y),
+            ),
+            color,
+        );
+    }
+
+    /// Text centered vertically in `rect` and aligned horizontally within it.
+    fn shape_text(&mut self, layout: TextLayout, rect: Rect, align: Align, color: Vec4) {
+        let free = rect.size() - layout.size;
+        let x = match align {
+            Align::Left => 0.0,
+            Align::Center => free.x * 0.5,
+            Align::Right => free.x,
+        };
+        let position = rect.min + Vec2::new(x, free.y * 0.5);
+        self.shapes.push(Shape::Text {
+            layout,
+            position,
+            color,
+        });
+    }
+
+    /// The background every interactive widget has, with an outline while focused.
+    fn shape_frame(&mut self, rect: Rect, interaction: Interaction) {
+        let theme = self.context.theme;
+        let color = if interaction.active && interaction.hovered {
+            theme.widget_active
+        } else if interaction.hovered {
+            theme.widget_hovered
+        } else {
+            theme.widget
+        };
+        self.shape_rect(rect, color);
+        if interaction.focused {
+            self.shape_outline(rect, theme.outline_width, theme.focus);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use common::math::{Vec2, Vec4};
+
+/// Sizes in logical pixels and colors for every widget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub text_size: f32,
+    pub title_size: f32,
+    pub heading_size: f32,
+    /// Space between a widget's edge and its contents.
+    pub padding: Vec2,
+    /// Space between consecutive widgets.
+    pub spacing: f32,
+    /// Width of sliders and text fields when laid out in a row.
+    pub field_width: f32,
+    pub outline_width: f32,
+    pub text: Vec4,
+    pub text_muted: Vec4,
+    pub panel: Vec4,
+    pub widget: Vec4,
+    pub widget_hovered: Vec4,
+    pub widget_active: Vec4,
+    pub accent: Vec4,
+    pub focus: Vec4,
+}
+
+impl Theme {
+    pub fn new() -> Theme {
+        Self {
+            text_size: 18.0,
+            title_size: 26.0,
+            heading_size: 21.0,
+            padding: Vec2::new(10.0, 6.0),
+            spacing: 6.0,
+            field_width: 180.0,
+            outline_width: 2.0,
+            text: Vec4::new(0.95, 0.95, 0.95, 1.0),
+            text_muted: Vec4::new(0.6, 0.6, 0.65, 1.0),
+            panel: Vec4::new(0.08, 0.08, 0.12, 0.85),
+            widget: Vec4::new(0.2, 0.2, 0.28, 1.0),
+            widget_hovered: Vec4::new(0.28, 0.28, 0.38, 1.0),
+            widget_active: Vec4::new(0.15, 0.15, 0.2, 1.0),
+            accent: Vec4::new(0.3, 0.55, 0.9, 1.0),
+            focus: Vec4::new(1.0, 0.8, 0.3, 1.0),
+        }
+    }
+
+    /// Height of a single line widget such as a button.
+    pub fn row_height(&self) -> f32 {
+        self.text_size * 1.2 + self.padding.y * 2.0
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::new()
+    }
+}
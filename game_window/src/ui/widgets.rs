@@ -0,0 +1,446 @@
+use super::{input::UiKey, Interaction, Rect, Ui, WidgetId, WidgetInfo, WidgetKind, WidgetValue};
+use crate::renderer::text::Align;
+use common::math::Vec2;
+use std::ops::RangeInclusive;
+
+impl<'a> Ui<'a> {
+    /// Text wrapped to the width of the panel.
+    pub fn label(&mut self, text: &str) {
+        let theme = self.context.theme;
+        let layout = self.layout_text(text, theme.text_size, self.wrap_width());
+        let rect = self.allocate(layout.size);
+        let id = self.id(text);
+        self.add_widget(
+            id,
+            WidgetKind::Label,
+            text,
+            rect,
+            Interaction::default(),
+            WidgetValue::None,
+        );
+        self.shape_text(layout, rect, Align::Left, theme.text);
+    }
+
+    /// Returns true when clicked, or activated with enter while focused.
+    pub fn button(&mut self, text: &str) -> bool {
+        let theme = self.context.theme;
+        let layout = self.layout_text(text, theme.text_size, None);
+        let rect = self.allocate(Vec2::new(
+            layout.size.x + theme.padding.x * 2.0,
+            theme.row_height(),
+        ));
+        let id = self.id(text);
+        let interaction = self.interact(id, WidgetKind::Button, rect);
+        let activated = interaction.clicked || (interaction.focused && self.take_key(UiKey::Enter));
+
+        self.add_widget(
+            id,
+            WidgetKind::Button,
+            text,
+            rect,
+            interaction,
+            WidgetValue::None,
+        );
+        self.shape_frame(rect, interaction);
+        self.shape_text(layout, rect, Align::Center, theme.text);
+        activated
+    }
+
+    /// Returns true when `value` was toggled.
+    pub fn checkbox(&mut self, text: &str, value: &mut bool) -> bool {
+        let theme = self.context.theme;
+        let layout = self.layout_text(text, theme.text_size, None);
+        let box_size = theme.text_size;
+        let rect = self.allocate(Vec2::new(
+            box_size + layout.size.x + theme.padding.x * 3.0,
+            theme.row_height(),
+        ));
+        let id = self.id(text);
+        let interaction = self.interact(id, WidgetKind::Checkbox, rect);
+        let toggled = interaction.clicked || (interaction.focused && self.take_key(UiKey::Enter));
+        if toggled {
+            *value = !*value;
+        }
+
+        self.add_widget(
+            id,
+            WidgetKind::Checkbox,
+            text,
+            rect,
+            interaction,
+            WidgetValue::Bool(*value),
+        );
+        let check = Rect::new(
+            Vec2::new(
+                rect.min.x + theme.padding.x,
+                rect.center().y - box_size * 0.5,
+            ),
+            Vec2::splat(box_size),
+        );
+        self.shape_frame(check, interaction);
+        if *value {
+            self.shape_rect(check.shrink(Vec2::splat(box_size * 0.2)), theme.accent);
+        }
+        if interaction.focused {
+            self.shape_outline(rect, theme.outline_width, theme.focus);
+        }
+        let text_rect = Rect {
+            min: Vec2::new(check.max.x + theme.padding.x, rect.min.y),
+            max: rect.max,
+        };
+        self.shape_text(layout, text_rect, Align::Left, theme.text);
+        toggled
+    }
+
+    /// Drags `value` within `range`, or steps it by a twentieth of the range with the left and
+    /// right keys. Returns true when it changed.
+    pub fn slider(&mut self, text: &str, value: &mut f32, range: RangeInclusive<f32>) -> bool {
+        let theme = self.context.theme;
+        let (min, max) = (*range.start(), *range.end());
+        let rect = self.allocate(Vec2::new(theme.field_width, theme.row_height()));
+        let id = self.id(text);
+        let interaction = self.interact(id, WidgetKind::Slider, rect);
+
+        let mut new_value = *value;
+        if interaction.active {
+            if let Some(cursor) = self.input.cursor {
+                let fraction = ((cursor.x - rect.min.x) / rect.size().x).clamp(0.0, 1.0);
+                new_value = min + fraction * (max - min);
+            }
+        }
+        if interaction.focused {
+            let step = (max - min) / 20.0;
+            while self.take_key(UiKey::Left) {
+                new_value -= step;
+            }
+            while self.take_key(UiKey::Right) {
+                new_value += step;
+            }
+        }
+        let new_value = new_value.clamp(min, max);
+        let changed = new_value != *value;
+        *value = new_value;
+
+        self.add_widget(
+            id,
+            WidgetKind::Slider,
+            text,
+            rect,
+            interaction,
+            WidgetValue::Number(*value),
+        );
+        self.shape_frame(rect, interaction);
+        let fraction = if max > min {
+            (*value - min) / (max - min)
+        } else {
+            0.0
+        };
+        let fill = Rect {
+            min: rect.min,
+            max: Vec2::new(rect.min.x + rect.size().x * fraction, rect.max.y),
+        };
+        self.shape_rect(fill.shrink(Vec2::splat(theme.outline_width)), theme.accent);
+
+        // Wide ranges are whole numbers like degrees, narrow ones fractions like volumes.
+        let label = if max - min >= 20.0 {
+            format!("{}: {:.0}", text, value)
+        } else {
+            format!("{}: {:.2}", text, value)
+        };
+        let layout = self.layout_text(&label, theme.text_size, None);
+        self.shape_text(layout, rect, Align::Center, theme.text);
+        changed
+    }
+
+    /// A single line of editable text, showing `text` as a placeholder while empty. Returns true
+    /// when the contents changed.
+    pub fn text_field(&mut self, text: &str, value: &mut String) -> bool {
+        let theme = self.context.theme;
+        let rect = self.allocate(Vec2::new(theme.field_width, theme.row_height()));
+        let id = self.id(text);
+        let interaction = self.interact(id, WidgetKind::TextField, rect);
+
+        let mut changed = false;
+        let mut caret = self.context.text_cursor.min(value.chars().count());
+        if interaction.focused {
+            for character in self.input.text.chars() {
+                value.insert(byte_index(value, caret), character);
+                caret += 1;
+                changed = true;
+            }
+
+            for key in std::mem::take(&mut self.keys) {
+                let length = value.chars().count();
+                match key {
+                    UiKey::Left => caret = caret.saturating_sub(1),
+                    UiKey::Right => caret = (caret + 1).min(length),
+                    UiKey::Home => caret = 0,
+                    UiKey::End => caret = length,
+                    UiKey::Backspace if caret > 0 => {
+                        caret -= 1;
+                        value.remove(byte_index(value, caret));
+                        changed = true;
+                    }
+                    UiKey::Delete if caret < length => {
+                        value.remove(byte_index(value, caret));
+                        changed = true;
+                    }
+                    UiKey::Backspace | UiKey::Delete => (),
+                    other => self.keys.push(other),
+                }
+            }
+            self.context.text_cursor = caret;
+        }
+
+        self.add_widget(
+            id,
+            WidgetKind::TextField,
+            text,
+            rect,
+            interaction,
+            WidgetValue::Text(value.clone()),
+        );
+        self.shape_frame(rect, interaction);
+
+        let inner = Rect {
+            min: Vec2::new(rect.min.x + theme.padding.x, rect.min.y),
+            max: rect.max,
+        };
+        if value.is_empty() && !interaction.focused {
+            let layout = self.layout_text(text, theme.text_size, None);
+            self.shape_text(layout, inner, Align::Left, theme.text_muted);
+        } else {
+            let layout = self.layout_text(value, theme.text_size, None);
+            self.shape_text(layout, inner, Align::Left, theme.text);
+        }
+
+        if interaction.focused {
+            let before: String = value.chars().take(caret).collect();
+            let x = inner.min.x + self.layout_text(&before, theme.text_size, None).size.x;
+            let height = theme.text_size * 1.2;
+            self.shape_rect(
+                Rect::new(
+                    Vec2::new(x, rect.center().y - height * 0.5),
+                    Vec2::new(theme.outline_width, height),
+                ),
+                theme.text,
+            );
+        }
+        changed
+    }
+
+    /// One row per item with `selected` highlighted. Up and down move the selection while
+    /// focused, and the focus on past either end. Returns true when the selection changed.
+    pub fn list<S: AsRef<str>>(&mut self, text: &str, items: &[S], selected: &mut usize) -> bool {
+        let theme = self.context.theme;
+        let row_height = theme.row_height();
+        let rect = self.allocate(Vec2::new(
+            theme.field_width,
+            row_height * items.len().max(1) as f32,
+        ));
+        let id = self.id(text);
+        let interaction = self.interact(id, WidgetKind::List, rect);
+
+        let row_at = |y: f32| ((y - rect.min.y) / row_height) as usize;
+        let mut new_selected = *selected;
+        if interaction.active && self.input.pointer_pressed {
+            if let Some(cursor) = self.input.cursor {
+                new_selected = row_at(cursor.y);
+            }
+        }
+        if interaction.focused {
+            for key in std::mem::take(&mut self.keys) {
+                if self.context.focused != Some(id) {
+                    self.keys.push(key);
+                    continue;
+                }
+                match key {
+                    UiKey::Up if new_selected == 0 => self.context.move_focus(-1),
+                    UiKey::Up => new_selected -= 1,
+                    UiKey::Down if new_selected + 1 >= items.len() => self.context.move_focus(1),
+                    UiKey::Down => new_selected += 1,
+                    other => self.keys.push(other),
+                }
+            }
+        }
+        let new_selected = new_selected.min(items.len().saturating_sub(1));
+        let changed = new_selected != *selected;
+        *selected = new_selected;
+
+        let list = self.add_widget(
+            id,
+            WidgetKind::List,
+            text,
+            rect,
+            interaction,
+            WidgetValue::Selected(*selected),
+        );
+        self.shape_rect(rect, theme.widget);
+
+        let hovered_row = self
+            .input
+            .cursor
+            .filter(|_| interaction.hovered)
+            .map(|cursor| row_at(cursor.y));
+        for (index, item) in items.iter().enumerate() {
+            let row = Rect::new(
+                Vec2::new(rect.min.x, rect.min.y + row_height * index as f32),
+                Vec2::new(rect.size().x, row_height),
+            );
+            self.list_item(
+                (list, id),
+                item.as_ref(),
+                row,
+                hovered_row == Some(index),
+                index == *selected,
+            );
+        }
+        if self.context.focused == Some(id) {
+            self.shape_outline(rect, theme.outline_width, theme.focus);
+        }
+        changed
+    }
+
+    /// Shows the selected option, and every option below it while open. Clicking or enter opens
+    /// and closes it, left and right step through the options while it's closed, up and down
+    /// while it's open. Returns true when the selection changed.
+    pub fn dropdown<S: AsRef<str>>(
+        &mut self,
+        text: &str,
+        options: &[S],
+        selected: &mut usize,
+    ) -> bool {
+        let theme = self.context.theme;
+        let rect = self.allocate(Vec2::new(theme.field_width, theme.row_height()));
+        let id = self.id(text);
+        let interaction = self.interact(id, WidgetKind::Dropdown, rect);
+
+        let mut open = self.context.open_dropdown == Some(id);
+        if interaction.clicked || (interaction.focused && self.take_key(UiKey::Enter)) {
+            open = !open;
+        }
+        let mut new_selected = *selected;
+        if interaction.focused {
+            let (previous, next) = if open {
+                (UiKey::Up, UiKey::Down)
+            } else {
+                (UiKey::Left, UiKey::Right)
+            };
+            while self.take_key(previous) {
+                new_selected = new_selected.saturating_sub(1);
+            }
+            while self.take_key(next) {
+                new_selected += 1;
+            }
+        }
+        let last = options.len().saturating_sub(1);
+        new_selected = new_selected.min(last);
+
+        let dropdown = self.add_widget(
+            id,
+            WidgetKind::Dropdown,
+            text,
+            rect,
+            interaction,
+            WidgetValue::Selected(new_selected),
+        );
+        self.shape_frame(rect, interaction);
+        let inner = rect.shrink(Vec2::new(theme.padding.x, 0.0));
+        let layout = self.layout_text(text, theme.text_size, None);
+        self.shape_text(layout, inner, Align::Left, theme.text_muted);
+
+        if open {
+            for (index, option) in options.iter().enumerate() {
+                let row = self.allocate(Vec2::new(theme.field_width, theme.row_height()));
+                let hovered = matches!(self.input.cursor, Some(cursor) if row.contains(cursor));
+                if hovered && self.input.pointer_pressed && !self.pointer_claimed {
+                    self.pointer_claimed = true;
+                    new_selected = index;
+                    open = false;
+                }
+                self.shape_rect(row, theme.widget);
+                self.list_item(
+                    (dropdown, id),
+                    option.as_ref(),
+                    row,
+                    hovered,
+                    index == new_selected,
+                );
+            }
+        }
+
+        // Drawn once options had the chance to change the selection.
+        if let Some(option) = options.get(new_selected) {
+            let layout = self.layout_text(option.as_ref(), theme.text_size, None);
+            self.shape_text(layout, inner, Align::Right, theme.text);
+        }
+        self.widgets[dropdown].value = WidgetValue::Selected(new_selected);
+        if open {
+            self.context.open_dropdown = Some(id);
+        } else if self.context.open_dropdown == Some(id) {
+            self.context.open_dropdown = None;
+        }
+
+        let changed = new_selected != *selected;
+        *selected = new_selected;
+        changed
+    }
+
+    /// A section title between groups of widgets.
+    pub fn heading(&mut self, text: &str) {
+        let theme = self.context.theme;
+        let layout = self.layout_text(text, theme.heading_size, self.wrap_width());
+        let rect = self.allocate(layout.size);
+        let id = self.id(text);
+        self.add_widget(
+            id,
+            WidgetKind::Heading,
+            text,
+            rect,
+            Interaction::default(),
+            WidgetValue::None,
+        );
+        self.shape_text(layout, rect, Align::Left, theme.text);
+    }
+
+    /// One option of a list or dropdown, highlighted when selected.
+    fn list_item(
+        &mut self,
+        (parent, parent_id): (usize, WidgetId),
+        text: &str,
+        rect: Rect,
+        hovered: bool,
+        selected: bool,
+    ) {
+        let theme = self.context.theme;
+        self.widgets.push(WidgetInfo {
+            id: parent_id.child(text),
+            kind: WidgetKind::ListItem,
+            label: text.to_owned(),
+            rect,
+            parent: Some(parent),
+            hovered,
+            active: false,
+            focused: false,
+            value: WidgetValue::Bool(selected),
+        });
+
+        if selected {
+            self.shape_rect(rect, theme.accent);
+        } else if hovered {
+            self.shape_rect(rect, theme.widget_hovered);
+        }
+        let layout = self.layout_text(text, theme.text_size, None);
+        let inner = Rect {
+            min: Vec2::new(rect.min.x + theme.padding.x, rect.min.y),
+            max: rect.max,
+        };
+        self.shape_text(layout, inner, Align::Left, theme.text);
+    }
+}
+
+fn byte_index(text: &str, character: usize) -> usize {
+    text.char_indices()
+        .nth(character)
+        .map_or(text.len(), |(index, _)| index)
+}
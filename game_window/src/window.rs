@@ -1,19 +1,21 @@
 use crate::{
     renderer::calc_logical_and_physical_window_size,
-    settings::Settings,
+    settings::{GraphicsSettings, Settings},
     types::{EventLoop, LogicalSize, PhysicalSize},
 };
-use common::consts::APP_NAME;
+use common::{build_info, consts::APP_NAME, math::Vec2};
 use gfx_hal::window::Extent2D;
 use winit::window::{Window, WindowBuilder};
 
 // So the settings can be serialized easily
 pub struct GameWindow {
-    window: Window,
+    /// Missing in headless runs, which keep the size from the settings and nothing else.
+    window: Option<Window>,
     logical_size: LogicalSize,
     physical_size: PhysicalSize,
     maximized: bool,
     surface_extent: Extent2D,
+    cursor_grabbed: bool,
 }
 
 impl GameWindow {
@@ -25,7 +27,7 @@ impl GameWindow {
         let maximized = settings.graphics().maximized();
 
         let window_builder = WindowBuilder::new()
-            .with_title(APP_NAME)
+            .with_title(format!("{} {}", APP_NAME, build_info::VERSION))
             .with_inner_size(logical_size)
             .with_maximized(maximized);
 
@@ -39,17 +41,36 @@ impl GameWindow {
         };
 
         let this = Self {
-            window,
+            window: Some(window),
             logical_size,
             physical_size,
             maximized,
             surface_extent,
+            cursor_grabbed: false,
         };
         (this, event_loop)
     }
 
-    pub fn window(&self) -> &Window {
-        &self.window
+    /// A window that is never opened, so headless replays don't need a display server.
+    pub fn headless(settings: &Settings) -> GameWindow {
+        let logical_size: LogicalSize = settings.graphics().window_size().into();
+        let physical_size: PhysicalSize = logical_size.to_physical(1.0);
+
+        Self {
+            window: None,
+            logical_size,
+            physical_size,
+            maximized: settings.graphics().maximized(),
+            surface_extent: Extent2D {
+                width: physical_size.width,
+                height: physical_size.height,
+            },
+            cursor_grabbed: false,
+        }
+    }
+
+    pub fn window(&self) -> Option<&Window> {
+        self.window.as_ref()
     }
 
     pub fn logical_size(&self) -> &LogicalSize {
@@ -64,10 +85,66 @@ impl GameWindow {
         self.maximized
     }
 
+    /// Physical pixels per logical pixel on the monitor the window is on.
+    pub fn scale_factor(&self) -> f32 {
+        self.window
+            .as_ref()
+            .map_or(1.0, |window| window.scale_factor() as f32)
+    }
+
+    /// Size of the drawable area in logical pixels.
+    pub fn screen_size(&self) -> Vec2 {
+        match &self.window {
+            Some(window) => {
+                let size = window.inner_size().to_logical::<f32>(window.scale_factor());
+                Vec2::new(size.width, size.height)
+            }
+            None => Vec2::new(
+                self.logical_size.width as f32,
+                self.logical_size.height as f32,
+            ),
+        }
+    }
+
     pub fn surface_extent(&mut self) -> &mut Extent2D {
         &mut self.surface_extent
     }
     pub fn update_surface_extent(&mut self, extent: Extent2D) {
         self.surface_extent = extent
     }
+
+    /// Resizes and maximizes the window to match changed settings. The resize event that follows
+    /// reconfigures the swapchain.
+    pub fn apply_settings(&mut self, settings: &GraphicsSettings) {
+        let [width, height] = settings.window_size();
+        let logical_size = LogicalSize::new(u32::from(width), u32::from(height));
+        if logical_size != self.logical_size {
+            if let Some(window) = &self.window {
+                window.set_inner_size(logical_size);
+            }
+            self.logical_size = logical_size;
+            self.physical_size = logical_size.to_physical(f64::from(self.scale_factor()));
+        }
+        if settings.maximized() != self.maximized {
+            if let Some(window) = &self.window {
+                window.set_maximized(settings.maximized());
+            }
+            self.maximized = settings.maximized();
+        }
+    }
+
+    pub fn cursor_grabbed(&self) -> bool {
+        self.cursor_grabbed
+    }
+
+    /// Locks and hides the cursor for mouse look, or gives it back.
+    pub fn set_cursor_grabbed(&mut self, grabbed: bool) {
+        // Some platforms refuse to grab, mouse look still works there, the cursor just isn't
+        // confined to the window.
+        if let Some(window) = &self.window {
+            let _ = window.set_cursor_grab(grabbed);
+            window.set_cursor_visible(!grabbed);
+        }
+        self.cursor_grabbed = grabbed;
+    }
 }
@@ -0,0 +1,120 @@
+use common::build_info::{GIT_COMMIT, PROTOCOL_VERSION, VERSION};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::{debug, instrument, warn};
+
+/// First message each side sends, used to turn away clients and servers that can't understand
+/// each other before anything else is exchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    pub protocol_version: u32,
+    pub version: String,
+    pub git_commit: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolMismatch {
+    pub local: Handshake,
+    pub remote: Handshake,
+}
+
+impl fmt::Display for ProtocolMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "protocol version {} ({} {}) can't talk to protocol version {} ({} {})",
+            self.local.protocol_version,
+            self.local.version,
+            self.local.git_commit,
+            self.remote.protocol_version,
+            self.remote.version,
+            self.remote.git_commit
+        )
+    }
+}
+
+impl std::error::Error for ProtocolMismatch {}
+
+impl Handshake {
+    pub fn current() -> Handshake {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            version: VERSION.to_owned(),
+            git_commit: GIT_COMMIT.to_owned(),
+        }
+    }
+
+    /// Only the protocol version has to match, builds from different commits are fine as long as
+    /// the protocol didn't change between them.
+    #[instrument(
+        name = "handshake",
+        skip(self, remote),
+        fields(remote.version = %remote.version, remote.git_commit = %remote.git_commit)
+    )]
+    pub fn check(&self, remote: &Handshake) -> Result<(), ProtocolMismatch> {
+        if self.protocol_version == remote.protocol_version {
+            debug!("Accepted protocol version {}", remote.protocol_version);
+            Ok(())
+        } else {
+            let mismatch = ProtocolMismatch {
+                local: self.clone(),
+                remote: remote.clone(),
+            };
+            warn!("Rejected handshake: {}", mismatch);
+            Err(mismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake(protocol_version: u32, version: &str, git_commit: &str) -> Handshake {
+        Handshake {
+            protocol_version,
+            version: version.to_owned(),
+            git_commit: git_commit.to_owned(),
+        }
+    }
+
+    #[test]
+    fn accepts_the_same_protocol_from_other_builds() {
+        let local = handshake(3, "0.2.0", "3f2a9c1");
+        assert_eq!(local.check(&local), Ok(()));
+        assert_eq!(local.check(&handshake(3, "0.1.0", "0b1d2e3")), Ok(()));
+    }
+
+    #[test]
+    fn rejects_other_protocols_either_way() {
+        let local = handshake(3, "0.2.0", "3f2a9c1");
+        for remote_version in [2, 4].iter() {
+            let remote = handshake(*remote_version, "0.2.0", "3f2a9c1");
+            assert_eq!(
+                local.check(&remote),
+                Err(ProtocolMismatch {
+                    local: local.clone(),
+                    remote: remote.clone(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn mismatches_name_both_builds() {
+        let local = handshake(3, "0.2.0", "3f2a9c1");
+        let remote = handshake(2, "0.1.0", "0b1d2e3");
+        let message = local.check(&remote).unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "protocol version 3 (0.2.0 3f2a9c1) can't talk to protocol version 2 (0.1.0 0b1d2e3)"
+        );
+    }
+
+    #[test]
+    fn current_matches_the_build() {
+        let current = Handshake::current();
+        assert_eq!(current.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(current.check(&Handshake::current()), Ok(()));
+    }
+}
@@ -1 +1 @@
-
+pub mod handshake;